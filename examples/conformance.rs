@@ -0,0 +1,12 @@
+//! Writes the ABI test vectors from `serval::conformance` to stdout as JSON lines, one vector per
+//! line.
+
+use std::io::{self, Write};
+
+fn main() -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for vector in serval::conformance::vectors() {
+        writeln!(out, "{}", vector.to_json())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,367 @@
+//! Procedural macros for the Serval SDK. These expand into calls to `serval::__private`, so they
+//! should be used through the re-exports in the `serval` crate rather than depending on this
+//! crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Error, FnArg, Ident, ItemFn, LitStr, Type};
+
+/// Turns a function into the job's entrypoint, exported to the host as `serval_main`.
+///
+/// The function may take no arguments or the job's input as a `Vec<u8>`, `&[u8]` or
+/// `serval::Typed<T>`, and may return `Vec<u8>`, `()`, `serval::Typed<T>`, or a `Result` of any
+/// of those whose error converts into `serval::GuestError`. The return value becomes the job's
+/// output, unless the job already published one with `serval::job::set_output`, and the job is
+/// marked as complete. Errors are reported to the host and the job is marked as failed.
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    if !attr.is_empty() {
+        return Error::new_spanned(
+            proc_macro2::TokenStream::from(attr),
+            "#[serval::main] takes no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    expand_export(func, "serval_main".to_string(), quote!(run_job)).into()
+}
+
+/// Exports an additional function to the host, using the same framing as `#[serval::main]`. The
+/// export is named after the function unless a name is given with `#[serval::export(name = "...")]`.
+#[proc_macro_attribute]
+pub fn export(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    let mut export_name = func.sig.ident.to_string();
+    if !attr.is_empty() {
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("name") {
+                export_name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unsupported #[serval::export] argument"))
+            }
+        });
+        parse_macro_input!(attr with parser);
+    }
+
+    expand_export(func, export_name, quote!(run_entrypoint)).into()
+}
+
+/// Emits the function itself plus an `extern "C"` wrapper exported as `export_name` that frames its
+/// input and output via `serval::__private::run_entrypoint` (or `run_job`, as given by `runner`).
+fn expand_export(
+    func: ItemFn,
+    export_name: String,
+    runner: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let name = &func.sig.ident;
+    let call = match func.sig.inputs.iter().collect::<Vec<_>>().as_slice() {
+        [] => quote!(|_input: ::std::vec::Vec<u8>| #name()),
+        [FnArg::Typed(arg)] if matches!(*arg.ty, Type::Reference(_)) => {
+            quote!(|input: ::std::vec::Vec<u8>| #name(&input))
+        }
+        [FnArg::Typed(_)] => quote!(|input| #name(input)),
+        _ => {
+            return Error::new_spanned(
+                &func.sig.inputs,
+                "exported functions take at most one argument: their input",
+            )
+            .to_compile_error();
+        }
+    };
+    let wrapper = format_ident!("__serval_export_{}", sanitize(&export_name));
+
+    quote! {
+        #func
+
+        #[doc(hidden)]
+        #[export_name = #export_name]
+        pub extern "C" fn #wrapper(input_ptr: usize) -> isize {
+            ::serval::__private::#runner(input_ptr, #call)
+        }
+    }
+}
+
+/// Export names can contain characters that aren't valid in identifiers.
+fn sanitize(export_name: &str) -> Ident {
+    let sanitized: String = export_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Ident::new(&sanitized, Span::call_site())
+}
+
+/// Turns a function into a test that runs against a fresh mock host:
+///
+/// ```ignore
+/// #[serval::test]
+/// fn caches_profiles(host: &serval::mock::TestHost) {
+///     ...
+///     host.assert_called_with("kv", b"profile/alice");
+/// }
+/// ```
+///
+/// The function may take no arguments or the `serval::mock::TestHost`, by value or by reference,
+/// and may be `async`, in which case it's run with `serval::block_on`. It may return anything
+/// `#[test]` accepts. Attributes such as `#[should_panic]` apply to the test as usual. The mock
+/// host is reset before the body runs and again once it's done, whether it passed or panicked.
+/// Requires the `mock-host` feature.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut func = parse_macro_input!(item as ItemFn);
+    if !attr.is_empty() {
+        return Error::new_spanned(
+            proc_macro2::TokenStream::from(attr),
+            "#[serval::test] takes no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let name = &func.sig.ident;
+    let host = match func.sig.inputs.iter().collect::<Vec<_>>().as_slice() {
+        [] => quote!(),
+        [FnArg::Typed(arg)] if matches!(*arg.ty, Type::Reference(_)) => quote!(&__serval_host),
+        [FnArg::Typed(_)] => quote!(__serval_host),
+        _ => {
+            return Error::new_spanned(
+                &func.sig.inputs,
+                "#[serval::test] functions take at most one argument: the TestHost",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    let call = match func.sig.asyncness {
+        Some(_) => quote!(::serval::block_on(#name(#host))),
+        None => quote!(#name(#host)),
+    };
+
+    // The attributes belong to the test; the function itself moves inside it, unchanged.
+    let attrs = std::mem::take(&mut func.attrs);
+    let vis = &func.vis;
+    let output = &func.sig.output;
+    quote! {
+        #(#attrs)*
+        #[::core::prelude::v1::test]
+        #vis fn #name() #output {
+            #func
+
+            let __serval_host = ::serval::mock::TestHost::new();
+            #call
+        }
+    }
+    .into()
+}
+
+/// Generates a client for an extension from a trait describing its operations:
+///
+/// ```ignore
+/// #[serval::extension_client(name = "image-resize")]
+/// trait ImageResize {
+///     fn resize(&self, request: ResizeRequest) -> serval::Result<ResizeResponse>;
+///     #[serval(operation = "get-info")]
+///     fn info(&self) -> serval::Result<Info>;
+/// }
+/// ```
+///
+/// This keeps the trait and adds an `ImageResizeClient` struct implementing it. Each method
+/// invokes the operation of the same name (or the one given with `#[serval(operation = "...")]`)
+/// via `serval::invoke_operation`, passing its arguments as the request (a single argument as-is,
+/// several as a tuple). Requests are encoded as JSON unless a codec is given with
+/// `codec = path::to::Codec`. Requires the `codec` feature (and `json` for the default codec).
+#[proc_macro_attribute]
+pub fn extension_client(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut item_trait = parse_macro_input!(item as syn::ItemTrait);
+    let mut extension_name = None;
+    let mut codec: syn::Path = syn::parse_quote!(::serval::codec::Json);
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            extension_name = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else if meta.path.is_ident("codec") {
+            codec = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported #[serval::extension_client] argument"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let Some(extension_name) = extension_name else {
+        return Error::new_spanned(
+            &item_trait.ident,
+            "#[serval::extension_client] needs the extension's name: name = \"...\"",
+        )
+        .to_compile_error()
+        .into();
+    };
+
+    match expand_client(&mut item_trait, &extension_name, &codec) {
+        Ok(client) => quote!(#item_trait #client).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_client(
+    item_trait: &mut syn::ItemTrait,
+    extension_name: &LitStr,
+    codec: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let trait_name = &item_trait.ident;
+    let vis = &item_trait.vis;
+    let client = format_ident!("{}Client", trait_name);
+
+    let mut methods = Vec::new();
+    for item in &mut item_trait.items {
+        let syn::TraitItem::Fn(method) = item else {
+            continue;
+        };
+        let operation =
+            take_operation_name(&mut method.attrs)?.unwrap_or_else(|| method.sig.ident.to_string());
+        if !matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_))) {
+            return Err(Error::new_spanned(
+                &method.sig,
+                "extension client methods must take &self",
+            ));
+        }
+        let args = method
+            .sig
+            .inputs
+            .iter()
+            .skip(1)
+            .map(|arg| match arg {
+                FnArg::Typed(arg) => match &*arg.pat {
+                    syn::Pat::Ident(pat) => Ok(pat.ident.clone()),
+                    pat => Err(Error::new_spanned(pat, "use a plain identifier here")),
+                },
+                FnArg::Receiver(receiver) => Err(Error::new_spanned(receiver, "unexpected self")),
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let request = match args.as_slice() {
+            [arg] => quote!(&#arg),
+            args => quote!(&(#(#args,)*)),
+        };
+        let sig = &method.sig;
+        methods.push(quote! {
+            #sig {
+                ::serval::invoke_operation(&self.extension, #operation, &#codec, #request)
+            }
+        });
+    }
+
+    Ok(quote! {
+        /// A client for the extension's operations, generated by `#[serval::extension_client]`.
+        #[derive(Clone, Debug)]
+        #vis struct #client {
+            extension: ::std::string::String,
+        }
+
+        impl #client {
+            /// The name of the extension this client talks to by default.
+            pub const EXTENSION: &'static str = #extension_name;
+
+            pub fn new() -> Self {
+                Self::with_extension(Self::EXTENSION)
+            }
+
+            /// Creates a client that talks to the extension registered under a different name,
+            /// e.g. a pinned version.
+            pub fn with_extension(extension: impl ::std::convert::Into<::std::string::String>) -> Self {
+                Self { extension: extension.into() }
+            }
+        }
+
+        impl ::std::default::Default for #client {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl #trait_name for #client {
+            #(#methods)*
+        }
+    })
+}
+
+/// Removes a `#[serval(operation = "...")]` attribute from a trait method, returning the name.
+fn take_operation_name(attrs: &mut Vec<syn::Attribute>) -> syn::Result<Option<String>> {
+    let mut operation = None;
+    let mut result = Ok(());
+    attrs.retain(|attr| {
+        if !attr.path().is_ident("serval") {
+            return true;
+        }
+        if let Err(err) = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("operation") {
+                operation = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported #[serval] argument"))
+            }
+        }) {
+            result = Err(err);
+        }
+        false
+    });
+    result.map(|()| operation)
+}
+
+/// Declares which extensions and capabilities the guest intends to use, embedding them in the
+/// module as the `serval.manifest` custom section so the host can check them against the job's
+/// grants before running it:
+///
+/// ```ignore
+/// serval::manifest! {
+///     extensions = ["geo.lookup", "kv"],
+///     capabilities = ["network", "storage"],
+/// }
+/// ```
+///
+/// Both lists are optional. Capabilities are named as in `serval::caps::Capability`. Use it once
+/// per guest, at module level; see `serval::permissions` for the section's layout.
+#[proc_macro]
+pub fn manifest(input: TokenStream) -> TokenStream {
+    match expand_manifest(input.into()) {
+        Ok(manifest) => manifest.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_manifest(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let mut extensions = Vec::new();
+    let mut capabilities = Vec::new();
+    let parser = syn::meta::parser(|meta| {
+        let list = if meta.path.is_ident("extensions") {
+            &mut extensions
+        } else if meta.path.is_ident("capabilities") {
+            &mut capabilities
+        } else {
+            return Err(meta.error("expected `extensions` or `capabilities`"));
+        };
+        let array: syn::ExprArray = meta.value()?.parse()?;
+        for elem in array.elems {
+            match elem {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(name),
+                    ..
+                }) if !name.value().is_empty() => list.push(name),
+                elem => return Err(Error::new_spanned(elem, "expected a non-empty string")),
+            }
+        }
+        Ok(())
+    });
+    syn::parse::Parser::parse2(parser, input)?;
+
+    Ok(quote! {
+        #[doc(hidden)]
+        #[cfg_attr(target_family = "wasm", link_section = "serval.manifest")]
+        #[used]
+        static __SERVAL_MANIFEST: [u8; ::serval::__private::manifest_len(
+            &[#(#extensions),*],
+            &[#(#capabilities),*],
+        )] = ::serval::__private::encode_manifest(&[#(#extensions),*], &[#(#capabilities),*]);
+    })
+}
@@ -0,0 +1,76 @@
+//! Reads back the section `serval::manifest!` embeds, as a host would.
+
+use serval::caps::Capability;
+use serval::permissions::{PermissionManifest, VERSION};
+
+mod full {
+    serval::manifest! {
+        extensions = ["geo.lookup", "kv"],
+        capabilities = ["network", "storage", "extension:*", "extension:blobs", "gpu"],
+    }
+
+    pub fn section() -> &'static [u8] {
+        &__SERVAL_MANIFEST
+    }
+}
+
+mod empty {
+    serval::manifest! {}
+
+    pub fn section() -> &'static [u8] {
+        &__SERVAL_MANIFEST
+    }
+}
+
+mod capabilities_only {
+    serval::manifest! {
+        capabilities = ["network",],
+    }
+
+    pub fn section() -> &'static [u8] {
+        &__SERVAL_MANIFEST
+    }
+}
+
+#[test]
+fn decodes_every_declared_permission() {
+    let manifest = PermissionManifest::decode(full::section()).unwrap();
+    assert_eq!(
+        manifest,
+        PermissionManifest {
+            extensions: vec!["geo.lookup".to_string(), "kv".to_string()],
+            capabilities: vec![
+                Capability::Network,
+                Capability::Storage,
+                Capability::AllExtensions,
+                Capability::Extension("blobs".to_string()),
+                Capability::Other("gpu".to_string()),
+            ],
+        }
+    );
+}
+
+#[test]
+fn lists_left_out_are_empty() {
+    assert_eq!(
+        PermissionManifest::decode(empty::section()).unwrap(),
+        PermissionManifest::default()
+    );
+    assert_eq!(
+        PermissionManifest::decode(capabilities_only::section()).unwrap(),
+        PermissionManifest {
+            extensions: Vec::new(),
+            capabilities: vec![Capability::Network],
+        }
+    );
+}
+
+#[test]
+fn section_has_the_documented_layout() {
+    let mut expected = vec![VERSION];
+    expected.extend_from_slice(&0u32.to_le_bytes());
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&7u32.to_le_bytes());
+    expected.extend_from_slice(b"network");
+    assert_eq!(capabilities_only::section(), expected);
+}
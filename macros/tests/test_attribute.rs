@@ -0,0 +1,68 @@
+//! `#[serval::test]` against the mock host. The mock host is per thread and the test harness runs
+//! each test on a thread of its own, so the tests that check nothing leaks call the others
+//! directly, one after another on the same thread.
+
+use serval::mock::{self, TestHost};
+
+#[serval::test]
+fn leaves_state_behind(host: TestHost) {
+    let host = host.register_reply("kv", Ok(b"cached".to_vec()));
+    mock::set_env("region", "eu-west");
+    assert_eq!(
+        serval::invoke_extension("kv", b"profile").unwrap(),
+        b"cached"
+    );
+    assert_eq!(host.calls().len(), 1);
+}
+
+#[serval::test]
+fn starts_from_an_empty_host(host: &TestHost) {
+    assert!(host.calls().is_empty());
+    assert_eq!(serval::env::get("region"), None);
+    assert!(serval::invoke_extension("kv", b"profile").is_err());
+}
+
+#[serval::test]
+#[should_panic(expected = "expected a call to kv")]
+fn panics_with_state_behind(host: &TestHost) {
+    mock::set_env("region", "eu-west");
+    host.assert_called("kv");
+}
+
+#[serval::test]
+async fn runs_async_bodies() {
+    mock::respond_with("kv", Ok(b"cached".to_vec()));
+    let reply = serval::invoke_extension_async("kv", b"profile").await;
+    assert_eq!(reply.unwrap(), b"cached");
+}
+
+#[serval::test]
+fn returns_results() -> serval::Result<()> {
+    mock::respond_with("kv", Ok(Vec::new()));
+    serval::invoke_extension("kv", b"profile")?;
+    Ok(())
+}
+
+#[test]
+fn state_does_not_leak_into_the_next_test() {
+    leaves_state_behind();
+    starts_from_an_empty_host();
+    runs_async_bodies();
+    starts_from_an_empty_host();
+    // Had the first run's call leaked, the second would see two.
+    leaves_state_behind();
+}
+
+#[test]
+fn state_set_up_outside_a_test_is_cleared() {
+    mock::respond_with("kv", Ok(Vec::new()));
+    mock::set_env("region", "eu-west");
+    starts_from_an_empty_host();
+}
+
+#[test]
+fn state_does_not_leak_out_of_a_failed_test() {
+    assert!(std::panic::catch_unwind(panics_with_state_behind).is_err());
+    assert_eq!(serval::env::get("region"), None);
+    starts_from_an_empty_host();
+}
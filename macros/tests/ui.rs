@@ -0,0 +1,10 @@
+//! Compile tests for the macros' expansions: everything under `ui/pass` must build and run, and
+//! everything under `ui/fail` must be rejected with the error in its `.stderr` file. Run with
+//! `TRYBUILD=overwrite` to regenerate those after changing a message.
+
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
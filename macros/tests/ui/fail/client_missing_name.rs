@@ -0,0 +1,6 @@
+#[serval::extension_client]
+trait ImageResize {
+    fn version(&self) -> serval::Result<String>;
+}
+
+fn main() {}
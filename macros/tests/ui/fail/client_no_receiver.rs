@@ -0,0 +1,6 @@
+#[serval::extension_client(name = "image-resize")]
+trait ImageResize {
+    fn version() -> serval::Result<String>;
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[serval::extension_client(name = "image-resize")]
+trait ImageResize {
+    fn resize(&self, (width, height): (u32, u32)) -> serval::Result<()>;
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[serval::extension_client(name = "image-resize", timeout = 5)]
+trait ImageResize {
+    fn version(&self) -> serval::Result<String>;
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[serval::extension_client(name = "image-resize")]
+trait ImageResize {
+    #[serval(op = "get-version")]
+    fn version(&self) -> serval::Result<String>;
+}
+
+fn main() {}
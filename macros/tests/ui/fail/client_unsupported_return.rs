@@ -0,0 +1,6 @@
+#[serval::extension_client(name = "image-resize")]
+trait ImageResize {
+    fn version(&self) -> String;
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[serval::export]
+fn handle_event(_event: &[u8], _context: &[u8]) {}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[serval::export(rename = "other")]
+fn reload_config() {}
+
+fn main() {}
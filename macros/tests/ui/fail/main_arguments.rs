@@ -0,0 +1,4 @@
+#[serval::main(name = "other")]
+fn run() {}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[serval::main]
+fn run(_input: Vec<u8>, _extra: Vec<u8>) {}
+
+fn main() {}
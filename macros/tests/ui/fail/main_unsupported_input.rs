@@ -0,0 +1,4 @@
+#[serval::main]
+fn run(_input: String) {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[serval::main]
+fn run() -> u32 {
+    0
+}
+
+fn main() {}
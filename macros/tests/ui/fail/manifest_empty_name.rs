@@ -0,0 +1,5 @@
+serval::manifest! {
+    extensions = ["kv", ""],
+}
+
+fn main() {}
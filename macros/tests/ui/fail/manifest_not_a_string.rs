@@ -0,0 +1,5 @@
+serval::manifest! {
+    capabilities = [network],
+}
+
+fn main() {}
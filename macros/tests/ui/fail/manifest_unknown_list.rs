@@ -0,0 +1,6 @@
+serval::manifest! {
+    extensions = ["kv"],
+    permissions = ["network"],
+}
+
+fn main() {}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use serval::frame::Frame;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[serval::extension_client(name = "image-resize")]
+trait ImageResize {
+    fn version(&self) -> serval::Result<String>;
+    fn resize(&self, size: Size) -> serval::Result<Size>;
+    fn crop(&self, size: Size, x: u32, y: u32) -> serval::Result<Size>;
+    fn tags(&self, ids: &[u32]) -> serval::Result<Vec<Option<String>>>;
+    #[serval(operation = "clear-cache")]
+    fn clear_cache(&self) -> serval::Result<()>;
+}
+
+#[serval::extension_client(name = "thumbnails", codec = serval::codec::Json)]
+pub trait Thumbnails {
+    fn render(&self, size: Size) -> Result<Vec<u8>, serval::SdkError>;
+}
+
+fn main() {
+    // Answers each operation with its name and the request body it was sent.
+    let echo = |request: &[u8]| {
+        let request = Frame::decode(request).unwrap();
+        let operation = request.operation().unwrap().to_string();
+        let reply = match operation.as_str() {
+            "version" => {
+                assert_eq!(request.body, b"null");
+                br#""1.2""#.to_vec()
+            }
+            "resize" | "render" => request.body.clone(),
+            "crop" => {
+                assert_eq!(request.body, br#"[{"width":4,"height":3},1,2]"#);
+                br#"{"width":3,"height":1}"#.to_vec()
+            }
+            "tags" => {
+                assert_eq!(request.body, b"[7,8]");
+                br#"["seven",null]"#.to_vec()
+            }
+            "clear-cache" => b"null".to_vec(),
+            other => panic!("unexpected operation {other}"),
+        };
+        Ok(Frame::new(reply).encode().unwrap())
+    };
+    serval::mock::respond("image-resize", echo);
+    serval::mock::respond("image-resize@2", echo);
+    serval::mock::respond("thumbnails", echo);
+
+    assert_eq!(ImageResizeClient::EXTENSION, "image-resize");
+    let client = ImageResizeClient::new();
+    assert_eq!(client.version().unwrap(), "1.2");
+    let size = Size {
+        width: 4,
+        height: 3,
+    };
+    assert_eq!(
+        client.resize(size).unwrap(),
+        Size {
+            width: 4,
+            height: 3
+        }
+    );
+    let size = Size {
+        width: 4,
+        height: 3,
+    };
+    assert_eq!(
+        client.crop(size, 1, 2).unwrap(),
+        Size {
+            width: 3,
+            height: 1
+        }
+    );
+    assert_eq!(
+        client.tags(&[7, 8]).unwrap(),
+        [Some("seven".to_string()), None]
+    );
+    client.clear_cache().unwrap();
+
+    let pinned = ImageResizeClient::with_extension("image-resize@2");
+    assert_eq!(pinned.version().unwrap(), "1.2");
+
+    let size = Size {
+        width: 1,
+        height: 1,
+    };
+    let rendered = ThumbnailsClient::default().render(size);
+    assert!(rendered.is_err(), "an object doesn't decode as bytes");
+
+    let operations: Vec<_> = serval::mock::calls()
+        .iter()
+        .map(|call| {
+            (
+                call.extension.clone(),
+                call.frame().unwrap().operation().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        operations,
+        [
+            ("image-resize", "version"),
+            ("image-resize", "resize"),
+            ("image-resize", "crop"),
+            ("image-resize", "tags"),
+            ("image-resize", "clear-cache"),
+            ("image-resize@2", "version"),
+            ("thumbnails", "render"),
+        ]
+        .map(|(name, operation)| (name.to_string(), operation.to_string()))
+    );
+}
@@ -0,0 +1,40 @@
+use serval::Typed;
+
+#[derive(serde::Deserialize)]
+struct Event {
+    kind: String,
+}
+
+#[derive(serde::Serialize)]
+struct Reply {
+    handled: bool,
+}
+
+#[serval::export]
+fn reload_config() {}
+
+#[serval::export]
+fn echo(input: Vec<u8>) -> Vec<u8> {
+    input
+}
+
+#[serval::export]
+fn checksum(input: &[u8]) -> Result<Vec<u8>, serval::GuestError> {
+    Ok(vec![input.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))])
+}
+
+#[serval::export(name = "handle-event")]
+fn handle_event(Typed(event): Typed<Event>) -> serval::Result<Typed<Reply>> {
+    Ok(Typed(Reply {
+        handled: event.kind == "ping",
+    }))
+}
+
+fn main() {
+    // The functions themselves stay callable, next to the exported wrappers.
+    assert_eq!(echo(b"hi".to_vec()), b"hi");
+    let _: extern "C" fn(usize) -> isize = __serval_export_reload_config;
+    let _: extern "C" fn(usize) -> isize = __serval_export_echo;
+    let _: extern "C" fn(usize) -> isize = __serval_export_checksum;
+    let _: extern "C" fn(usize) -> isize = __serval_export_handle_event;
+}
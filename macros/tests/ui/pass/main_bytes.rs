@@ -0,0 +1,11 @@
+#[serval::main]
+fn run(input: Vec<u8>) -> Result<Vec<u8>, serval::GuestError> {
+    let mut output = b"got ".to_vec();
+    output.extend_from_slice(&input);
+    Ok(output)
+}
+
+fn main() {
+    assert_eq!(__serval_export_serval_main(0), 0);
+    assert_eq!(serval::mock::job_output().as_deref(), Some(&b"got "[..]));
+}
@@ -0,0 +1,14 @@
+#[serval::main]
+fn run() -> Result<(), &'static str> {
+    Err("nothing to do")
+}
+
+fn main() {
+    assert_eq!(
+        __serval_export_serval_main(0),
+        serval::__private::ENTRYPOINT_FAILED
+    );
+    let (status, message) = serval::mock::job_completion().unwrap();
+    assert_eq!(status, serval::job::JobStatus::Failed);
+    assert_eq!(message, "nothing to do");
+}
@@ -0,0 +1,9 @@
+#[serval::main]
+fn run() -> Vec<u8> {
+    b"done".to_vec()
+}
+
+fn main() {
+    assert_eq!(__serval_export_serval_main(0), 0);
+    assert_eq!(serval::mock::job_output().as_deref(), Some(&b"done"[..]));
+}
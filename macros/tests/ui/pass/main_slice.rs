@@ -0,0 +1,8 @@
+#[serval::main]
+fn run(input: &[u8]) {
+    assert!(input.is_empty());
+}
+
+fn main() {
+    assert_eq!(__serval_export_serval_main(0), 0);
+}
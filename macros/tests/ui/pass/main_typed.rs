@@ -0,0 +1,22 @@
+use serval::Typed;
+
+#[derive(serde::Deserialize, Default)]
+struct Request {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(serde::Serialize)]
+struct Response {
+    greeting: String,
+}
+
+#[serval::main]
+fn run(Typed(request): Typed<Option<Request>>) -> serval::Result<Typed<Response>> {
+    let request = request.unwrap_or_default();
+    Ok(Typed(Response {
+        greeting: format!("hello {}", request.name),
+    }))
+}
+
+fn main() {}
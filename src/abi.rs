@@ -0,0 +1,72 @@
+//! The version of the ABI between guest and host: the imports in `host`, the exports in `lib`, and
+//! the framing of the buffers passed between them. Hosts read it from the `serval_abi_version`
+//! export before calling into the guest, and the guest checks it in turn with `handshake`, which
+//! the entrypoint glue does before running any entrypoint. A mismatch on either side is then
+//! reported as `SdkError::IncompatibleAbi` instead of surfacing as corrupted buffers once the
+//! layouts have diverged.
+//!
+//! The ABI comes in two widths, picked by the target the guest is built for. On wasm32 pointers
+//! are i32s; on wasm64 (memory64, for guests that need more than 4 GiB of linear memory) they're
+//! i64s. That covers the parameters of `alloc` and `dealloc`, pointer parameters of imports, the
+//! buffer pointers imports and entrypoints return, and entrypoint inputs. Lengths and error codes
+//! are 32 bits either way, as are the length prefixes of buffers. Hosts tell the two apart from
+//! whether the module's memory is 64-bit, and have to support memory64 to run wasm64 guests;
+//! `harness` only runs wasm32 ones.
+
+use std::sync::OnceLock;
+
+use crate::{host, ExtensionErrorCode, Result, SdkError};
+
+/// The newest ABI version this SDK speaks. Bumped whenever a change to the imports, exports or
+/// buffer layouts would make an older host or guest misread the other.
+///
+/// Version 2 added `invoke_raw_inline`; see `inline`.
+pub const ABI_VERSION: u32 = 2;
+
+/// The oldest ABI version this SDK still speaks.
+pub const MIN_ABI_VERSION: u32 = 1;
+
+/// The version of the exchange memory protocol (see `exchange`) this SDK speaks, which hosts pass
+/// to `serval_exchange_enable`.
+pub const EXCHANGE_VERSION: u32 = 1;
+
+/// Reports the newest ABI version the guest speaks, so the host can tell before its first call
+/// whether it can run the guest at all.
+#[no_mangle]
+pub fn serval_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// Agrees on an ABI version with the host and returns it. The host picks the newest version both
+/// sides speak; if there isn't one the result is `SdkError::IncompatibleAbi`. The outcome is
+/// fetched from the host once and remembered.
+pub fn handshake() -> Result<u32> {
+    static AGREED: OnceLock<Result<u32>> = OnceLock::new();
+    if cfg!(feature = "mock-host") {
+        // The mock host's version can change from one test to the next.
+        return negotiate();
+    }
+    AGREED.get_or_init(negotiate).clone()
+}
+
+fn negotiate() -> Result<u32> {
+    let incompatible = |host| SdkError::IncompatibleAbi {
+        guest_min: MIN_ABI_VERSION,
+        guest_max: ABI_VERSION,
+        host,
+    };
+    let version = unsafe { host::abi_negotiate(MIN_ABI_VERSION, ABI_VERSION) };
+    match version {
+        version if version == ExtensionErrorCode::NoMatchingVersion.as_raw() => {
+            Err(incompatible(None))
+        }
+        version if version < 0 => Err(ExtensionErrorCode::from(version).into()),
+        version => {
+            let version = version as u32;
+            if !(MIN_ABI_VERSION..=ABI_VERSION).contains(&version) {
+                return Err(incompatible(Some(version)));
+            }
+            Ok(version)
+        }
+    }
+}
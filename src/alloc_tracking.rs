@@ -0,0 +1,180 @@
+//! Bookkeeping for every block handed out by our allocation exports, for tracking down leaks and
+//! mismatched frees between the host and the guest. Enabled by the `alloc-tracking` feature, which
+//! is meant for debug builds: every allocation takes a lock and a map update.
+//!
+//! Blocks allocated or freed through the exports are attributed to the host; blocks the SDK
+//! allocates or frees itself are attributed to the place in the SDK or guest code that did so.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::Location;
+use std::sync::Mutex;
+
+/// Where an allocation or free happened; `None` means the host called one of our exports.
+pub type Site = Option<&'static Location<'static>>;
+
+static TRACKER: Mutex<Tracker> = Mutex::new(Tracker {
+    live: BTreeMap::new(),
+    allocations: 0,
+    deallocations: 0,
+    live_bytes: 0,
+    peak_bytes: 0,
+    mismatched_frees: Vec::new(),
+});
+
+struct Tracker {
+    live: BTreeMap<usize, (usize, Site)>,
+    allocations: u64,
+    deallocations: u64,
+    live_bytes: usize,
+    peak_bytes: usize,
+    mismatched_frees: Vec<MismatchedFree>,
+}
+
+/// A block that has been allocated and not freed yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiveAllocation {
+    pub ptr: usize,
+    pub size: usize,
+    pub site: Site,
+}
+
+/// A free that doesn't match any live block: either nothing was allocated at `ptr`, or the block
+/// there has a different size than the one being freed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MismatchedFree {
+    pub ptr: usize,
+    pub size: usize,
+    /// The size the block was allocated with, if there is one at `ptr`.
+    pub allocated_size: Option<usize>,
+    pub site: Site,
+}
+
+/// A snapshot of the allocator's bookkeeping; see `memory_report`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+    pub live: Vec<LiveAllocation>,
+    pub mismatched_frees: Vec<MismatchedFree>,
+}
+
+impl MemoryReport {
+    /// Whether every block has been freed, and every free matched a block.
+    pub fn is_clean(&self) -> bool {
+        self.live.is_empty() && self.mismatched_frees.is_empty()
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} allocations, {} frees, {} bytes live, {} bytes at peak",
+            self.allocations, self.deallocations, self.live_bytes, self.peak_bytes
+        )?;
+        for block in &self.live {
+            writeln!(
+                f,
+                "leaked {} bytes at {:#x}, allocated by {}",
+                block.size,
+                block.ptr,
+                SiteDisplay(block.site)
+            )?;
+        }
+        for free in &self.mismatched_frees {
+            match free.allocated_size {
+                Some(allocated_size) => writeln!(
+                    f,
+                    "freed {:#x} as {} bytes but it was allocated as {allocated_size}, by {}",
+                    free.ptr,
+                    free.size,
+                    SiteDisplay(free.site)
+                )?,
+                None => writeln!(
+                    f,
+                    "freed {} bytes at {:#x} which isn't allocated, by {}",
+                    free.size,
+                    free.ptr,
+                    SiteDisplay(free.site)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct SiteDisplay(Site);
+
+impl fmt::Display for SiteDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(location) => write!(f, "{location}"),
+            None => write!(f, "the host"),
+        }
+    }
+}
+
+/// Returns everything that's currently allocated, along with any frees that didn't match.
+pub fn memory_report() -> MemoryReport {
+    let tracker = TRACKER.lock().unwrap_or_else(|err| err.into_inner());
+    MemoryReport {
+        allocations: tracker.allocations,
+        deallocations: tracker.deallocations,
+        live_bytes: tracker.live_bytes,
+        peak_bytes: tracker.peak_bytes,
+        live: tracker
+            .live
+            .iter()
+            .map(|(&ptr, &(size, site))| LiveAllocation { ptr, size, site })
+            .collect(),
+        mismatched_frees: tracker.mismatched_frees.clone(),
+    }
+}
+
+/// Panics with the memory report if anything is still allocated or a free didn't match. Call this
+/// at the end of a job, once the host has freed the job's output.
+#[track_caller]
+pub fn assert_no_leaks() {
+    let report = memory_report();
+    assert!(report.is_clean(), "guest memory isn't clean:\n{report}");
+}
+
+pub(crate) fn record_alloc(ptr: *mut u8, size: usize, site: Site) {
+    // Zero-length blocks all share dangling addresses and are never really allocated.
+    if ptr.is_null() || size == 0 {
+        return;
+    }
+    let mut tracker = TRACKER.lock().unwrap_or_else(|err| err.into_inner());
+    tracker.live.insert(ptr as usize, (size, site));
+    tracker.allocations += 1;
+    tracker.live_bytes += size;
+    tracker.peak_bytes = tracker.peak_bytes.max(tracker.live_bytes);
+}
+
+pub(crate) fn record_dealloc(ptr: *mut u8, size: usize, site: Site) {
+    if size == 0 {
+        return;
+    }
+    let mut tracker = TRACKER.lock().unwrap_or_else(|err| err.into_inner());
+    let ptr = ptr as usize;
+    match tracker
+        .live
+        .get(&ptr)
+        .map(|&(allocated_size, _)| allocated_size)
+    {
+        Some(allocated_size) if allocated_size == size => {
+            tracker.live.remove(&ptr);
+            tracker.deallocations += 1;
+            tracker.live_bytes -= size;
+        }
+        allocated_size => tracker.mismatched_frees.push(MismatchedFree {
+            ptr,
+            size,
+            allocated_size,
+            site,
+        }),
+    }
+}
@@ -0,0 +1,128 @@
+//! Global allocators for the guest, selected with features. They only take effect on wasm
+//! targets; native builds (e.g. unit tests of guest code) keep the system allocator.
+//!
+//! - `dlmalloc` installs dlmalloc, a good general-purpose allocator. This is also what the
+//!   standard library uses on `wasm32-unknown-unknown` by default, but selecting it explicitly
+//!   pins it regardless of target or toolchain.
+//! - `bump-allocator` installs `BumpAllocator`, which is tiny but never reuses freed memory.
+//!
+//! Without either feature the standard library's default allocator is used. If both are enabled,
+//! for example by two crates in the same build, `dlmalloc` wins.
+
+#[cfg(all(feature = "dlmalloc", target_family = "wasm"))]
+#[global_allocator]
+static ALLOCATOR: dlmalloc::GlobalDlmalloc = dlmalloc::GlobalDlmalloc;
+
+#[cfg(all(
+    feature = "bump-allocator",
+    not(feature = "dlmalloc"),
+    target_family = "wasm"
+))]
+#[global_allocator]
+static ALLOCATOR: bump::BumpAllocator = bump::BumpAllocator::new();
+
+#[cfg(all(feature = "bump-allocator", target_family = "wasm"))]
+pub use bump::BumpAllocator;
+
+#[cfg(all(feature = "bump-allocator", target_family = "wasm"))]
+mod bump {
+    use crate::arch;
+    use std::alloc::{GlobalAlloc, Layout};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const PAGE_SIZE: usize = 64 * 1024;
+
+    extern "C" {
+        /// Provided by the linker: the first address after the module's static data and stack.
+        static __heap_base: u8;
+    }
+
+    /// An allocator that hands out memory by bumping a pointer, growing linear memory as needed.
+    /// Freeing only reclaims memory if it's the most recent allocation, so this suits short jobs
+    /// that allocate a bounded amount, in exchange for adding almost nothing to the module size.
+    pub struct BumpAllocator {
+        /// The next free address, or 0 before the first allocation.
+        next: AtomicUsize,
+        /// The end of linear memory as of the last time we grew it.
+        end: AtomicUsize,
+    }
+
+    impl BumpAllocator {
+        pub const fn new() -> Self {
+            Self {
+                next: AtomicUsize::new(0),
+                end: AtomicUsize::new(0),
+            }
+        }
+
+        /// Makes sure linear memory extends to at least `address`.
+        fn reserve(&self, address: usize) -> bool {
+            let end = self.end.load(Ordering::Relaxed);
+            if address <= end {
+                return true;
+            }
+            let pages = (address - end).div_ceil(PAGE_SIZE);
+            if arch::memory_grow(0, pages) == usize::MAX {
+                return false;
+            }
+            self.end.store(end + pages * PAGE_SIZE, Ordering::Relaxed);
+            true
+        }
+    }
+
+    impl Default for BumpAllocator {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    // Wasm guests are single-threaded, so relaxed loads and stores are all the synchronization
+    // the bookkeeping needs.
+    unsafe impl GlobalAlloc for BumpAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let mut next = self.next.load(Ordering::Relaxed);
+            if next == 0 {
+                next = std::ptr::addr_of!(__heap_base) as usize;
+                self.end
+                    .store(arch::memory_size(0) * PAGE_SIZE, Ordering::Relaxed);
+            }
+
+            let start = next.next_multiple_of(layout.align());
+            let Some(new_next) = start.checked_add(layout.size()) else {
+                return std::ptr::null_mut();
+            };
+            if !self.reserve(new_next) {
+                return std::ptr::null_mut();
+            }
+            self.next.store(new_next, Ordering::Relaxed);
+            start as *mut u8
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            // Only the most recent allocation can be given back.
+            if ptr as usize + layout.size() == self.next.load(Ordering::Relaxed) {
+                self.next.store(ptr as usize, Ordering::Relaxed);
+            }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            // The most recent allocation can grow or shrink in place.
+            if ptr as usize + layout.size() == self.next.load(Ordering::Relaxed) {
+                let Some(new_next) = (ptr as usize).checked_add(new_size) else {
+                    return std::ptr::null_mut();
+                };
+                if self.reserve(new_next) {
+                    self.next.store(new_next, Ordering::Relaxed);
+                    return ptr;
+                }
+                return std::ptr::null_mut();
+            }
+
+            let new_ptr = self.alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+            if !new_ptr.is_null() {
+                std::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            }
+            new_ptr
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Request-scoped scratch memory that's freed all at once.
+//!
+//! `with_request_arena` runs a closure with a fresh `RequestArena` and frees everything allocated
+//! from it when the closure returns. While it's running, the SDK's own serialization helpers (e.g.
+//! `invoke_with_codec`) encode requests into scratch buffers owned by the arena and reuse them from
+//! one call to the next, rather than allocating and freeing a buffer per invocation.
+
+use std::cell::{Cell, RefCell};
+use std::ptr;
+
+/// Size of the chunks the arena carves allocations out of. Larger allocations get a chunk of their
+/// own.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+thread_local! {
+    /// The arena of the innermost `with_request_arena` call on this thread, if any.
+    static CURRENT: Cell<*const RequestArena> = const { Cell::new(ptr::null()) };
+}
+
+/// A bump allocator for temporaries that live as long as one request; see `with_request_arena`.
+pub struct RequestArena {
+    /// Start and length of every chunk, owned by the arena and freed when it's dropped.
+    chunks: RefCell<Vec<(*mut u8, usize)>>,
+    /// How much of the last chunk is in use.
+    used: Cell<usize>,
+    allocated: Cell<usize>,
+    scratch: RefCell<Vec<Vec<u8>>>,
+}
+
+impl RequestArena {
+    fn new() -> Self {
+        Self {
+            chunks: RefCell::new(Vec::new()),
+            used: Cell::new(0),
+            allocated: Cell::new(0),
+            scratch: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Allocates `len` zeroed bytes that stay valid until the arena is dropped.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_bytes(&self, len: usize) -> &mut [u8] {
+        let mut chunks = self.chunks.borrow_mut();
+        let fits = chunks
+            .last()
+            .is_some_and(|&(_, chunk_len)| chunk_len - self.used.get() >= len);
+        if !fits {
+            let chunk = vec![0u8; len.max(CHUNK_SIZE)].into_boxed_slice();
+            let chunk_len = chunk.len();
+            chunks.push((Box::into_raw(chunk) as *mut u8, chunk_len));
+            self.used.set(0);
+        }
+
+        let (start, _) = *chunks.last().expect("a chunk was just pushed");
+        let offset = self.used.get();
+        self.used.set(offset + len);
+        self.allocated.set(self.allocated.get() + len);
+        // Safety: `offset..offset + len` lies within the chunk, no other allocation overlaps it,
+        // and the chunk isn't freed or moved until the arena is dropped, which the returned
+        // borrow can't outlive.
+        unsafe { std::slice::from_raw_parts_mut(start.add(offset), len) }
+    }
+
+    /// Copies `bytes` into the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn copy_bytes(&self, bytes: &[u8]) -> &mut [u8] {
+        let copy = self.alloc_bytes(bytes.len());
+        copy.copy_from_slice(bytes);
+        copy
+    }
+
+    /// Copies `s` into the arena.
+    pub fn alloc_str(&self, s: &str) -> &str {
+        let bytes = self.copy_bytes(s.as_bytes());
+        // Safety: the bytes were copied from a `str`.
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+
+    /// The total size of everything allocated from the arena so far.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated.get()
+    }
+
+    /// Takes an empty buffer, reusing one handed back with `recycle` if there is one.
+    pub fn scratch(&self) -> Vec<u8> {
+        self.scratch.borrow_mut().pop().unwrap_or_default()
+    }
+
+    /// Hands a buffer back to the arena for later `scratch` calls. Its allocation is kept until
+    /// the arena is dropped.
+    pub fn recycle(&self, mut buf: Vec<u8>) {
+        buf.clear();
+        self.scratch.borrow_mut().push(buf);
+    }
+}
+
+impl Drop for RequestArena {
+    fn drop(&mut self) {
+        for &(start, len) in self.chunks.get_mut().iter() {
+            // Safety: every chunk came from `Box::into_raw` on a boxed slice of `len` bytes.
+            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(start, len)) });
+        }
+    }
+}
+
+/// Runs `f` with a fresh arena, freeing everything allocated from it when `f` returns. Arenas can
+/// be nested; the SDK uses the innermost one.
+pub fn with_request_arena<R>(f: impl FnOnce(&RequestArena) -> R) -> R {
+    struct Restore(*const RequestArena);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT.set(self.0);
+        }
+    }
+
+    let arena = RequestArena::new();
+    let _restore = Restore(CURRENT.replace(&arena));
+    f(&arena)
+}
+
+/// Runs `f` with an empty buffer, taken from the current request arena if there is one so the
+/// allocation is reused by later calls.
+#[cfg(feature = "codec")]
+pub(crate) fn with_scratch<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    let current = CURRENT.get();
+    if current.is_null() {
+        return f(&mut Vec::new());
+    }
+
+    // Safety: `CURRENT` only ever points at the arena of a `with_request_arena` call that's still
+    // running further up this thread's stack.
+    let arena = unsafe { &*current };
+    let mut buf = arena.scratch();
+    let output = f(&mut buf);
+    arena.recycle(buf);
+    output
+}
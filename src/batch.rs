@@ -0,0 +1,69 @@
+//! Sending many invocations to the host in a single call.
+//!
+//! A batch request is a u32 count followed by that many entries, each a length-prefixed extension
+//! name and a length-prefixed payload. The host answers with a u32 count followed by one entry per
+//! invocation, in the same order: an i32 status (0 on success, otherwise a negative
+//! `ExtensionErrorCode`) and a length-prefixed response body, which is empty for failed calls.
+
+use crate::wire::{Reader, Writer};
+use crate::{host, take_host_bytes, ExtensionErrorCode, InvocationError, Result, SdkError};
+
+/// Invokes every `(extension name, payload)` pair in `calls` with a single host call, returning
+/// one result per invocation in the same order. The invocations are independent: one failing
+/// doesn't stop the others. If the batch as a whole is rejected, every invocation reports that
+/// error.
+pub fn invoke_batch<N, P>(calls: &[(N, P)]) -> Vec<Result<Vec<u8>>>
+where
+    N: AsRef<str>,
+    P: AsRef<[u8]>,
+{
+    match send_batch(calls) {
+        Ok(results) => results,
+        Err(err) => calls
+            .iter()
+            .map(|(name, payload)| {
+                let status = err.code().map_or(0, |code| code.as_raw());
+                let err = err.clone();
+                Err(InvocationError::new(name.as_ref(), payload.as_ref().len(), status, err).into())
+            })
+            .collect(),
+    }
+}
+
+fn send_batch<N, P>(calls: &[(N, P)]) -> Result<Vec<Result<Vec<u8>>>>
+where
+    N: AsRef<str>,
+    P: AsRef<[u8]>,
+{
+    let mut writer = Writer::new();
+    writer.write_u32(calls.len() as u32);
+    for (name, payload) in calls {
+        writer.write_str(name.as_ref());
+        writer.write_prefixed(payload.as_ref());
+    }
+    let encoded = writer.into_bytes();
+
+    let out_ptr = unsafe { host::invoke_batch(encoded.as_ptr() as usize, encoded.len() as u32) };
+    if out_ptr < 0 {
+        return Err(ExtensionErrorCode::from(out_ptr as i32).into());
+    }
+    let response = take_host_bytes(out_ptr as usize)?;
+
+    let mut reader = Reader::new(&response);
+    if reader.read_u32()? as usize != calls.len() {
+        return Err(SdkError::InvalidPayload);
+    }
+    let mut results = Vec::with_capacity(calls.len());
+    for (name, payload) in calls {
+        let (name, payload) = (name.as_ref(), payload.as_ref());
+        let status = reader.read_i32()?;
+        let body = reader.read_prefixed()?;
+        results.push(if status < 0 {
+            let err = ExtensionErrorCode::from(status).into();
+            Err(InvocationError::new(name, payload.len(), status, err).into())
+        } else {
+            Ok(body.to_vec())
+        });
+    }
+    Ok(results)
+}
@@ -0,0 +1,51 @@
+//! Helpers for calling extensions that are also written in Rust, where bincode is much faster than
+//! any self-describing format.
+//!
+//! Bincode payloads carry no schema information at all, so every payload produced here starts
+//! with a header byte identifying the encoding. Decoding a payload with a different header fails
+//! loudly instead of silently misparsing it.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::{invoke_with_codec, Bincode};
+use crate::{CodecError, Result, SdkError};
+
+/// The header byte identifying the current bincode encoding (bincode 1.x, default options).
+pub const BINCODE_HEADER: u8 = 1;
+
+/// Encodes a value as a bincode payload, prefixed with `BINCODE_HEADER`.
+pub fn to_payload_bincode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    write_payload_bincode(value, &mut payload)?;
+    Ok(payload)
+}
+
+/// Like `to_payload_bincode`, but appends the payload to `buf`.
+pub fn write_payload_bincode<T: Serialize + ?Sized>(value: &T, buf: &mut Vec<u8>) -> Result<()> {
+    buf.push(BINCODE_HEADER);
+    ::bincode::serialize_into(buf, value)
+        .map_err(|err| SdkError::Encode(CodecError::new(err.to_string())))
+}
+
+/// Decodes a bincode payload produced by `to_payload_bincode`, checking its header byte first.
+pub fn from_payload_bincode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    match bytes.split_first() {
+        Some((&BINCODE_HEADER, body)) => ::bincode::deserialize(body)
+            .map_err(|err| SdkError::Decode(CodecError::new(err.to_string()))),
+        Some((header, _)) => Err(SdkError::Decode(
+            CodecError::new(format!("unsupported bincode header {header}")).with_offset(0),
+        )),
+        None => Err(SdkError::Decode(
+            CodecError::new("missing bincode header").with_offset(0),
+        )),
+    }
+}
+
+/// Invokes an extension with a bincode-encoded request and decodes its bincode response.
+pub fn invoke_bincode<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    invoke_with_codec(extension_name, &Bincode, request)
+}
@@ -0,0 +1,399 @@
+//! A client for the platform's blob store, for artifacts too large to hold in memory at once.
+//!
+//! ```ignore
+//! use serval::blobs;
+//!
+//! let info = blobs::put_stream("builds/42/output.tar", File::open("output.tar")?)?;
+//! let mut reader = blobs::get_stream("builds/42/output.tar")?;
+//! io::copy(&mut reader, &mut destination)?;
+//! ```
+//!
+//! Uploads are sent as a sequence of segments, each committed by the store as it arrives. If a
+//! job dies halfway through, a later run can pick the upload up by id with `Upload::resume` and
+//! continue from the last committed segment instead of starting over. Content is hashed with
+//! SHA-256 as it's uploaded; the store checks the hash when the upload is finished and
+//! `BlobReader` checks it again on the way out.
+//!
+//! Requests use the same frames as `kv`, sent to the `EXTENSION` extension (or the one a `Store`
+//! names), but as plain payloads so that segments can be streamed to and from the host with
+//! `InvocationWriter` and `ResponseStream`:
+//!
+//! | operation | request body | response body |
+//! |---|---|---|
+//! | `stat` | name | blob info |
+//! | `delete` | name | empty |
+//! | `create_upload` | name | upload id |
+//! | `upload_status` | upload id | u64 committed length |
+//! | `append` | upload id, u64 offset, segment bytes | u64 committed length |
+//! | `finish_upload` | upload id, u8 has hash, 32 byte SHA-256 | blob info |
+//! | `read` | name, u64 offset | the blob's bytes from `offset`, streamed, with no frame |
+//!
+//! Strings are length-prefixed and integers little-endian. Blob info is the name, the u64 size and
+//! the 32 byte SHA-256 of the content. An `append` at any offset other than the committed length
+//! fails with `frame::status::CONFLICT`, as does finishing an upload whose content doesn't match
+//! the hash.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::frame::Frame;
+use crate::stream::{negotiate_segment_size, DEFAULT_SEGMENT_SIZE};
+use crate::wire::{Reader, Writer};
+use crate::{
+    invoke_streaming, service, InvocationError, InvocationWriter, ResponseStream, Result, SdkError,
+};
+
+/// The extension the platform's blob store is registered under.
+pub const EXTENSION: &str = "blobs";
+
+/// What the store knows about a blob.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobInfo {
+    pub name: String,
+    pub size: u64,
+    pub sha256: [u8; 32],
+}
+
+impl BlobInfo {
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        Ok(Self {
+            name: reader.read_str()?.to_string(),
+            size: reader.read_u64()?,
+            sha256: reader.read_bytes(32)?.try_into().unwrap(),
+        })
+    }
+}
+
+/// A handle to a blob store. The free functions in this module use the default one; create a
+/// `Store` to talk to a store registered under a different extension name.
+#[derive(Clone, Debug)]
+pub struct Store {
+    extension: String,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self::new(EXTENSION)
+    }
+}
+
+impl Store {
+    pub fn new(extension_name: impl Into<String>) -> Self {
+        Self {
+            extension: extension_name.into(),
+        }
+    }
+
+    /// The extension the store's requests go to.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Returns what the store knows about the named blob. Fails with `SdkError::NotFound` if there
+    /// isn't one.
+    pub fn stat(&self, name: &str) -> Result<BlobInfo> {
+        BlobInfo::decode(&self.call("stat", name_body(name))?)
+    }
+
+    /// Removes the named blob. Fails with `SdkError::NotFound` if there isn't one.
+    pub fn delete(&self, name: &str) -> Result<()> {
+        self.call("delete", name_body(name))?;
+        Ok(())
+    }
+
+    /// Uploads `data` as the named blob, replacing any existing one.
+    pub fn put(&self, name: &str, data: &[u8]) -> Result<BlobInfo> {
+        self.put_stream(name, data)
+    }
+
+    /// Uploads everything read from `input` as the named blob, replacing any existing one, without
+    /// holding more than a segment of it in memory. Use `Upload` directly to be able to resume
+    /// the upload if it's interrupted.
+    pub fn put_stream(&self, name: &str, input: impl Read) -> Result<BlobInfo> {
+        let mut upload = self.start_upload(name)?;
+        upload.write_from(input)?;
+        upload.finish()
+    }
+
+    /// Starts a resumable upload of the named blob; see `Upload`.
+    pub fn start_upload(&self, name: &str) -> Result<Upload> {
+        let response = self.call("create_upload", name_body(name))?;
+        let id = Reader::new(&response).read_str()?.to_string();
+        Ok(Upload {
+            store: self.clone(),
+            id,
+            offset: 0,
+            hasher: Some(Sha256::new()),
+        })
+    }
+
+    /// Picks up an interrupted upload where the store left off. The content uploaded before the
+    /// interruption can't be hashed again, so the finished upload is only checked by the store;
+    /// use `resume_upload_from` to check the whole blob.
+    pub fn resume_upload(&self, id: &str) -> Result<Upload> {
+        Ok(Upload {
+            store: self.clone(),
+            id: id.to_string(),
+            offset: self.upload_status(id)?,
+            hasher: None,
+        })
+    }
+
+    /// Like `resume_upload`, but rereads the part of `source` that was already uploaded to hash it,
+    /// leaving `source` positioned where the upload left off so it can be handed to
+    /// `Upload::write_from`.
+    pub fn resume_upload_from(&self, id: &str, source: &mut (impl Read + Seek)) -> Result<Upload> {
+        let offset = self.upload_status(id)?;
+        source.seek(SeekFrom::Start(0)).map_err(SdkError::from)?;
+        let mut hasher = Sha256::new();
+        let hashed = io::copy(&mut source.take(offset), &mut hasher).map_err(SdkError::from)?;
+        if hashed != offset {
+            return Err(SdkError::InvalidPayload);
+        }
+        Ok(Upload {
+            store: self.clone(),
+            id: id.to_string(),
+            offset,
+            hasher: Some(hasher),
+        })
+    }
+
+    /// Streams the named blob's content, checking it against its hash as it's read. Fails with
+    /// `SdkError::NotFound` if there isn't one.
+    pub fn get_stream(&self, name: &str) -> Result<BlobReader> {
+        let info = self.stat(name)?;
+        let stream = self.read_from(name, 0)?;
+        Ok(BlobReader {
+            stream,
+            buffered: Vec::new(),
+            position: 0,
+            hasher: Some(Sha256::new()),
+            info,
+        })
+    }
+
+    /// Streams the named blob's content starting at `offset`, for picking up an interrupted
+    /// download. Only a read from the start can be checked against the blob's hash, so this one
+    /// isn't.
+    pub fn get_stream_from(&self, name: &str, offset: u64) -> Result<BlobReader> {
+        let info = self.stat(name)?;
+        let stream = self.read_from(name, offset)?;
+        Ok(BlobReader {
+            stream,
+            buffered: Vec::new(),
+            position: 0,
+            hasher: None,
+            info,
+        })
+    }
+
+    fn read_from(&self, name: &str, offset: u64) -> Result<ResponseStream> {
+        let mut body = Writer::new();
+        body.write_str(name);
+        body.write_u64(offset);
+        invoke_streaming(
+            &self.extension,
+            &service::request("read", body.into_bytes()).encode_for_send()?,
+        )
+    }
+
+    fn upload_status(&self, id: &str) -> Result<u64> {
+        let response = self.call("upload_status", name_body(id))?;
+        Reader::new(&response).read_u64()
+    }
+
+    /// Sends a request as a plain payload, which is how the store expects all of them.
+    fn call(&self, operation: &str, body: Vec<u8>) -> Result<Vec<u8>> {
+        let request = service::request(operation, body).encode_for_send()?;
+        let response = crate::invoke_extension(&self.extension, request)?;
+        service::response_body(Frame::decode(&response)?)
+    }
+}
+
+fn name_body(name: &str) -> Vec<u8> {
+    let mut body = Writer::new();
+    body.write_str(name);
+    body.into_bytes()
+}
+
+/// Uploads `data` as the named blob in the default store; see `Store::put`.
+pub fn put(name: &str, data: &[u8]) -> Result<BlobInfo> {
+    Store::default().put(name, data)
+}
+
+/// See `Store::put_stream`.
+pub fn put_stream(name: &str, input: impl Read) -> Result<BlobInfo> {
+    Store::default().put_stream(name, input)
+}
+
+/// See `Store::get_stream`.
+pub fn get_stream(name: &str) -> Result<BlobReader> {
+    Store::default().get_stream(name)
+}
+
+/// See `Store::stat`.
+pub fn stat(name: &str) -> Result<BlobInfo> {
+    Store::default().stat(name)
+}
+
+/// See `Store::delete`.
+pub fn delete(name: &str) -> Result<()> {
+    Store::default().delete(name)
+}
+
+/// An upload in progress. Content is written in segments with `write_from`, each committed by the
+/// store once it arrives, and the blob appears under its name once `finish` is called. Keep `id`
+/// somewhere durable to be able to resume the upload after a crash.
+#[derive(Debug)]
+pub struct Upload {
+    store: Store,
+    id: String,
+    offset: u64,
+    /// Hashes everything uploaded so far, unless the upload was resumed without rereading it.
+    hasher: Option<Sha256>,
+}
+
+impl Upload {
+    /// The id to resume the upload with.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// How many bytes the store has committed.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Uploads everything read from `input`, a segment at a time, and returns the number of bytes
+    /// uploaded. If a segment fails, everything before it stays committed.
+    pub fn write_from(&mut self, mut input: impl Read) -> Result<u64> {
+        let segment_size = negotiate_segment_size(DEFAULT_SEGMENT_SIZE)
+            .map_err(|err| InvocationError::new(&self.store.extension, 0, 0, err))?;
+        let mut segment = vec![0; segment_size as usize];
+        let mut written = 0;
+        loop {
+            let len = read_segment(&mut input, &mut segment)?;
+            if len == 0 {
+                return Ok(written);
+            }
+            self.append(&segment[..len])?;
+            written += len as u64;
+        }
+    }
+
+    fn append(&mut self, segment: &[u8]) -> Result<()> {
+        let mut header = Writer::new();
+        header.write_str(&self.id);
+        header.write_u64(self.offset);
+        let header = service::request("append", header.into_bytes()).encode_for_send()?;
+
+        // The segment is the tail of the frame's body, so it can go straight to the host after the
+        // header without being copied into a frame first.
+        let mut request = InvocationWriter::new(&self.store.extension)?;
+        request.write_all(&header).map_err(SdkError::from)?;
+        request.write_all(segment).map_err(SdkError::from)?;
+        let response = service::response_body(Frame::decode(&request.finish()?)?)?;
+
+        let committed = Reader::new(&response).read_u64()?;
+        if committed != self.offset + segment.len() as u64 {
+            return Err(SdkError::InvalidPayload);
+        }
+        self.offset = committed;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(segment);
+        }
+        Ok(())
+    }
+
+    /// Completes the upload, making the blob available under its name. Fails with
+    /// `SdkError::Conflict` if the store's hash of the content doesn't match ours.
+    pub fn finish(self) -> Result<BlobInfo> {
+        let mut body = Writer::new();
+        body.write_str(&self.id);
+        match self.hasher {
+            Some(hasher) => {
+                body.write_u8(1);
+                body.write_bytes(&hasher.finalize());
+            }
+            None => {
+                body.write_u8(0);
+                body.write_bytes(&[0; 32]);
+            }
+        }
+        BlobInfo::decode(&self.store.call("finish_upload", body.into_bytes())?)
+    }
+}
+
+/// Reads until `segment` is full or `input` runs out, returning how much was read.
+fn read_segment(input: &mut impl Read, segment: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < segment.len() {
+        match input.read(&mut segment[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(SdkError::from(err)),
+        }
+    }
+    Ok(len)
+}
+
+/// A blob's content, fetched from the host as it's read. If the whole blob is read from the start,
+/// the content is checked against its hash at the end and a mismatch is reported as an
+/// `io::ErrorKind::InvalidData` error instead of the end of the stream.
+#[derive(Debug)]
+pub struct BlobReader {
+    stream: ResponseStream,
+    /// The chunk being handed out, and how much of it has been.
+    buffered: Vec<u8>,
+    position: usize,
+    hasher: Option<Sha256>,
+    info: BlobInfo,
+}
+
+impl BlobReader {
+    /// What the store knew about the blob when the read started.
+    pub fn info(&self) -> &BlobInfo {
+        &self.info
+    }
+
+    fn verify(&mut self) -> io::Result<()> {
+        let Some(hasher) = self.hasher.take() else {
+            return Ok(());
+        };
+        if hasher.finalize()[..] != self.info.sha256 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("content of blob {} doesn't match its hash", self.info.name),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Read for BlobReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffered.len() {
+            match self.stream.next() {
+                Some(chunk) => {
+                    self.buffered = chunk.map_err(io::Error::other)?;
+                    self.position = 0;
+                    if let Some(hasher) = &mut self.hasher {
+                        hasher.update(&self.buffered);
+                    }
+                }
+                None => {
+                    self.verify()?;
+                    return Ok(0);
+                }
+            }
+        }
+
+        let len = buf.len().min(self.buffered.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffered[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
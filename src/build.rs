@@ -0,0 +1,247 @@
+//! Build-script support for generating typed extension clients from a manifest, so guest code
+//! stays in sync with what the mesh provides. In `build.rs`:
+//!
+//! ```ignore
+//! fn main() {
+//!     serval::build::generate("serval-extensions.toml").unwrap();
+//! }
+//! ```
+//!
+//! and in the crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/serval_extensions.rs"));
+//! ```
+//!
+//! The manifest lists extensions and their operations, naming the Rust types used for each
+//! operation's request and response:
+//!
+//! ```toml
+//! [[extension]]
+//! name = "image-resize"
+//! codec = "json"
+//!
+//! [[extension.operation]]
+//! name = "resize"
+//! request = "crate::types::ResizeRequest"
+//! response = "crate::types::ResizeResponse"
+//! ```
+//!
+//! Each extension becomes a module (named after the extension, or `module`) containing a `Client`
+//! with one method per operation. Manifests ending in `.json` are parsed as JSON with the same
+//! structure.
+
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[cfg(feature = "wit")]
+pub mod wit;
+
+/// The file written to `OUT_DIR` by `generate`.
+pub const OUTPUT_FILE: &str = "serval_extensions.rs";
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io(std::io::Error),
+    /// The manifest couldn't be parsed.
+    Manifest(String),
+    /// `OUT_DIR` isn't set, i.e. we aren't running inside a build script.
+    MissingOutDir,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io(err) => write!(f, "{err}"),
+            BuildError::Manifest(err) => write!(f, "invalid extension manifest: {err}"),
+            BuildError::MissingOutDir => write!(f, "OUT_DIR is not set; call this from build.rs"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<std::io::Error> for BuildError {
+    fn from(err: std::io::Error) -> Self {
+        BuildError::Io(err)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default, rename = "extension")]
+    pub extensions: Vec<ExtensionSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtensionSpec {
+    /// The name the extension is registered under.
+    pub name: String,
+    /// The generated module's name; defaults to the extension name in snake case.
+    pub module: Option<String>,
+    /// One of `json`, `msgpack`, `cbor`, `bincode` or `postcard`; defaults to `json`. The
+    /// corresponding SDK feature must be enabled.
+    pub codec: Option<String>,
+    #[serde(default, rename = "operation")]
+    pub operations: Vec<OperationSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationSpec {
+    /// The operation name sent to the extension.
+    pub name: String,
+    /// The generated method's name; defaults to the operation name in snake case.
+    pub method: Option<String>,
+    /// The request type; defaults to `()`.
+    pub request: Option<String>,
+    /// The response type; defaults to `()`.
+    pub response: Option<String>,
+}
+
+impl Manifest {
+    /// Parses a manifest from TOML.
+    pub fn from_toml(source: &str) -> Result<Self, BuildError> {
+        toml::from_str(source).map_err(|err| BuildError::Manifest(err.to_string()))
+    }
+
+    /// Parses a manifest from JSON.
+    pub fn from_json(source: &str) -> Result<Self, BuildError> {
+        serde_json::from_str(source).map_err(|err| BuildError::Manifest(err.to_string()))
+    }
+
+    /// Reads a manifest, picking the format based on the file extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, BuildError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&source),
+            _ => Self::from_toml(&source),
+        }
+    }
+
+    /// Generates Rust source for the client modules described by the manifest.
+    pub fn to_rust(&self) -> Result<String, BuildError> {
+        let mut out = String::from("// @generated by serval::build. Do not edit.\n");
+        for extension in &self.extensions {
+            write_extension(&mut out, extension)?;
+        }
+        Ok(out)
+    }
+}
+
+/// Reads the manifest at `manifest_path` and writes the generated clients to
+/// `$OUT_DIR/serval_extensions.rs`, telling cargo to rerun the build script when the manifest
+/// changes. Returns the path of the generated file.
+pub fn generate(manifest_path: impl AsRef<Path>) -> Result<PathBuf, BuildError> {
+    let manifest_path = manifest_path.as_ref();
+    println!("cargo:rerun-if-changed={}", manifest_path.display());
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(BuildError::MissingOutDir)?;
+    let out_path = Path::new(&out_dir).join(OUTPUT_FILE);
+    std::fs::write(&out_path, Manifest::from_path(manifest_path)?.to_rust()?)?;
+    Ok(out_path)
+}
+
+fn write_extension(out: &mut String, extension: &ExtensionSpec) -> Result<(), BuildError> {
+    let module = identifier(extension.module.as_deref().unwrap_or(&extension.name))?;
+    let codec = codec_path(extension.codec.as_deref().unwrap_or("json"))?;
+
+    // Writing to a String can't fail, hence the unwraps.
+    writeln!(out, "\n/// Client for the `{}` extension.", extension.name).unwrap();
+    writeln!(out, "pub mod {module} {{").unwrap();
+    writeln!(
+        out,
+        "    pub const EXTENSION: &str = {:?};\n",
+        extension.name
+    )
+    .unwrap();
+    out.push_str(CLIENT_PRELUDE);
+    for operation in &extension.operations {
+        let method = identifier(operation.method.as_deref().unwrap_or(&operation.name))?;
+        let request = operation.request.as_deref().unwrap_or("()");
+        let response = operation.response.as_deref().unwrap_or("()");
+        writeln!(
+            out,
+            "\n        /// Invokes the `{}` operation.",
+            operation.name
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        pub fn {method}(&self, request: &{request}) -> ::serval::Result<{response}> {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "            ::serval::invoke_operation(&self.extension, {:?}, &{codec}, request)",
+            operation.name
+        )
+        .unwrap();
+        out.push_str("        }\n");
+    }
+    out.push_str("    }\n}\n");
+    Ok(())
+}
+
+const CLIENT_PRELUDE: &str = "    #[derive(Clone, Debug)]
+    pub struct Client {
+        extension: ::std::string::String,
+    }
+
+    impl ::std::default::Default for Client {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Client {
+        pub fn new() -> Self {
+            Self::with_extension(EXTENSION)
+        }
+
+        pub fn with_extension(extension: impl ::std::convert::Into<::std::string::String>) -> Self {
+            Self { extension: extension.into() }
+        }
+";
+
+fn codec_path(codec: &str) -> Result<&'static str, BuildError> {
+    Ok(match codec {
+        "json" => "::serval::codec::Json",
+        "msgpack" => "::serval::codec::MsgPack",
+        "cbor" => "::serval::codec::Cbor",
+        "bincode" => "::serval::codec::Bincode",
+        "postcard" => "::serval::codec::Postcard",
+        codec => return Err(BuildError::Manifest(format!("unknown codec {codec:?}"))),
+    })
+}
+
+/// Turns an extension or operation name into a snake_case Rust identifier.
+fn identifier(name: &str) -> Result<String, BuildError> {
+    let mut ident = String::new();
+    for (i, c) in name.chars().enumerate() {
+        match c {
+            'A'..='Z' => {
+                if i > 0 && !ident.ends_with('_') {
+                    ident.push('_');
+                }
+                ident.push(c.to_ascii_lowercase());
+            }
+            'a'..='z' | '0'..='9' | '_' => ident.push(c),
+            '-' | '.' | ' ' | '@' => ident.push('_'),
+            c => {
+                return Err(BuildError::Manifest(format!(
+                    "{name:?} can't be turned into an identifier (contains {c:?})"
+                )))
+            }
+        }
+    }
+    match ident.chars().next() {
+        None => Err(BuildError::Manifest("empty name".to_string())),
+        Some('0'..='9') => Ok(format!("_{ident}")),
+        Some(_) => Ok(ident),
+    }
+}
@@ -0,0 +1,397 @@
+//! Generates guest bindings from WIT interface definitions, so extension interfaces can be shared
+//! across the Rust SDK and other language SDKs instead of being re-described in each. In
+//! `build.rs`:
+//!
+//! ```ignore
+//! fn main() {
+//!     serval::build::wit::generate("wit").unwrap();
+//! }
+//! ```
+//!
+//! and in the crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/serval_wit.rs"));
+//! ```
+//!
+//! Every interface in the package becomes a module containing its types (with serde derives, so
+//! the crate needs a `serde` dependency with the `derive` feature) and a `Client` with one method
+//! per function. Clients invoke the extension named after the interface, passing the function name
+//! as the operation and the arguments as a JSON request: a single argument as-is, several as a
+//! tuple. Resources, futures and streams aren't supported.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wit_parser::{
+    Docs, FunctionKind, InterfaceId, PackageId, Resolve, Type, TypeDefKind, TypeId, TypeOwner,
+};
+
+use super::{BuildError, CLIENT_PRELUDE};
+
+/// The file written to `OUT_DIR` by `generate`.
+pub const OUTPUT_FILE: &str = "serval_wit.rs";
+
+/// Parses the WIT package at `path` (a file or a directory with an optional `deps` directory) and
+/// writes bindings for it to `$OUT_DIR/serval_wit.rs`, telling cargo to rerun the build script
+/// when it changes. Returns the path of the generated file.
+pub fn generate(path: impl AsRef<Path>) -> Result<PathBuf, BuildError> {
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}", path.display());
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(BuildError::MissingOutDir)?;
+    let out_path = Path::new(&out_dir).join(OUTPUT_FILE);
+
+    let mut resolve = Resolve::default();
+    let (package, _) = resolve.push_path(path).map_err(wit_error)?;
+    std::fs::write(&out_path, Bindings::new(&resolve, package).to_rust()?)?;
+    Ok(out_path)
+}
+
+/// Generates bindings for a single WIT document held in memory.
+pub fn wit_to_rust(source: &str) -> Result<String, BuildError> {
+    let mut resolve = Resolve::default();
+    let package = resolve.push_str("input.wit", source).map_err(wit_error)?;
+    Bindings::new(&resolve, package).to_rust()
+}
+
+fn wit_error(err: impl std::fmt::Display) -> BuildError {
+    BuildError::Manifest(format!("{err:#}"))
+}
+
+struct Bindings<'a> {
+    resolve: &'a Resolve,
+    package: PackageId,
+}
+
+impl<'a> Bindings<'a> {
+    fn new(resolve: &'a Resolve, package: PackageId) -> Self {
+        Self { resolve, package }
+    }
+
+    fn to_rust(&self) -> Result<String, BuildError> {
+        let mut out = String::from("// @generated by serval::build::wit. Do not edit.\n");
+        for (name, interface) in &self.resolve.packages[self.package].interfaces {
+            self.write_interface(&mut out, name, *interface)?;
+        }
+        Ok(out)
+    }
+
+    fn write_interface(
+        &self,
+        out: &mut String,
+        name: &str,
+        id: InterfaceId,
+    ) -> Result<(), BuildError> {
+        let interface = &self.resolve.interfaces[id];
+        write_docs(
+            out,
+            &interface.docs,
+            "",
+            &format!("Bindings for the `{name}` interface."),
+        );
+        writeln!(out, "pub mod {} {{", snake(name)).unwrap();
+        out.push_str("    #![allow(dead_code, clippy::all)]\n\n");
+        writeln!(out, "    pub const EXTENSION: &str = {name:?};\n").unwrap();
+
+        for (type_name, type_id) in &interface.types {
+            self.write_type(out, type_name, *type_id, id)?;
+        }
+
+        if interface.functions.is_empty() {
+            out.push_str("}\n\n");
+            return Ok(());
+        }
+
+        out.push_str(CLIENT_PRELUDE);
+        for function in interface.functions.values() {
+            if !matches!(function.kind, FunctionKind::Freestanding) {
+                return Err(BuildError::Manifest(format!(
+                    "{name}.{}: only freestanding, synchronous functions are supported",
+                    function.name
+                )));
+            }
+            let params = function
+                .params
+                .iter()
+                .map(|param| {
+                    Ok(format!(
+                        "{}: &{}",
+                        snake(&param.name),
+                        self.type_ref(&param.ty, id)?
+                    ))
+                })
+                .collect::<Result<Vec<_>, BuildError>>()?;
+            let args: Vec<_> = function
+                .params
+                .iter()
+                .map(|param| snake(&param.name))
+                .collect();
+            let request = match args.as_slice() {
+                [arg] => arg.clone(),
+                args => format!(
+                    "&({})",
+                    args.iter().map(|arg| format!("{arg},")).collect::<String>()
+                ),
+            };
+            let result = match &function.result {
+                Some(ty) => self.type_ref(ty, id)?,
+                None => "()".to_string(),
+            };
+
+            out.push('\n');
+            write_docs(
+                out,
+                &function.docs,
+                "        ",
+                &format!("Invokes `{}`.", function.name),
+            );
+            writeln!(
+                out,
+                "        pub fn {}(&self{}) -> ::serval::Result<{result}> {{",
+                snake(&function.name),
+                params
+                    .iter()
+                    .map(|param| format!(", {param}"))
+                    .collect::<String>(),
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "            ::serval::invoke_operation(&self.extension, {:?}, &::serval::codec::Json, {request})",
+                function.name
+            )
+            .unwrap();
+            out.push_str("        }\n");
+        }
+        out.push_str("    }\n}\n\n");
+        Ok(())
+    }
+
+    fn write_type(
+        &self,
+        out: &mut String,
+        name: &str,
+        id: TypeId,
+        interface: InterfaceId,
+    ) -> Result<(), BuildError> {
+        const DERIVE: &str =
+            "    #[derive(Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]\n";
+        let def = &self.resolve.types[id];
+        let rust_name = camel(name);
+        write_docs(out, &def.docs, "    ", "");
+
+        match &def.kind {
+            // A `use` of a type from another interface shows up as an alias pointing at it.
+            TypeDefKind::Type(ty) => {
+                writeln!(
+                    out,
+                    "    pub type {rust_name} = {};",
+                    self.type_ref(ty, interface)?
+                )
+                .unwrap();
+            }
+            TypeDefKind::Record(record) => {
+                out.push_str(DERIVE);
+                writeln!(out, "    pub struct {rust_name} {{").unwrap();
+                for field in &record.fields {
+                    write_docs(out, &field.docs, "        ", "");
+                    writeln!(
+                        out,
+                        "        #[serde(rename = {:?})]\n        pub {}: {},",
+                        field.name,
+                        snake(&field.name),
+                        self.type_ref(&field.ty, interface)?
+                    )
+                    .unwrap();
+                }
+                out.push_str("    }\n");
+            }
+            TypeDefKind::Flags(flags) => {
+                out.push_str(DERIVE);
+                writeln!(out, "    pub struct {rust_name} {{").unwrap();
+                for flag in &flags.flags {
+                    writeln!(
+                        out,
+                        "        #[serde(rename = {:?}, default)]\n        pub {}: bool,",
+                        flag.name,
+                        snake(&flag.name)
+                    )
+                    .unwrap();
+                }
+                out.push_str("    }\n");
+            }
+            TypeDefKind::Enum(enum_) => {
+                out.push_str(DERIVE);
+                writeln!(out, "    pub enum {rust_name} {{").unwrap();
+                for case in &enum_.cases {
+                    write_docs(out, &case.docs, "        ", "");
+                    writeln!(
+                        out,
+                        "        #[serde(rename = {:?})]\n        {},",
+                        case.name,
+                        camel(&case.name)
+                    )
+                    .unwrap();
+                }
+                out.push_str("    }\n");
+            }
+            TypeDefKind::Variant(variant) => {
+                out.push_str(DERIVE);
+                writeln!(out, "    pub enum {rust_name} {{").unwrap();
+                for case in &variant.cases {
+                    write_docs(out, &case.docs, "        ", "");
+                    let payload = match &case.ty {
+                        Some(ty) => format!("({})", self.type_ref(ty, interface)?),
+                        None => String::new(),
+                    };
+                    writeln!(
+                        out,
+                        "        #[serde(rename = {:?})]\n        {}{payload},",
+                        case.name,
+                        camel(&case.name)
+                    )
+                    .unwrap();
+                }
+                out.push_str("    }\n");
+            }
+            _ => {
+                writeln!(
+                    out,
+                    "    pub type {rust_name} = {};",
+                    self.anonymous_type(id, interface)?
+                )
+                .unwrap();
+            }
+        }
+        out.push('\n');
+        Ok(())
+    }
+
+    /// Renders a reference to `ty` from code inside `interface`'s module.
+    fn type_ref(&self, ty: &Type, interface: InterfaceId) -> Result<String, BuildError> {
+        Ok(match ty {
+            Type::Bool => "bool".into(),
+            Type::U8 => "u8".into(),
+            Type::U16 => "u16".into(),
+            Type::U32 => "u32".into(),
+            Type::U64 => "u64".into(),
+            Type::S8 => "i8".into(),
+            Type::S16 => "i16".into(),
+            Type::S32 => "i32".into(),
+            Type::S64 => "i64".into(),
+            Type::F32 => "f32".into(),
+            Type::F64 => "f64".into(),
+            Type::Char => "char".into(),
+            Type::String => "::std::string::String".into(),
+            Type::ErrorContext => return Err(unsupported("error-context")),
+            Type::Id(id) => {
+                let def = &self.resolve.types[*id];
+                match (&def.name, def.owner) {
+                    (Some(name), TypeOwner::Interface(owner)) if owner == interface => camel(name),
+                    (Some(name), TypeOwner::Interface(owner)) => {
+                        let owner = &self.resolve.interfaces[owner];
+                        if owner.package != Some(self.package) {
+                            return Err(unsupported("types from other packages"));
+                        }
+                        let module = owner
+                            .name
+                            .as_deref()
+                            .ok_or_else(|| unsupported("anonymous interfaces"))?;
+                        format!("super::{}::{}", snake(module), camel(name))
+                    }
+                    (Some(_), _) => return Err(unsupported("types defined in worlds")),
+                    (None, _) => self.anonymous_type(*id, interface)?,
+                }
+            }
+        })
+    }
+
+    /// Renders the structural type behind an unnamed (or aliased) type definition.
+    fn anonymous_type(&self, id: TypeId, interface: InterfaceId) -> Result<String, BuildError> {
+        let opt = |ty: &Option<Type>| match ty {
+            Some(ty) => self.type_ref(ty, interface),
+            None => Ok("()".to_string()),
+        };
+        Ok(match &self.resolve.types[id].kind {
+            TypeDefKind::Type(ty) => self.type_ref(ty, interface)?,
+            TypeDefKind::Option(ty) => {
+                format!("::std::option::Option<{}>", self.type_ref(ty, interface)?)
+            }
+            TypeDefKind::Result(result) => format!(
+                "::std::result::Result<{}, {}>",
+                opt(&result.ok)?,
+                opt(&result.err)?
+            ),
+            TypeDefKind::List(ty) => format!("::std::vec::Vec<{}>", self.type_ref(ty, interface)?),
+            TypeDefKind::FixedLengthList(ty, len) => {
+                format!("[{}; {len}]", self.type_ref(ty, interface)?)
+            }
+            TypeDefKind::Map(key, value) => format!(
+                "::std::collections::HashMap<{}, {}>",
+                self.type_ref(key, interface)?,
+                self.type_ref(value, interface)?
+            ),
+            TypeDefKind::Tuple(tuple) => {
+                let types = tuple
+                    .types
+                    .iter()
+                    .map(|ty| Ok(format!("{},", self.type_ref(ty, interface)?)))
+                    .collect::<Result<String, BuildError>>()?;
+                format!("({types})")
+            }
+            TypeDefKind::Record(_)
+            | TypeDefKind::Flags(_)
+            | TypeDefKind::Enum(_)
+            | TypeDefKind::Variant(_) => {
+                return Err(unsupported("anonymous records, flags, enums or variants"))
+            }
+            TypeDefKind::Resource | TypeDefKind::Handle(_) => return Err(unsupported("resources")),
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                return Err(unsupported("futures and streams"))
+            }
+            TypeDefKind::Unknown => return Err(unsupported("unresolved types")),
+        })
+    }
+}
+
+fn unsupported(what: &str) -> BuildError {
+    BuildError::Manifest(format!("WIT bindings don't support {what}"))
+}
+
+/// Writes the WIT doc comment if there is one, otherwise `fallback` (if non-empty).
+fn write_docs(out: &mut String, docs: &Docs, indent: &str, fallback: &str) {
+    let docs = docs.contents.as_deref().unwrap_or(fallback);
+    for line in docs.lines() {
+        writeln!(out, "{indent}/// {line}").unwrap();
+    }
+}
+
+/// kebab-case to snake_case, escaping Rust keywords.
+fn snake(name: &str) -> String {
+    let ident = name.replace('-', "_").to_lowercase();
+    match ident.as_str() {
+        "self" | "super" | "crate" => format!("{ident}_"),
+        "as" | "break" | "const" | "continue" | "else" | "enum" | "extern" | "false" | "fn"
+        | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut"
+        | "pub" | "ref" | "return" | "static" | "struct" | "trait" | "true" | "type" | "unsafe"
+        | "use" | "where" | "while" | "async" | "await" | "dyn" | "abstract" | "become" | "box"
+        | "do" | "final" | "macro" | "override" | "priv" | "typeof" | "unsized" | "virtual"
+        | "yield" | "try" | "gen" => format!("r#{ident}"),
+        _ => ident,
+    }
+}
+
+/// kebab-case to UpperCamelCase.
+fn camel(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect()
+}
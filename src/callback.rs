@@ -0,0 +1,106 @@
+//! Guest callbacks the host can call while an extension operation is in flight.
+//!
+//! A callback is a closure registered under a name such as `on_data`. Attaching it to an
+//! invocation (see `Invocation::callback`) tells the host its id; whenever the extension calls back
+//! under that name, the host calls our `serval_callback` export with the id and an encoded frame,
+//! and the closure's output is handed back the same way an entrypoint's is.
+//!
+//! Callbacks only ever run on the guest's own thread, nested inside the host call that's waiting
+//! on the extension. A callback may invoke extensions itself, but the host can't call back into a
+//! callback that's already running; that call fails instead of re-entering the closure.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::entrypoint::{run_entrypoint, EntrypointOutput, FromInput};
+use crate::frame::Frame;
+use crate::GuestError;
+
+type Handler = Box<dyn FnMut(Frame) -> Result<Vec<u8>, GuestError>>;
+
+thread_local! {
+    /// Registered callbacks by id. A `None` slot is a callback that's currently running.
+    static CALLBACKS: RefCell<HashMap<u32, Option<Handler>>> = RefCell::new(HashMap::new());
+    static NEXT_ID: Cell<u32> = const { Cell::new(1) };
+}
+
+/// A registered callback. It can be attached to any number of invocations, and is unregistered
+/// when this is dropped.
+#[derive(Debug)]
+pub struct Callback {
+    id: u32,
+    name: String,
+}
+
+impl Callback {
+    /// Registers `f` as a callback the extension knows as `name`. The frame passed to it carries
+    /// whatever headers the extension sent along with the body.
+    pub fn register<O, F>(name: impl Into<String>, mut f: F) -> Self
+    where
+        O: EntrypointOutput,
+        F: FnMut(Frame) -> O + 'static,
+    {
+        let id = NEXT_ID.replace(NEXT_ID.get().wrapping_add(1));
+        let handler: Handler = Box::new(move |frame| f(frame).into_output());
+        CALLBACKS.with_borrow_mut(|callbacks| callbacks.insert(id, Some(handler)));
+        Self {
+            id,
+            name: name.into(),
+        }
+    }
+
+    /// The id the host passes to `serval_callback` to call this callback.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The name the extension calls this callback by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the `tags::CALLBACK` header field announcing this callback.
+    pub(crate) fn header_value(&self) -> Vec<u8> {
+        let mut value = self.id.to_le_bytes().to_vec();
+        value.extend_from_slice(self.name.as_bytes());
+        value
+    }
+}
+
+impl Drop for Callback {
+    fn drop(&mut self) {
+        CALLBACKS.with_borrow_mut(|callbacks| callbacks.remove(&self.id));
+    }
+}
+
+impl FromInput for Frame {
+    fn from_input(input: Vec<u8>) -> Result<Self, GuestError> {
+        Ok(Frame::decode(&input)?)
+    }
+}
+
+/// Called by the host to run the callback registered under `callback_id`; see the module docs.
+#[no_mangle]
+pub extern "C" fn serval_callback(callback_id: u32, input_ptr: usize) -> isize {
+    run_entrypoint(input_ptr, |frame: Frame| dispatch(callback_id, frame))
+}
+
+fn dispatch(id: u32, frame: Frame) -> Result<Vec<u8>, GuestError> {
+    let mut handler = CALLBACKS
+        .with_borrow_mut(|callbacks| match callbacks.get_mut(&id) {
+            Some(slot) => slot
+                .take()
+                .ok_or_else(|| format!("callback {id} is already running")),
+            None => Err(format!("no callback is registered with id {id}")),
+        })
+        .map_err(GuestError::from)?;
+
+    let output = handler(frame);
+    // Put the handler back unless the callback was dropped while it was running.
+    CALLBACKS.with_borrow_mut(|callbacks| {
+        if let Some(slot) = callbacks.get_mut(&id) {
+            *slot = Some(handler);
+        }
+    });
+    output
+}
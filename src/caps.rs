@@ -0,0 +1,139 @@
+//! What the job has been permitted to do, so it can check up front instead of discovering a
+//! missing grant halfway through its work. A job that can do without a capability can check for it
+//! with `has` or `can_invoke` and take another route; one that can't can call `require` at the
+//! start and fail straight away with `SdkError::MissingCapability` naming what's missing.
+//!
+//! ```ignore
+//! use serval::caps::{self, Capability};
+//!
+//! caps::require(&Capability::Network)?;
+//! if !caps::can_invoke("geo.lookup")? {
+//!     serval::log::warn("geo.lookup isn't granted, skipping enrichment");
+//! }
+//! ```
+
+use std::fmt;
+
+use crate::wire::Reader;
+use crate::{host, take_host_bytes, ExtensionErrorCode, Result, SdkError};
+
+/// Something the host can permit a job to do. Hosts name them with the strings `Display` produces.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Opening sockets with `net`: `network`.
+    Network,
+    /// Using the job's storage with `fs`: `storage`.
+    Storage,
+    /// Invoking every extension: `extension:*`.
+    AllExtensions,
+    /// Invoking the named extension: `extension:<name>`.
+    Extension(String),
+    /// A capability this SDK doesn't know about, by the name the host gave it.
+    Other(String),
+}
+
+impl Capability {
+    pub(crate) fn parse(name: &str) -> Self {
+        match name {
+            "network" => Capability::Network,
+            "storage" => Capability::Storage,
+            "extension:*" => Capability::AllExtensions,
+            _ => match name.strip_prefix("extension:") {
+                Some(extension) => Capability::Extension(extension.to_string()),
+                None => Capability::Other(name.to_string()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::Network => write!(f, "network"),
+            Capability::Storage => write!(f, "storage"),
+            Capability::AllExtensions => write!(f, "extension:*"),
+            Capability::Extension(name) => write!(f, "extension:{name}"),
+            Capability::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Returns the capabilities the job has been granted.
+pub fn granted_capabilities() -> Result<Vec<Capability>> {
+    let out_ptr = unsafe { host::caps_granted() };
+    if out_ptr < 0 {
+        return Err(ExtensionErrorCode::from(out_ptr as i32).into());
+    }
+    decode(&take_host_bytes(out_ptr as usize)?)
+}
+
+/// Whether the job is permitted to invoke the extension `name`, either by name or because it may
+/// invoke every extension. The host answers rather than the SDK working it out from
+/// `granted_capabilities`, since hosts may grant extensions by rules of their own, such as a
+/// prefix.
+pub fn can_invoke(name: &str) -> Result<bool> {
+    let status = unsafe { host::caps_can_invoke(name.as_ptr() as usize, name.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(status != 0)
+}
+
+/// Whether the job has been granted `capability`.
+pub fn has(capability: &Capability) -> Result<bool> {
+    if let Capability::Extension(name) = capability {
+        return can_invoke(name);
+    }
+    Ok(granted_capabilities()?.contains(capability))
+}
+
+/// Fails with `SdkError::MissingCapability` unless the job has been granted `capability`.
+pub fn require(capability: &Capability) -> Result<()> {
+    if !has(capability)? {
+        return Err(SdkError::MissingCapability(capability.to_string()));
+    }
+    Ok(())
+}
+
+/// Decodes the layout `caps_granted` returns: a u32 count followed by that many length-prefixed
+/// capability names.
+fn decode(bytes: &[u8]) -> Result<Vec<Capability>> {
+    let mut reader = Reader::new(bytes);
+    let count = reader.read_u32()?;
+    let mut capabilities = Vec::new();
+    for _ in 0..count {
+        capabilities.push(Capability::parse(reader.read_str()?));
+    }
+    Ok(capabilities)
+}
+
+/// What the stand-in hosts grant unless told otherwise: everything.
+#[cfg(any(feature = "mock-host", feature = "harness"))]
+pub(crate) fn everything() -> Vec<Capability> {
+    vec![
+        Capability::Network,
+        Capability::Storage,
+        Capability::AllExtensions,
+    ]
+}
+
+/// Whether `capabilities` permit invoking the extension `name`, for the stand-in hosts.
+#[cfg(any(feature = "mock-host", feature = "harness"))]
+pub(crate) fn permits(capabilities: &[Capability], name: &str) -> bool {
+    capabilities.iter().any(|capability| match capability {
+        Capability::AllExtensions => true,
+        Capability::Extension(extension) => extension == name,
+        _ => false,
+    })
+}
+
+/// Encodes `capabilities` in the layout `decode` reads, for the stand-in hosts.
+#[cfg(any(feature = "mock-host", feature = "harness"))]
+pub(crate) fn encode(capabilities: &[Capability]) -> Vec<u8> {
+    let mut writer = crate::wire::Writer::new();
+    writer.write_u32(capabilities.len() as u32);
+    for capability in capabilities {
+        writer.write_str(&capability.to_string());
+    }
+    writer.into_bytes()
+}
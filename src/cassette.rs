@@ -0,0 +1,215 @@
+//! Recording extension traffic and playing it back, for golden tests of guest logic against real
+//! responses without the mesh.
+//!
+//! Record a run on a real host, then save the cassette somewhere the test can get at it:
+//!
+//! ```ignore
+//! serval::cassette::start_recording();
+//! run_report()?;
+//! serval::fs::write("report.cassette", serval::cassette::stop_recording().encode())?;
+//! ```
+//!
+//! and replay it natively under the `mock-host` feature:
+//!
+//! ```ignore
+//! let cassette = Cassette::decode(include_bytes!("report.cassette"))?;
+//! let replay = cassette.replay();
+//! run_report()?;
+//! replay.assert_finished();
+//! ```
+//!
+//! What's recorded is each invocation's extension, request and response as they crossed the ABI:
+//! framed calls are recorded as encoded frames, and compressed payloads compressed. Calls made with
+//! `invoke_extension`, `invoke_extension_owned`, `invoke_extension_with_timeout` and
+//! `frame::invoke_framed` (which the service clients and typed APIs are built on) are recorded;
+//! streams, channels, batches, pipelines and async calls aren't.
+//!
+//! An encoded cassette is a version byte (see `CASSETTE_VERSION`) and a u32 count, followed by
+//! that many interactions: a length-prefixed extension name and request, an i32 status (0 for a
+//! response, otherwise a negative `ExtensionErrorCode`) and a length-prefixed response, which is
+//! empty for failures.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::wire::{Reader, Writer};
+use crate::{ExtensionErrorCode, Result, SdkError};
+
+/// The version byte encoded cassettes start with.
+pub const CASSETTE_VERSION: u8 = 1;
+
+/// One recorded invocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interaction {
+    pub extension: String,
+    pub request: Vec<u8>,
+    /// The response, or the error code the host reported instead.
+    pub response: std::result::Result<Vec<u8>, ExtensionErrorCode>,
+}
+
+/// A sequence of recorded invocations, in the order they were made.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u8(CASSETTE_VERSION);
+        writer.write_u32(self.interactions.len() as u32);
+        for interaction in &self.interactions {
+            writer.write_str(&interaction.extension);
+            writer.write_prefixed(&interaction.request);
+            match &interaction.response {
+                Ok(response) => {
+                    writer.write_i32(0);
+                    writer.write_prefixed(response);
+                }
+                Err(code) => {
+                    writer.write_i32(code.as_raw());
+                    writer.write_prefixed(&[]);
+                }
+            }
+        }
+        writer.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_u8()? != CASSETTE_VERSION {
+            return Err(SdkError::InvalidPayload);
+        }
+        let count = reader.read_u32()?;
+        let interactions = (0..count)
+            .map(|_| {
+                let extension = reader.read_str()?.to_string();
+                let request = reader.read_prefixed()?.to_vec();
+                let status = reader.read_i32()?;
+                let response = reader.read_prefixed()?;
+                Ok(Interaction {
+                    extension,
+                    request,
+                    response: match status {
+                        0 => Ok(response.to_vec()),
+                        code => Err(ExtensionErrorCode::from(code)),
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { interactions })
+    }
+}
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static RECORDED: Mutex<Vec<Interaction>> = Mutex::new(Vec::new());
+
+/// Starts recording invocations, discarding anything recorded before.
+pub fn start_recording() {
+    RECORDED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clear();
+    RECORDING.store(true, Ordering::Relaxed);
+}
+
+/// Stops recording and returns everything recorded since `start_recording`.
+pub fn stop_recording() -> Cassette {
+    RECORDING.store(false, Ordering::Relaxed);
+    let interactions = std::mem::take(&mut *RECORDED.lock().unwrap_or_else(|err| err.into_inner()));
+    Cassette { interactions }
+}
+
+/// Records an invocation if recording is on. `response` is borrowed as bytes so that callers that
+/// don't otherwise have a `Vec` only copy when something is listening. Errors that didn't come from
+/// the host, such as a response that failed to decode, aren't recorded.
+pub(crate) fn record<T: AsRef<[u8]>>(extension: &str, request: &[u8], response: &Result<T>) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+    let response = match response {
+        Ok(response) => Ok(response.as_ref().to_vec()),
+        Err(err) => match err.host_code() {
+            Some(code) => Err(code),
+            None => return,
+        },
+    };
+    RECORDED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(Interaction {
+            extension: extension.to_string(),
+            request: request.to_vec(),
+            response,
+        });
+}
+
+/// Serves a cassette from the mock host; see `Cassette::replay`.
+#[cfg(feature = "mock-host")]
+#[derive(Debug)]
+pub struct Replay {
+    remaining: std::rc::Rc<std::cell::RefCell<std::collections::VecDeque<Interaction>>>,
+}
+
+#[cfg(feature = "mock-host")]
+impl Cassette {
+    /// Registers every extension on the cassette with the calling thread's mock host, answering
+    /// with the recorded responses. Calls have to arrive in the order they were recorded: one
+    /// that doesn't match the next interaction on the cassette panics, naming the extension it
+    /// expected.
+    pub fn replay(&self) -> Replay {
+        let remaining = std::rc::Rc::new(std::cell::RefCell::new(
+            self.interactions
+                .iter()
+                .cloned()
+                .collect::<std::collections::VecDeque<_>>(),
+        ));
+        let mut extensions: Vec<&str> = self
+            .interactions
+            .iter()
+            .map(|interaction| interaction.extension.as_str())
+            .collect();
+        extensions.sort_unstable();
+        extensions.dedup();
+        for extension in extensions {
+            let name = extension.to_string();
+            let remaining = remaining.clone();
+            crate::mock::respond(extension, move |request| {
+                let mut remaining = remaining.borrow_mut();
+                match remaining.front() {
+                    Some(next) if next.extension == name && next.request == request => {
+                        remaining.pop_front().unwrap().response
+                    }
+                    Some(next) => panic!(
+                        "cassette expected a call to {} with {:?}, got a call to {name} with {:?}",
+                        next.extension,
+                        String::from_utf8_lossy(&next.request),
+                        String::from_utf8_lossy(request),
+                    ),
+                    None => panic!("cassette is finished, got a call to {name}"),
+                }
+            });
+        }
+        Replay { remaining }
+    }
+}
+
+#[cfg(feature = "mock-host")]
+impl Replay {
+    /// How many recorded interactions haven't been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining.borrow().len()
+    }
+
+    /// Panics unless every recorded interaction has been replayed.
+    #[track_caller]
+    pub fn assert_finished(&self) {
+        if let Some(next) = self.remaining.borrow().front() {
+            panic!(
+                "cassette has {} interactions left, starting with a call to {}",
+                self.remaining(),
+                next.extension
+            );
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Helpers for extensions that speak CBOR, which is what our Go and JS extensions standardize on.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::{invoke_with_codec, Cbor};
+use crate::{CodecError, Result, SdkError};
+
+/// Encodes a value as a CBOR payload.
+pub fn to_payload_cbor<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    write_payload_cbor(value, &mut payload)?;
+    Ok(payload)
+}
+
+/// Like `to_payload_cbor`, but appends the payload to `buf`.
+pub fn write_payload_cbor<T: Serialize + ?Sized>(value: &T, buf: &mut Vec<u8>) -> Result<()> {
+    ciborium::ser::into_writer(value, buf)
+        .map_err(|err| SdkError::Encode(CodecError::new(err.to_string())))
+}
+
+/// Decodes a CBOR payload. If decoding fails, the error carries the byte offset the decoder
+/// reported, if any.
+pub fn from_payload_cbor<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    ciborium::de::from_reader(bytes).map_err(|err| {
+        let offset = match &err {
+            ciborium::de::Error::Syntax(offset) => Some(*offset),
+            ciborium::de::Error::Semantic(offset, _) => *offset,
+            _ => None,
+        };
+        let codec_err = CodecError::new(err.to_string());
+        SdkError::Decode(match offset {
+            Some(offset) => codec_err.with_offset(offset),
+            None => codec_err,
+        })
+    })
+}
+
+/// Invokes an extension with a CBOR-encoded request and decodes its CBOR response.
+pub fn invoke_cbor<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    invoke_with_codec(extension_name, &Cbor, request)
+}
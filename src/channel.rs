@@ -0,0 +1,140 @@
+//! Long-lived duplex channels to interactive extensions.
+//!
+//! Unlike an invocation, a channel carries any number of messages in both directions until it's
+//! closed. Either side can close its sending half first: once the guest calls `close_send` the
+//! extension sees the end of its input but can keep replying, and once the extension closes its
+//! half `recv` returns `None`. The channel is released when it's dropped or `close`d.
+//!
+//! Messages from the extension are flow controlled the same way streamed responses are; see
+//! `Channel::set_window_size`.
+
+use crate::stream::{FlowControl, DEFAULT_WINDOW_SIZE};
+use crate::{
+    check_ptr, check_status, get_bytes_from_host, host, InvocationError, Result, SdkError,
+};
+
+/// A duplex channel to an extension; see the module docs.
+#[derive(Debug)]
+pub struct Channel {
+    id: u32,
+    extension: String,
+    send_closed: bool,
+    recv_closed: bool,
+    released: bool,
+    flow: FlowControl,
+}
+
+impl Channel {
+    /// Opens a channel to the named extension.
+    pub fn open(extension_name: &str) -> Result<Self> {
+        let id = unsafe {
+            host::open_channel(
+                extension_name.as_ptr() as usize,
+                extension_name.len() as u32,
+            )
+        };
+
+        check_status(id, extension_name, 0)?;
+        Ok(Self {
+            id: id as u32,
+            extension: extension_name.to_string(),
+            send_closed: false,
+            recv_closed: false,
+            released: false,
+            flow: FlowControl::new(DEFAULT_WINDOW_SIZE),
+        })
+    }
+
+    /// The extension on the other end of the channel.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Sets how many bytes of messages the extension may send ahead of what's been received, which
+    /// also caps the size of a single message. Defaults to `DEFAULT_WINDOW_SIZE`. 0 is taken as 1,
+    /// since the extension couldn't send anything at all otherwise.
+    pub fn set_window_size(&mut self, bytes: u32) {
+        self.flow.set_window(bytes);
+    }
+
+    /// Sends a message to the extension. Fails with `SdkError::ChannelClosed` if either side has
+    /// closed it for sending.
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        if self.send_closed {
+            return Err(self.closed(message.len()));
+        }
+
+        let status =
+            unsafe { host::channel_send(self.id, message.as_ptr() as usize, message.len() as u32) };
+        let result = check_status(status, &self.extension, message.len());
+        if matches!(&result, Err(err) if *err.root() == SdkError::ChannelClosed) {
+            self.send_closed = true;
+        }
+        result
+    }
+
+    /// Blocks until the extension sends the next message, returning `None` once it has closed its
+    /// side of the channel.
+    pub fn recv(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.recv_closed {
+            return Ok(None);
+        }
+
+        if let Some(credit) = self.flow.top_up() {
+            let status = unsafe { host::channel_grant(self.id, credit) };
+            check_status(status, &self.extension, 0)?;
+        }
+
+        let out_ptr = unsafe { host::channel_recv(self.id) };
+        if out_ptr == 0 {
+            self.recv_closed = true;
+            return Ok(None);
+        }
+        let ptr = check_ptr(out_ptr, &self.extension, 0)?;
+        get_bytes_from_host(ptr)
+            .inspect(|message| self.flow.received(message.len()))
+            .map(Some)
+            .map_err(|err| InvocationError::new(&self.extension, 0, out_ptr as i32, err).into())
+    }
+
+    /// Tells the extension that no more messages are coming, while still letting it send any
+    /// remaining replies. Closing an already closed side does nothing.
+    pub fn close_send(&mut self) -> Result<()> {
+        if self.send_closed {
+            return Ok(());
+        }
+
+        self.send_closed = true;
+        let status = unsafe { host::channel_close_send(self.id) };
+        check_status(status, &self.extension, 0)
+    }
+
+    /// Closes both sides of the channel and releases it. Messages the extension sent that haven't
+    /// been received yet are discarded.
+    pub fn close(mut self) -> Result<()> {
+        self.released = true;
+        let status = unsafe { host::channel_close(self.id) };
+        check_status(status, &self.extension, 0)
+    }
+
+    fn closed(&self, payload_len: usize) -> SdkError {
+        let status = SdkError::ChannelClosed
+            .code()
+            .map_or(0, |code| code.as_raw());
+        InvocationError::new(
+            &self.extension,
+            payload_len,
+            status,
+            SdkError::ChannelClosed,
+        )
+        .into()
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        if !self.released {
+            unsafe { host::channel_close(self.id) };
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! A common interface over the payload formats the SDK supports, so typed invocation APIs can be
+//! written once and projects can plug in their own wire format.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::arena::with_scratch;
+use crate::frame::{send_frame, Frame};
+use crate::{invoke_extension, Result};
+
+/// A serde-based payload encoding.
+pub trait Codec {
+    /// A MIME-like identifier for the encoding, e.g. `application/json`.
+    fn content_type(&self) -> &'static str;
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>>;
+
+    /// Like `encode`, but appends to `buf`, so callers can reuse one buffer for many values. The
+    /// default goes through `encode`; formats that can write into a buffer directly override it.
+    fn encode_into<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&self.encode(value)?);
+        Ok(())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T>;
+}
+
+/// Invokes an extension, encoding the request and decoding the response with `codec`.
+pub fn invoke_with_codec<C: Codec, Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    codec: &C,
+    request: &Req,
+) -> Result<Resp> {
+    let response = with_scratch(|payload| {
+        codec.encode_into(request, payload)?;
+        invoke_extension(extension_name, payload)
+    })?;
+    codec.decode(&response)
+}
+
+/// Invokes a named operation on an extension that exposes several. The operation is carried in the
+/// frame header and the body is encoded with `codec`; this is what clients generated by
+/// `#[serval::extension_client]` call into.
+pub fn invoke_operation<C: Codec, Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    operation: &str,
+    codec: &C,
+    request: &Req,
+) -> Result<Resp> {
+    let mut frame = Frame::new(codec.encode(request)?).with_content_type(codec.content_type());
+    frame.set_operation(operation);
+    let response = send_frame(extension_name, frame)?;
+    codec.decode(&response.body)
+}
+
+/// JSON, via `serval::json`.
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn content_type(&self) -> &'static str {
+        crate::content_type::JSON
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        crate::json::to_payload_json(value)
+    }
+
+    fn encode_into<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> Result<()> {
+        crate::json::write_payload_json(value, buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        crate::json::from_payload_json(bytes)
+    }
+}
+
+/// MessagePack, via `serval::msgpack`.
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsgPack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MsgPack {
+    fn content_type(&self) -> &'static str {
+        crate::content_type::MSGPACK
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        crate::msgpack::to_payload_msgpack(value)
+    }
+
+    fn encode_into<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> Result<()> {
+        crate::msgpack::write_payload_msgpack(value, buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        crate::msgpack::from_payload_msgpack(bytes)
+    }
+}
+
+/// CBOR, via `serval::cbor`.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn content_type(&self) -> &'static str {
+        crate::content_type::CBOR
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        crate::cbor::to_payload_cbor(value)
+    }
+
+    fn encode_into<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> Result<()> {
+        crate::cbor::write_payload_cbor(value, buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        crate::cbor::from_payload_cbor(bytes)
+    }
+}
+
+/// Headered bincode, via `serval::bincode`.
+#[cfg(feature = "bincode")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    fn content_type(&self) -> &'static str {
+        crate::content_type::BINCODE
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        crate::bincode::to_payload_bincode(value)
+    }
+
+    fn encode_into<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> Result<()> {
+        crate::bincode::write_payload_bincode(value, buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        crate::bincode::from_payload_bincode(bytes)
+    }
+}
+
+/// Postcard, via `serval::postcard`.
+#[cfg(feature = "postcard")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Codec for Postcard {
+    fn content_type(&self) -> &'static str {
+        crate::content_type::POSTCARD
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        crate::postcard::to_payload_postcard(value)
+    }
+
+    fn encode_into<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> Result<()> {
+        crate::postcard::write_payload_postcard(value, buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        crate::postcard::from_payload_postcard(bytes)
+    }
+}
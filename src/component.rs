@@ -0,0 +1,52 @@
+//! Builds the guest as a WebAssembly component instead of a core module. The host interface is
+//! expressed as the WIT world in `wit/serval.wit` (also available as `WIT`), with bindings
+//! generated by wit-bindgen. The `component` feature swaps the imports declared in `host` for the
+//! functions in `imports`, which forward each call to the generated bindings, so the rest of the
+//! SDK and the guest's own code work unchanged. The pointer and length ABI of core-module guests
+//! becomes the legacy mode only those still use.
+//!
+//! The world's `entrypoint.run-main` export runs the guest's `#[serval::main]` function, which
+//! component guests must define. Other `#[serval::export]` functions remain core exports, which
+//! components don't expose. Produce the component from the built module with
+//! `wasm-tools component new`.
+
+/// The WIT definition of the host interface, for hosts and tooling.
+pub const WIT: &str = include_str!("../wit/serval.wit");
+
+mod bindings {
+    wit_bindgen::generate!({
+        path: "wit",
+        world: "guest",
+    });
+
+    #[cfg(target_arch = "wasm32")]
+    use super::Component;
+    #[cfg(target_arch = "wasm32")]
+    export!(Component);
+}
+
+// The mock host takes precedence when both are enabled, as it does over the real imports.
+#[cfg(not(feature = "mock-host"))]
+pub(crate) mod imports;
+
+#[cfg(target_arch = "wasm32")]
+struct Component;
+
+#[cfg(target_arch = "wasm32")]
+impl bindings::exports::serval::host::entrypoint::Guest for Component {
+    fn run_main(input: Vec<u8>) -> Result<(), ()> {
+        extern "C" {
+            /// Defined by `#[serval::main]` in the guest.
+            fn serval_main(input_ptr: usize) -> isize;
+        }
+
+        let input_ptr = crate::bytes_to_host(&input);
+        drop(input);
+        // Safety: `serval_main` takes ownership of the length-prefixed buffer at `input_ptr`, the
+        // same as when the host calls it directly.
+        match unsafe { serval_main(input_ptr) } {
+            crate::entrypoint::ENTRYPOINT_FAILED => Err(()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,419 @@
+//! The `host` imports for component guests, with the same names and signatures as the core-module
+//! ones, implemented on top of the bindings generated from `wit/serval.wit`. Buffers the bindings
+//! return are copied into length-prefixed blocks from our `alloc`, just as the host would have
+//! written them, so the callers can't tell the difference.
+
+use std::borrow::Cow;
+
+use super::bindings::serval::host::{channels, config, fs, invoke, job, runtime, sockets, streams};
+use crate::bytes_to_host;
+#[cfg(feature = "inline-returns")]
+use crate::inline;
+
+/// Borrows `len` bytes of our memory at `ptr`.
+unsafe fn bytes<'a>(ptr: usize, len: u32) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(ptr as *const u8, len as usize)
+}
+
+unsafe fn string<'a>(ptr: usize, len: u32) -> Cow<'a, str> {
+    String::from_utf8_lossy(bytes(ptr, len))
+}
+
+/// Copies what a call into the bindings returned into the buffer at `ptr`, which has room for
+/// `len` bytes, returning how many were copied.
+unsafe fn fill(ptr: usize, len: u32, result: Result<Vec<u8>, i32>) -> i32 {
+    match result {
+        Ok(data) => {
+            let copied = data.len().min(len as usize);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, copied);
+            copied as i32
+        }
+        Err(code) => code,
+    }
+}
+
+/// Turns a returned buffer into what an import returning a buffer gives the guest.
+fn buffer(result: Result<Vec<u8>, i32>) -> isize {
+    match result {
+        Ok(data) => bytes_to_host(&data) as isize,
+        Err(code) => code as isize,
+    }
+}
+
+/// Like `buffer`, for imports that return 0 when there's nothing to return.
+fn optional(result: Result<Option<Vec<u8>>, i32>) -> isize {
+    match result {
+        Ok(Some(data)) => bytes_to_host(&data) as isize,
+        Ok(None) => 0,
+        Err(code) => code as isize,
+    }
+}
+
+fn number(result: Result<u32, i32>) -> i32 {
+    result.map_or_else(|code| code, |value| value as i32)
+}
+
+fn status(result: Result<(), i32>) -> i32 {
+    result.map_or_else(|code| code, |()| 0)
+}
+
+pub(crate) unsafe fn invoke_raw(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> isize {
+    buffer(invoke::invoke_raw(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+    ))
+}
+
+#[cfg(feature = "inline-returns")]
+pub(crate) unsafe fn invoke_raw_inline(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i64 {
+    // The response has already been copied into our memory by the canonical ABI, so all packing
+    // saves is the length-prefixed copy.
+    let result = invoke::invoke_raw(&string(name_ptr, name_len), bytes(data_ptr, data_len));
+    match result.as_deref().ok().and_then(inline::pack) {
+        Some(value) => value,
+        None => buffer(result) as i64,
+    }
+}
+
+pub(crate) unsafe fn invoke_raw_with_timeout(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+    timeout_ms: u32,
+) -> isize {
+    buffer(invoke::invoke_raw_with_timeout(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+        timeout_ms,
+    ))
+}
+
+pub(crate) unsafe fn invoke_raw_oneway(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i32 {
+    status(invoke::invoke_raw_oneway(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+    ))
+}
+
+pub(crate) unsafe fn invoke_framed(
+    name_ptr: usize,
+    name_len: u32,
+    frame_ptr: usize,
+    frame_len: u32,
+) -> isize {
+    buffer(invoke::invoke_framed(
+        &string(name_ptr, name_len),
+        bytes(frame_ptr, frame_len),
+    ))
+}
+
+pub(crate) unsafe fn invoke_batch(batch_ptr: usize, batch_len: u32) -> isize {
+    buffer(invoke::invoke_batch(bytes(batch_ptr, batch_len)))
+}
+
+pub(crate) unsafe fn invoke_pipeline(pipeline_ptr: usize, pipeline_len: u32) -> isize {
+    buffer(invoke::invoke_pipeline(bytes(pipeline_ptr, pipeline_len)))
+}
+
+pub(crate) unsafe fn start_invoke(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i32 {
+    number(invoke::start_invoke(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+    ))
+}
+
+pub(crate) unsafe fn poll_invoke(handle: u32) -> isize {
+    optional(invoke::poll_invoke(handle))
+}
+
+pub(crate) unsafe fn wait_invoke(handle: u32) -> isize {
+    buffer(invoke::wait_invoke(handle))
+}
+
+pub(crate) unsafe fn wait_any(handles_ptr: usize, count: u32) -> i32 {
+    let handles = if count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(handles_ptr as *const u32, count as usize)
+    };
+    number(invoke::wait_any(handles))
+}
+
+pub(crate) unsafe fn cancel_invoke(handle: u32) -> i32 {
+    status(invoke::cancel_invoke(handle))
+}
+
+pub(crate) unsafe fn list_extensions() -> isize {
+    buffer(invoke::list_extensions())
+}
+
+pub(crate) unsafe fn describe_extension(name_ptr: usize, name_len: u32) -> isize {
+    optional(invoke::describe_extension(&string(name_ptr, name_len)))
+}
+
+pub(crate) unsafe fn invoke_streaming(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i32 {
+    number(streams::invoke_streaming(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+    ))
+}
+
+pub(crate) unsafe fn stream_next(stream: u32) -> isize {
+    optional(streams::stream_next(stream))
+}
+
+pub(crate) unsafe fn stream_grant(stream: u32, credit: u32) -> i32 {
+    status(streams::stream_grant(stream, credit))
+}
+
+pub(crate) unsafe fn stream_close(stream: u32) -> i32 {
+    status(streams::stream_close(stream))
+}
+
+pub(crate) unsafe fn open_request(name_ptr: usize, name_len: u32) -> i32 {
+    number(streams::open_request(&string(name_ptr, name_len)))
+}
+
+pub(crate) unsafe fn request_write(request: u32, data_ptr: usize, data_len: u32) -> i32 {
+    status(streams::request_write(request, bytes(data_ptr, data_len)))
+}
+
+pub(crate) unsafe fn request_finish(request: u32) -> isize {
+    buffer(streams::request_finish(request))
+}
+
+pub(crate) unsafe fn request_finish_streaming(request: u32) -> i32 {
+    number(streams::request_finish_streaming(request))
+}
+
+pub(crate) unsafe fn negotiate_segment_size(preferred: u32) -> i32 {
+    number(streams::negotiate_segment_size(preferred))
+}
+
+pub(crate) unsafe fn request_abort(request: u32) -> i32 {
+    status(streams::request_abort(request))
+}
+
+pub(crate) unsafe fn open_channel(name_ptr: usize, name_len: u32) -> i32 {
+    number(channels::open_channel(&string(name_ptr, name_len)))
+}
+
+pub(crate) unsafe fn channel_send(channel: u32, data_ptr: usize, data_len: u32) -> i32 {
+    status(channels::channel_send(channel, bytes(data_ptr, data_len)))
+}
+
+pub(crate) unsafe fn channel_recv(channel: u32) -> isize {
+    optional(channels::channel_recv(channel))
+}
+
+pub(crate) unsafe fn channel_grant(channel: u32, credit: u32) -> i32 {
+    status(channels::channel_grant(channel, credit))
+}
+
+pub(crate) unsafe fn channel_close_send(channel: u32) -> i32 {
+    status(channels::channel_close_send(channel))
+}
+
+pub(crate) unsafe fn channel_close(channel: u32) -> i32 {
+    status(channels::channel_close(channel))
+}
+
+pub(crate) unsafe fn abi_negotiate(min: u32, max: u32) -> i32 {
+    number(runtime::abi_negotiate(min, max))
+}
+
+pub(crate) unsafe fn caps_granted() -> isize {
+    buffer(runtime::caps_granted())
+}
+
+pub(crate) unsafe fn caps_can_invoke(name_ptr: usize, name_len: u32) -> i32 {
+    number(runtime::caps_can_invoke(&string(name_ptr, name_len)).map(u32::from))
+}
+
+pub(crate) unsafe fn get_last_error() -> isize {
+    optional(runtime::get_last_error())
+}
+
+pub(crate) unsafe fn report_memory_stats(ptr: usize, len: u32) -> i32 {
+    status(runtime::report_memory_stats(bytes(ptr, len)))
+}
+
+pub(crate) unsafe fn job_metadata() -> isize {
+    buffer(job::job_metadata())
+}
+
+pub(crate) unsafe fn job_input() -> isize {
+    buffer(job::job_input())
+}
+
+pub(crate) unsafe fn job_trace_id() -> isize {
+    optional(job::job_trace_id().map(|id| id.map(String::into_bytes)))
+}
+
+pub(crate) unsafe fn job_set_output(ptr: usize, len: u32) -> i32 {
+    status(job::job_set_output(bytes(ptr, len)))
+}
+
+pub(crate) unsafe fn job_complete(status_code: u32, message_ptr: usize, message_len: u32) -> i32 {
+    status(job::job_complete(
+        status_code,
+        &string(message_ptr, message_len),
+    ))
+}
+
+pub(crate) unsafe fn job_progress(percent: f32, message_ptr: usize, message_len: u32) -> i32 {
+    status(job::job_progress(
+        percent,
+        &string(message_ptr, message_len),
+    ))
+}
+
+pub(crate) unsafe fn job_cancelled() -> i32 {
+    job::job_cancelled() as i32
+}
+
+pub(crate) unsafe fn job_heartbeat() -> i32 {
+    status(job::job_heartbeat())
+}
+
+pub(crate) unsafe fn metrics_flush(ptr: usize, len: u32) -> i32 {
+    status(runtime::metrics_flush(bytes(ptr, len)))
+}
+
+pub(crate) unsafe fn secret_get(name_ptr: usize, name_len: u32) -> isize {
+    optional(config::secret_get(&string(name_ptr, name_len)))
+}
+
+pub(crate) unsafe fn env_get(key_ptr: usize, key_len: u32) -> isize {
+    optional(config::env_get(&string(key_ptr, key_len)).map(|value| value.map(String::into_bytes)))
+}
+
+pub(crate) unsafe fn env_vars() -> isize {
+    buffer(config::env_vars())
+}
+
+pub(crate) unsafe fn wall_clock_now() -> u64 {
+    runtime::wall_clock_now()
+}
+
+pub(crate) unsafe fn monotonic_now() -> u64 {
+    runtime::monotonic_now()
+}
+
+pub(crate) unsafe fn socket_connect(kind: u32, addr_ptr: usize, addr_len: u32) -> i32 {
+    number(sockets::socket_connect(kind, &string(addr_ptr, addr_len)))
+}
+
+pub(crate) unsafe fn socket_read(socket: u32, buf_ptr: usize, buf_len: u32) -> i32 {
+    fill(buf_ptr, buf_len, sockets::socket_read(socket, buf_len))
+}
+
+pub(crate) unsafe fn socket_write(socket: u32, ptr: usize, len: u32) -> i32 {
+    number(sockets::socket_write(socket, bytes(ptr, len)))
+}
+
+pub(crate) unsafe fn socket_close(socket: u32) -> i32 {
+    status(sockets::socket_close(socket))
+}
+
+pub(crate) unsafe fn fs_open(path_ptr: usize, path_len: u32, flags: u32) -> i32 {
+    number(fs::fs_open(&string(path_ptr, path_len), flags))
+}
+
+pub(crate) unsafe fn fs_read(file: u32, buf_ptr: usize, buf_len: u32) -> i32 {
+    fill(buf_ptr, buf_len, fs::fs_read(file, buf_len))
+}
+
+pub(crate) unsafe fn fs_write(file: u32, ptr: usize, len: u32) -> i32 {
+    number(fs::fs_write(file, bytes(ptr, len)))
+}
+
+pub(crate) unsafe fn fs_seek(file: u32, whence: u32, offset: i64) -> i64 {
+    fs::fs_seek(file, whence, offset).map_or_else(i64::from, |position| position as i64)
+}
+
+pub(crate) unsafe fn fs_close(file: u32) -> i32 {
+    status(fs::fs_close(file))
+}
+
+pub(crate) unsafe fn fs_list(path_ptr: usize, path_len: u32) -> isize {
+    buffer(fs::fs_list(&string(path_ptr, path_len)))
+}
+
+pub(crate) unsafe fn fs_remove(path_ptr: usize, path_len: u32) -> i32 {
+    status(fs::fs_remove(&string(path_ptr, path_len)))
+}
+
+pub(crate) unsafe fn sleep(nanos: u64) {
+    runtime::sleep(nanos);
+}
+
+pub(crate) unsafe fn yield_now() {
+    runtime::yield_now();
+}
+
+pub(crate) unsafe fn random_fill(ptr: usize, len: u32) -> i32 {
+    match runtime::random_fill(len) {
+        Ok(data) if data.len() == len as usize => {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
+            0
+        }
+        // Handing back fewer random bytes than asked for would leave part of the buffer
+        // predictable.
+        Ok(_) => crate::ExtensionErrorCode::HostTrap.as_raw(),
+        Err(code) => code,
+    }
+}
+
+pub(crate) unsafe fn log_raw(level: u32, ptr: usize, len: u32) {
+    runtime::log_raw(level, &string(ptr, len));
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) unsafe fn trace_record(ptr: usize, len: u32) -> i32 {
+    status(runtime::trace_record(bytes(ptr, len)))
+}
+
+pub(crate) unsafe fn report_error(ptr: usize, len: u32) -> i32 {
+    status(runtime::report_error(bytes(ptr, len)))
+}
+
+/// Component hosts pass buffers by value, so they never enable exchange memory.
+#[cfg(feature = "exchange-memory")]
+pub(crate) unsafe fn exchange_read(_offset: usize, _dst_ptr: usize, _len: u32) -> i32 {
+    crate::ExtensionErrorCode::HostTrap.as_raw()
+}
+
+#[cfg(feature = "exchange-memory")]
+pub(crate) unsafe fn exchange_free(_offset: usize) -> i32 {
+    crate::ExtensionErrorCode::HostTrap.as_raw()
+}
@@ -0,0 +1,222 @@
+//! Transparent compression of large payloads. Once a `Compression` config is installed with
+//! `set_compression`, `invoke_extension` sends its requests as frames, compressing bodies at or
+//! above the configured threshold and advertising which algorithms we can decompress so the host
+//! can compress its responses too.
+
+use std::cell::Cell;
+
+use crate::frame::{flags, invoke_framed, Frame};
+use crate::{CodecError, Result, SdkError};
+
+/// A compression algorithm available in this build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    #[cfg(feature = "lz4")]
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Algorithm {
+    fn flag(self) -> u8 {
+        match self {
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => flags::LZ4,
+            #[cfg(feature = "zstd")]
+            Algorithm::Zstd => flags::ZSTD,
+        }
+    }
+}
+
+/// When and how to compress request bodies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compression {
+    pub algorithm: Algorithm,
+    /// Bodies smaller than this many bytes are sent uncompressed; compressing tiny payloads
+    /// costs more than it saves.
+    pub threshold: usize,
+}
+
+impl Compression {
+    /// The threshold used by `Compression::new`.
+    pub const DEFAULT_THRESHOLD: usize = 64 * 1024;
+
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            threshold: Self::DEFAULT_THRESHOLD,
+        }
+    }
+
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+/// The largest body a compressed response may decompress to. Responses that claim or turn out to
+/// be bigger are rejected with `SdkError::Decode` instead of exhausting the guest's memory.
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    static CURRENT: Cell<Option<Compression>> = const { Cell::new(None) };
+}
+
+/// Installs (or, with `None`, removes) the compression config `invoke_extension` applies.
+pub fn set_compression(compression: Option<Compression>) {
+    CURRENT.with(|current| current.set(compression));
+}
+
+/// Returns the compression config currently in effect.
+pub fn compression() -> Option<Compression> {
+    CURRENT.with(Cell::get)
+}
+
+/// The accept flags for every algorithm compiled into this build.
+fn accept_flags() -> u8 {
+    let mut accept = 0;
+    if cfg!(feature = "lz4") {
+        accept |= flags::ACCEPT_LZ4;
+    }
+    if cfg!(feature = "zstd") {
+        accept |= flags::ACCEPT_ZSTD;
+    }
+    accept
+}
+
+/// Compresses `frame`'s body in place if it's at least `compression.threshold` bytes long.
+fn compress_frame(frame: &mut Frame, compression: &Compression) -> Result<()> {
+    frame.flags |= accept_flags();
+    if frame.body.len() < compression.threshold {
+        return Ok(());
+    }
+    frame.body = compress(compression.algorithm, &frame.body)?;
+    frame.flags |= compression.algorithm.flag();
+    Ok(())
+}
+
+/// Decompresses `frame`'s body in place according to its flags.
+fn decompress_frame(frame: &mut Frame) -> Result<()> {
+    #[cfg(feature = "lz4")]
+    if frame.flags & flags::LZ4 != 0 {
+        frame.body = decompress_lz4(&frame.body)?;
+        frame.flags &= !flags::LZ4;
+    }
+    #[cfg(feature = "zstd")]
+    if frame.flags & flags::ZSTD != 0 {
+        frame.body = decompress_zstd(&frame.body)?;
+        frame.flags &= !flags::ZSTD;
+    }
+    if frame.flags & (flags::LZ4 | flags::ZSTD) != 0 {
+        return Err(SdkError::Decode(CodecError::new(
+            "response compressed with an algorithm this build doesn't support",
+        )));
+    }
+    Ok(())
+}
+
+/// Decompresses an LZ4 block prefixed with its decompressed size as a little-endian u32. The
+/// buffer is sized from the prefix, so it's checked against `MAX_DECOMPRESSED_SIZE` first rather
+/// than letting a corrupt prefix allocate up to 4 GiB.
+#[cfg(feature = "lz4")]
+fn decompress_lz4(bytes: &[u8]) -> Result<Vec<u8>> {
+    let (prefix, block) = bytes
+        .split_first_chunk::<4>()
+        .ok_or_else(|| SdkError::Decode(CodecError::new("LZ4 body is missing its size")))?;
+    let len = u32::from_le_bytes(*prefix) as usize;
+    if len > MAX_DECOMPRESSED_SIZE {
+        return Err(too_large());
+    }
+    let mut body = vec![0; len];
+    let written = lz4_flex::decompress_into(block, &mut body)
+        .map_err(|err| SdkError::Decode(CodecError::new(err.to_string())))?;
+    if written != len {
+        return Err(SdkError::Decode(CodecError::new(
+            "LZ4 body is shorter than its declared size",
+        )));
+    }
+    Ok(body)
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_zstd(bytes: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let decoder = zstd::Decoder::new(bytes)
+        .map_err(|err| SdkError::Decode(CodecError::new(err.to_string())))?;
+    let mut body = Vec::new();
+    // One byte past the limit is enough to tell that the body goes over it.
+    decoder
+        .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| SdkError::Decode(CodecError::new(err.to_string())))?;
+    if body.len() > MAX_DECOMPRESSED_SIZE {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn too_large() -> SdkError {
+    SdkError::Decode(CodecError::new(format!(
+        "response decompresses to more than {MAX_DECOMPRESSED_SIZE} bytes"
+    )))
+}
+
+fn compress(algorithm: Algorithm, bytes: &[u8]) -> Result<Vec<u8>> {
+    match algorithm {
+        #[cfg(feature = "lz4")]
+        Algorithm::Lz4 => Ok(lz4_flex::compress_prepend_size(bytes)),
+        #[cfg(feature = "zstd")]
+        Algorithm::Zstd => zstd::encode_all(bytes, 0)
+            .map_err(|err| SdkError::Encode(CodecError::new(err.to_string()))),
+    }
+}
+
+/// Sends a frame, compressing it according to `compression`, and decompresses the response. The
+/// response is decompressed even without a config, in case the host compressed it anyway.
+pub(crate) fn send_compressed(
+    extension_name: &str,
+    mut frame: Frame,
+    compression: Option<Compression>,
+) -> Result<Frame> {
+    if let Some(compression) = compression {
+        compress_frame(&mut frame, &compression)?;
+    }
+    let mut response = invoke_framed(extension_name, &frame)?;
+    decompress_frame(&mut response)?;
+    Ok(response)
+}
+
+/// Sends `data` as a frame compressed with the current config and returns the response body.
+pub(crate) fn invoke_compressed(extension_name: &str, data: &[u8]) -> Result<Vec<u8>> {
+    Ok(send_compressed(extension_name, Frame::new(data.to_vec()), compression())?.body)
+}
+
+#[cfg(all(test, feature = "lz4"))]
+mod tests {
+    use super::{decompress_lz4, MAX_DECOMPRESSED_SIZE};
+
+    #[test]
+    fn lz4_bodies_round_trip() {
+        let body = b"hello hello hello hello".repeat(100);
+        let compressed = lz4_flex::compress_prepend_size(&body);
+        assert_eq!(decompress_lz4(&compressed).unwrap(), body);
+    }
+
+    #[test]
+    fn lz4_sizes_over_the_limit_are_rejected_before_allocating() {
+        let mut compressed = lz4_flex::compress_prepend_size(b"tiny");
+        compressed[..4].copy_from_slice(&(MAX_DECOMPRESSED_SIZE as u32 + 1).to_le_bytes());
+        assert!(decompress_lz4(&compressed).is_err());
+        compressed[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decompress_lz4(&compressed).is_err());
+    }
+
+    #[test]
+    fn lz4_bodies_shorter_than_declared_are_rejected() {
+        let mut compressed = lz4_flex::compress_prepend_size(b"tiny");
+        compressed[..4].copy_from_slice(&100u32.to_le_bytes());
+        assert!(decompress_lz4(&compressed).is_err());
+        assert!(decompress_lz4(&[1, 0]).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Canonical test vectors for the byte layouts the SDK exchanges with the host, so that host
+//! implementations and SDKs in other languages can check themselves against this one's exact
+//! encoding. The `conformance` example writes them out as JSON lines:
+//!
+//! ```text
+//! cargo run --example conformance --features conformance > serval-vectors.jsonl
+//! ```
+//!
+//! Each vector is a named byte string with what it decodes to. Valid vectors must decode to what
+//! they describe and encoders must produce exactly their bytes; invalid ones must be rejected.
+//! Names are stable across releases, and a change to an existing vector's bytes is a breaking
+//! change to the ABI.
+
+use crate::envelope::{Envelope, NEGOTIATION_SCHEMA_VERSION};
+use crate::frame::{flags, tags, Frame};
+use crate::framing::encode_frame;
+use crate::{ExtensionErrorCode, GuestError, SdkError};
+
+/// A single test vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vector {
+    /// A stable identifier, grouped by layout: `framing/...`, `error_code/...`, `frame/...`,
+    /// `envelope/...` or `guest_error/...`.
+    pub name: String,
+    /// What the bytes decode to, or why they must be rejected.
+    pub description: String,
+    pub bytes: Vec<u8>,
+    /// Whether a conforming decoder accepts the bytes.
+    pub valid: bool,
+}
+
+impl Vector {
+    fn valid(name: &str, description: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.into(),
+            bytes,
+            valid: true,
+        }
+    }
+
+    fn invalid(name: &str, description: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            valid: false,
+            ..Self::valid(name, description, bytes)
+        }
+    }
+
+    /// The vector as a single line of JSON with `name`, `description`, `hex` and `valid` keys.
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"name":"{}","description":"{}","hex":"{}","valid":{}}}"#,
+            escape(&self.name),
+            escape(&self.description),
+            hex(&self.bytes),
+            self.valid
+        )
+    }
+}
+
+/// Every test vector, grouped by layout.
+pub fn vectors() -> Vec<Vector> {
+    let mut vectors = Vec::new();
+    vectors.extend(framing());
+    vectors.extend(error_codes());
+    vectors.extend(frames());
+    vectors.extend(envelopes());
+    vectors.extend(guest_errors());
+    vectors
+}
+
+/// Length-prefixed buffers: a little-endian u32 length followed by that many bytes.
+fn framing() -> Vec<Vector> {
+    vec![
+        Vector::valid("framing/empty", "no data", encode_frame(b"")),
+        Vector::valid(
+            "framing/hello",
+            "the 5 bytes \"hello\"",
+            encode_frame(b"hello"),
+        ),
+        Vector::valid(
+            "framing/256-bytes",
+            "256 bytes counting up from 0 and wrapping, showing the multi-byte length",
+            encode_frame(&(0..=255).collect::<Vec<u8>>()),
+        ),
+        Vector::invalid(
+            "framing/short-prefix",
+            "only 3 of the 4 length bytes",
+            vec![5, 0, 0],
+        ),
+        Vector::invalid(
+            "framing/short-data",
+            "a length of 5 followed by only 3 bytes",
+            [&5u32.to_le_bytes()[..], b"hel"].concat(),
+        ),
+    ]
+}
+
+/// The negative i32s imports return on failure, as little-endian bytes.
+fn error_codes() -> Vec<Vector> {
+    (1..=10)
+        .map(|code| {
+            let code = ExtensionErrorCode::from(-code);
+            let name = format!("error_code/{}", snake_case(&format!("{code:?}")));
+            Vector::valid(
+                &name,
+                format!("{code:?} ({}): {}", code.as_raw(), SdkError::from(code)),
+                code.as_raw().to_le_bytes().to_vec(),
+            )
+        })
+        .collect()
+}
+
+/// Invocation frames: a version byte, a flags byte, a u16 field count, each field as a tag byte
+/// and a length-prefixed value, then the body.
+fn frames() -> Vec<Vector> {
+    let mut with_fields = Frame::new(b"{}".to_vec());
+    with_fields.set_content_type("application/json");
+    with_fields.set_operation("get");
+    with_fields.push_field(tags::TIMEOUT_MS, 1500u32.to_le_bytes().to_vec());
+
+    let with_metadata = Frame::new(b"body".to_vec())
+        .with_metadata("authorization", "Bearer t0k3n")
+        .with_metadata("tenant", "acme");
+
+    let mut repeated_metadata = Frame::new(b"body".to_vec())
+        .with_metadata("set-cookie", "a=1")
+        .with_metadata("set-cookie", "b=2");
+    repeated_metadata.push_field(tags::METADATA, vec![10, 0, 0, 0, b'k']);
+
+    let mut compressed = Frame::new(b"body".to_vec());
+    compressed.flags = flags::LZ4 | flags::ACCEPT_LZ4;
+
+    let mut unknown_field = Frame::new(b"body".to_vec());
+    unknown_field.push_field(200, b"ignored".to_vec());
+
+    let mut truncated_field = Frame::new(Vec::new());
+    truncated_field.set_operation("get");
+    let mut truncated_field = encode(&truncated_field);
+    truncated_field.pop();
+
+    vec![
+        Vector::valid(
+            "frame/empty",
+            "no flags, no fields, empty body",
+            encode(&Frame::new(Vec::new())),
+        ),
+        Vector::valid(
+            "frame/body-only",
+            "no flags, no fields, body \"hello\"",
+            encode(&Frame::new(b"hello".to_vec())),
+        ),
+        Vector::valid(
+            "frame/fields",
+            "content type application/json, operation get, a 1500 ms timeout, body \"{}\"",
+            encode(&with_fields),
+        ),
+        Vector::valid(
+            "frame/metadata",
+            "metadata entries authorization \"Bearer t0k3n\" and tenant \"acme\", body \"body\"",
+            encode(&with_metadata),
+        ),
+        Vector::valid(
+            "frame/metadata-repeated",
+            "metadata entries set-cookie \"a=1\" and set-cookie \"b=2\", both kept and in that \
+             order, then a metadata field whose key is cut short, which decoders keep as a field \
+             but skip as an entry, body \"body\"",
+            encode(&repeated_metadata),
+        ),
+        Vector::valid(
+            "frame/flags",
+            "LZ4 and ACCEPT_LZ4 flags set, body \"body\"",
+            encode(&compressed),
+        ),
+        Vector::valid(
+            "frame/unknown-field",
+            "a field with the unassigned tag 200, which decoders keep and otherwise ignore, body \
+             \"body\"",
+            encode(&unknown_field),
+        ),
+        Vector::invalid(
+            "frame/bad-version",
+            "version 2, which doesn't exist",
+            vec![2, 0, 0, 0],
+        ),
+        Vector::invalid(
+            "frame/short-field",
+            "an operation field whose value is one byte shorter than its length says",
+            truncated_field,
+        ),
+    ]
+}
+
+/// Encodes one of the frames above, none of which has anywhere near `u16::MAX` fields.
+fn encode(frame: &Frame) -> Vec<u8> {
+    frame.encode().expect("conformance frames encode")
+}
+
+/// Schema envelopes: the magic byte 0xE1, a u32 schema version, a length-prefixed content type and
+/// a length-prefixed body.
+fn envelopes() -> Vec<Vector> {
+    vec![
+        Vector::valid(
+            "envelope/json",
+            "schema version 3, content type application/json, body \"{}\"",
+            Envelope::new(3, "application/json", b"{}".to_vec()).encode(),
+        ),
+        Vector::valid(
+            "envelope/negotiation",
+            "a negotiation reply listing schema versions 1 and 2",
+            Envelope::new(
+                NEGOTIATION_SCHEMA_VERSION,
+                "",
+                [1u32.to_le_bytes(), 2u32.to_le_bytes()].concat(),
+            )
+            .encode(),
+        ),
+        Vector::invalid(
+            "envelope/bad-magic",
+            "magic byte 0xE2 instead of 0xE1",
+            [&[0xE2][..], &Envelope::new(1, "", Vec::new()).encode()[1..]].concat(),
+        ),
+    ]
+}
+
+/// Error reports passed to `report_error`: a version byte, an i32 code, a length-prefixed message
+/// and a length-prefixed backtrace, empty if there is none.
+fn guest_errors() -> Vec<Vector> {
+    vec![
+        Vector::valid(
+            "guest_error/message",
+            "code -1, message \"boom\", no backtrace",
+            GuestError::new(-1, "boom").encode(),
+        ),
+        Vector::valid(
+            "guest_error/backtrace",
+            "code 42, message \"bad input\", backtrace \"at main\"",
+            GuestError::new(42, "bad input")
+                .with_backtrace("at main")
+                .encode(),
+        ),
+    ]
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && !snake.is_empty() {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,12 @@
+//! Constants for the content types the SDK's codecs produce, plus a few other common ones. These
+//! are what travel in the `CONTENT_TYPE` field of an invocation frame.
+
+pub const JSON: &str = "application/json";
+pub const MSGPACK: &str = "application/msgpack";
+pub const CBOR: &str = "application/cbor";
+pub const BINCODE: &str = "application/x-bincode";
+pub const POSTCARD: &str = "application/x-postcard";
+pub const PROTOBUF: &str = "application/x-protobuf";
+pub const FLATBUFFERS: &str = "application/x-flatbuffers";
+pub const OCTET_STREAM: &str = "application/octet-stream";
+pub const TEXT: &str = "text/plain; charset=utf-8";
@@ -0,0 +1,139 @@
+//! Runtime support for guest entrypoints generated by `#[serval::main]` and `#[serval::export]`.
+//!
+//! The host calls an entrypoint with a pointer to a length-prefixed input buffer it allocated with
+//! our `alloc` (or 0 if there is no input). On success the entrypoint returns a pointer to a
+//! length-prefixed output buffer, which the host frees with our `dealloc` once it has read it. On
+//! failure the error is reported with `report_error` and `ENTRYPOINT_FAILED` is returned.
+//!
+//! `#[serval::main]` entrypoints instead deliver their output through the job's output sink (see
+//! `job::set_output`) and report completion with `job::complete`, returning 0 on success.
+
+use crate::{bytes_to_host, get_bytes_from_host, job, report_error, GuestError};
+
+/// The status an entrypoint returns when it failed; the details were sent with `report_error`.
+pub const ENTRYPOINT_FAILED: isize = -1;
+
+/// Types an entrypoint may take as its input.
+pub trait FromInput: Sized {
+    fn from_input(input: Vec<u8>) -> Result<Self, GuestError>;
+}
+
+impl FromInput for Vec<u8> {
+    fn from_input(input: Vec<u8>) -> Result<Self, GuestError> {
+        Ok(input)
+    }
+}
+
+/// Types an entrypoint may return.
+pub trait EntrypointOutput {
+    fn into_output(self) -> Result<Vec<u8>, GuestError>;
+}
+
+impl EntrypointOutput for Vec<u8> {
+    fn into_output(self) -> Result<Vec<u8>, GuestError> {
+        Ok(self)
+    }
+}
+
+impl EntrypointOutput for () {
+    fn into_output(self) -> Result<Vec<u8>, GuestError> {
+        Ok(Vec::new())
+    }
+}
+
+impl<T: EntrypointOutput, E: Into<GuestError>> EntrypointOutput for Result<T, E> {
+    fn into_output(self) -> Result<Vec<u8>, GuestError> {
+        self.map_err(Into::into)?.into_output()
+    }
+}
+
+/// Reads the entrypoint's input, runs `f` on it and hands its output (or error) to the host.
+pub fn run_entrypoint<I: FromInput, O: EntrypointOutput>(
+    input_ptr: usize,
+    f: impl FnOnce(I) -> O,
+) -> isize {
+    run(input_ptr, f, Sink::Return)
+}
+
+/// Like `run_entrypoint`, for the job's main entrypoint: the output is published with
+/// `job::set_output`, unless the job already published one itself, and the job is marked as
+/// complete with `job::complete`.
+pub fn run_job<I: FromInput, O: EntrypointOutput>(
+    input_ptr: usize,
+    f: impl FnOnce(I) -> O,
+) -> isize {
+    run(input_ptr, f, Sink::Job)
+}
+
+/// Where an entrypoint's output goes.
+#[derive(Clone, Copy)]
+enum Sink {
+    /// Returned to the host as a pointer to a length-prefixed buffer.
+    Return,
+    /// Published as the job's output.
+    Job,
+}
+
+fn run<I: FromInput, O: EntrypointOutput>(
+    input_ptr: usize,
+    f: impl FnOnce(I) -> O,
+    sink: Sink,
+) -> isize {
+    #[cfg(feature = "panic-report")]
+    {
+        static INSTALL_PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+        INSTALL_PANIC_HOOK.call_once(crate::install_panic_hook);
+    }
+
+    let input = crate::abi::handshake()
+        .and_then(|_| match input_ptr {
+            0 => Ok(Vec::new()),
+            ptr => get_bytes_from_host(ptr),
+        })
+        .map_err(GuestError::from);
+
+    let output = input
+        .and_then(I::from_input)
+        .and_then(|input| f(input).into_output());
+    let status = match (output, sink) {
+        (Ok(output), Sink::Return) => Ok(bytes_to_host(&output) as isize),
+        (Ok(output), Sink::Job) => job::finish(&output).map(|()| 0).map_err(GuestError::from),
+        (Err(err), _) => Err(err),
+    };
+    let status = status.unwrap_or_else(|err| {
+        crate::history::at_failure();
+        // If even reporting fails there's nobody left to tell; the status still signals failure.
+        let _ = report_error(&err);
+        if let Sink::Job = sink {
+            let _ = job::complete(job::JobStatus::Failed, &err.message);
+        }
+        ENTRYPOINT_FAILED
+    });
+    // Exports and callbacks run many times over a job's life, so only its main entrypoint
+    // flushes what's been gathered.
+    if let Sink::Job = sink {
+        crate::metrics::at_exit();
+        crate::memory::at_exit();
+    }
+    status
+}
+
+/// An entrypoint input or output that is encoded as JSON, so exported functions can take and
+/// return serde types directly: `fn handle_event(Typed(event): Typed<Event>) -> Typed<Reply>`.
+#[cfg(feature = "json")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Typed<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromInput for Typed<T> {
+    fn from_input(input: Vec<u8>) -> Result<Self, GuestError> {
+        Ok(Typed(crate::json::from_payload_json(&input)?))
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize> EntrypointOutput for Typed<T> {
+    fn into_output(self) -> Result<Vec<u8>, GuestError> {
+        Ok(crate::json::to_payload_json(&self.0)?)
+    }
+}
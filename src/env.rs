@@ -0,0 +1,44 @@
+//! Configuration handed to the job by the host, such as endpoints and feature flags, so it
+//! doesn't have to be baked into the module or smuggled through the input payload.
+//!
+//! Like `std::env`, lookups that fail for any reason (the variable isn't set, the value isn't
+//! UTF-8, the host couldn't hand it over) are treated as the variable not being set.
+
+use crate::wire::Reader;
+use crate::{get_bytes_from_host, host};
+
+/// Returns the value of the configuration variable `key`, or `None` if it isn't set.
+pub fn get(key: &str) -> Option<String> {
+    let out_ptr = unsafe { host::env_get(key.as_ptr() as usize, key.len() as u32) };
+    if out_ptr <= 0 {
+        return None;
+    }
+    let value = get_bytes_from_host(out_ptr as usize).ok()?;
+    String::from_utf8(value).ok()
+}
+
+/// Returns every configuration variable as a list of key/value pairs.
+pub fn vars() -> Vec<(String, String)> {
+    let out_ptr = unsafe { host::env_vars() };
+    if out_ptr <= 0 {
+        return Vec::new();
+    }
+    match get_bytes_from_host(out_ptr as usize) {
+        Ok(bytes) => parse_vars(&bytes).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Parses the layout `env_vars` returns: a u32 count followed by that many length-prefixed key
+/// and value pairs.
+fn parse_vars(bytes: &[u8]) -> crate::Result<Vec<(String, String)>> {
+    let mut reader = Reader::new(bytes);
+    let count = reader.read_u32()?;
+    let mut vars = Vec::new();
+    for _ in 0..count {
+        let key = reader.read_str()?;
+        let value = reader.read_str()?;
+        vars.push((key.to_string(), value.to_string()));
+    }
+    Ok(vars)
+}
@@ -0,0 +1,126 @@
+//! An optional envelope around payloads carrying a schema version and codec identifier, so that
+//! rolling upgrades of an extension fail loudly instead of silently misinterpreting old guests'
+//! payloads.
+//!
+//! Schema version 0 is reserved for negotiation: an extension that receives an envelope with
+//! version 0 replies with an envelope (also version 0) whose body is the list of schema versions it
+//! supports, each a little-endian u32.
+
+use crate::wire::{Reader, Writer};
+use crate::{invoke_extension, Result, SdkError};
+
+/// Identifies the envelope layout itself, as opposed to the schema of the payload inside it.
+const ENVELOPE_MAGIC: u8 = 0xE1;
+
+/// The schema version reserved for asking an extension which versions it supports.
+pub const NEGOTIATION_SCHEMA_VERSION: u32 = 0;
+
+/// A payload tagged with the schema version and codec used to produce it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub schema_version: u32,
+    /// The content type of the codec the body was encoded with, e.g. `application/json`.
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn new(schema_version: u32, content_type: impl Into<String>, body: Vec<u8>) -> Self {
+        Self {
+            schema_version,
+            content_type: content_type.into(),
+            body,
+        }
+    }
+
+    /// Serializes the envelope: a magic byte, the u32 schema version, a length-prefixed content
+    /// type and a length-prefixed body.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u8(ENVELOPE_MAGIC);
+        writer.write_u32(self.schema_version);
+        writer.write_str(&self.content_type);
+        writer.write_prefixed(&self.body);
+        writer.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_u8()? != ENVELOPE_MAGIC {
+            return Err(SdkError::InvalidPayload);
+        }
+        Ok(Self {
+            schema_version: reader.read_u32()?,
+            content_type: reader.read_str()?.to_string(),
+            body: reader.read_prefixed()?.to_vec(),
+        })
+    }
+}
+
+/// Asks an extension which schema versions it supports.
+pub fn query_schema_versions(extension_name: &str) -> Result<Vec<u32>> {
+    let probe = Envelope::new(NEGOTIATION_SCHEMA_VERSION, "", Vec::new());
+    let response = Envelope::decode(&invoke_extension(extension_name, probe.encode())?)?;
+    if response.schema_version != NEGOTIATION_SCHEMA_VERSION || response.body.len() % 4 != 0 {
+        return Err(SdkError::InvalidPayload);
+    }
+    Ok(response
+        .body
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+/// Picks the newest schema version both we and the extension support, failing with
+/// `SdkError::NoCommonSchemaVersion` if there isn't one.
+pub fn negotiate_schema_version(extension_name: &str, supported: &[u32]) -> Result<u32> {
+    let theirs = query_schema_versions(extension_name)?;
+    supported
+        .iter()
+        .copied()
+        .filter(|version| theirs.contains(version))
+        .max()
+        .ok_or(SdkError::NoCommonSchemaVersion {
+            ours: supported.to_vec(),
+            theirs,
+        })
+}
+
+/// Invokes an extension with an enveloped payload and checks that the response was produced with
+/// the same schema version, failing with `SdkError::SchemaVersionMismatch` otherwise.
+pub fn invoke_enveloped(extension_name: &str, request: &Envelope) -> Result<Envelope> {
+    let response = Envelope::decode(&invoke_extension(extension_name, request.encode())?)?;
+    if response.schema_version != request.schema_version {
+        return Err(SdkError::SchemaVersionMismatch {
+            expected: request.schema_version,
+            actual: response.schema_version,
+        });
+    }
+    Ok(response)
+}
+
+/// Like `invoke_enveloped`, but encodes the request body and decodes the response body with
+/// `codec`. Responses encoded with a different codec are rejected.
+#[cfg(feature = "codec")]
+pub fn invoke_enveloped_with_codec<C, Req, Resp>(
+    extension_name: &str,
+    schema_version: u32,
+    codec: &C,
+    request: &Req,
+) -> Result<Resp>
+where
+    C: crate::Codec,
+    Req: serde::Serialize + ?Sized,
+    Resp: serde::de::DeserializeOwned,
+{
+    let request = Envelope::new(schema_version, codec.content_type(), codec.encode(request)?);
+    let response = invoke_enveloped(extension_name, &request)?;
+    if response.content_type != codec.content_type() {
+        return Err(SdkError::Decode(crate::CodecError::new(format!(
+            "expected {} response, got {}",
+            codec.content_type(),
+            response.content_type
+        ))));
+    }
+    codec.decode(&response.body)
+}
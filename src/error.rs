@@ -0,0 +1,485 @@
+use std::fmt;
+
+use crate::{host, take_host_bytes, wire::Reader};
+
+/// Convenience alias for results returned by the SDK.
+pub type Result<T> = std::result::Result<T, SdkError>;
+
+/// Everything that can go wrong when talking to the Serval host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SdkError {
+    /// The host has no extension registered under the requested name.
+    ExtensionNotFound,
+    /// The extension panicked while handling the call.
+    ExtensionPanicked,
+    /// The host trapped while handling the call for reasons unrelated to the extension itself.
+    HostTrap,
+    /// The payload could not be understood, either by the extension or by us when reading the
+    /// response.
+    InvalidPayload,
+    /// The payload exceeds the size the host is willing to accept.
+    PayloadTooLarge,
+    /// Memory for an exchange buffer could not be allocated.
+    AllocationFailed,
+    /// The host passed us a buffer that doesn't fit in our linear memory: its length prefix
+    /// (`len`, if the prefix itself was in bounds) runs past the end of the `memory_size` bytes of
+    /// memory we have.
+    CorruptFrame {
+        ptr: usize,
+        len: Option<usize>,
+        memory_size: usize,
+    },
+    /// The extension didn't respond before the call's deadline.
+    TimedOut,
+    /// The extension exists, but no installed version satisfies the requested version.
+    NoMatchingVersion,
+    /// The other side of a `Channel` has closed it, or the guest already closed it for sending.
+    ChannelClosed,
+    /// The job has been cancelled, or the `CancellationToken` the work was checking has.
+    Cancelled,
+    /// Reading a payload from or writing a response to an `io::Read` or `io::Write` failed.
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    /// An extension reference couldn't be parsed; see `ExtensionRef`.
+    InvalidExtensionRef(String),
+    /// A platform service such as `kv` has nothing under the requested key or name. Carries the
+    /// service's description of what was missing.
+    NotFound(String),
+    /// A platform service refused a write because its precondition no longer holds, such as the
+    /// version passed to `kv::put_if_version` being out of date.
+    Conflict(String),
+    /// A platform service failed the request with a status of its own; see `frame::tags::STATUS`.
+    Service { status: u32, message: String },
+    /// The host returned a status code we don't know how to interpret.
+    HostStatus(i32),
+    /// A value could not be encoded into a payload.
+    Encode(CodecError),
+    /// A payload could not be decoded into the expected type.
+    Decode(CodecError),
+    /// An enveloped response was produced with a different schema version than the request.
+    SchemaVersionMismatch { expected: u32, actual: u32 },
+    /// We and the extension don't support any schema version in common.
+    NoCommonSchemaVersion { ours: Vec<u32>, theirs: Vec<u32> },
+    /// The extension doesn't declare `operation` (`actual` is `None`), or declares it with a
+    /// schema hash other than the `expected` one the guest was built against; see
+    /// `extensions::Description::check_schema`.
+    SchemaMismatch {
+        extension: String,
+        operation: String,
+        expected: u64,
+        actual: Option<u64>,
+    },
+    /// The job hasn't been granted the capability with this name; see `caps::require`.
+    MissingCapability(String),
+    /// The host doesn't speak any of the ABI versions from `guest_min` to `guest_max` that this
+    /// guest does; `host` is the version it answered with, if it named one. See `abi`.
+    IncompatibleAbi {
+        guest_min: u32,
+        guest_max: u32,
+        host: Option<u32>,
+    },
+    /// One of the errors above, raised while invoking an extension, along with details about the
+    /// call. Use `SdkError::root` to get at the underlying error.
+    Invocation(Box<InvocationError>),
+}
+
+/// Describes a failure to encode or decode a payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodecError {
+    pub message: String,
+    /// The byte offset into the payload at which decoding failed, when the codec reports one.
+    pub offset: Option<usize>,
+}
+
+impl CodecError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            offset: None,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{} at byte {offset}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Details about the invocation an error was raised from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvocationError {
+    /// The name of the extension that was invoked.
+    pub extension: String,
+    /// How many bytes of payload were sent to the extension.
+    pub payload_len: usize,
+    /// The raw value the host returned from the invoke call. For a call that succeeded but whose
+    /// response couldn't be read, that's the response's address, truncated to 32 bits on wasm64.
+    pub status: i32,
+    /// What actually went wrong.
+    pub error: SdkError,
+}
+
+impl InvocationError {
+    pub(crate) fn new(extension: &str, payload_len: usize, status: i32, error: SdkError) -> Self {
+        Self {
+            extension: extension.to_string(),
+            payload_len,
+            status,
+            error,
+        }
+    }
+}
+
+impl SdkError {
+    /// Returns the underlying error, looking through any invocation context attached to it.
+    pub fn root(&self) -> &SdkError {
+        match self {
+            SdkError::Invocation(context) => context.error.root(),
+            err => err,
+        }
+    }
+
+    /// Returns details about the invocation this error was raised from, if any.
+    pub fn context(&self) -> Option<&InvocationError> {
+        match self {
+            SdkError::Invocation(context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the error is likely transient (a node restarting, a network blip) and the
+    /// same call has a chance of succeeding if retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.root(),
+            SdkError::HostTrap | SdkError::AllocationFailed | SdkError::TimedOut
+        )
+    }
+
+    /// Returns the code from the ABI's table of error codes (see `ExtensionErrorCode`) that this
+    /// error maps to, as used in the `GuestError`s we report. The mapping goes by the kind of
+    /// error alone, whichever side raised it: `Cancelled`, for one, maps to
+    /// `ExtensionErrorCode::Cancelled` whether the host failed a call with it or
+    /// `job::check_cancelled` noticed the cancellation itself. Use `host_code` to tell whether the
+    /// host reported the error.
+    pub fn code(&self) -> Option<ExtensionErrorCode> {
+        let code = match self {
+            SdkError::ExtensionNotFound => ExtensionErrorCode::NotFound,
+            SdkError::ExtensionPanicked => ExtensionErrorCode::Panicked,
+            SdkError::HostTrap => ExtensionErrorCode::HostTrap,
+            SdkError::InvalidPayload => ExtensionErrorCode::InvalidPayload,
+            SdkError::PayloadTooLarge => ExtensionErrorCode::PayloadTooLarge,
+            SdkError::AllocationFailed => ExtensionErrorCode::AllocationFailed,
+            SdkError::TimedOut => ExtensionErrorCode::TimedOut,
+            SdkError::NoMatchingVersion => ExtensionErrorCode::NoMatchingVersion,
+            SdkError::ChannelClosed => ExtensionErrorCode::ChannelClosed,
+            SdkError::Cancelled => ExtensionErrorCode::Cancelled,
+            SdkError::HostStatus(code) => ExtensionErrorCode::Unknown(*code),
+            SdkError::CorruptFrame { .. }
+            | SdkError::Io { .. }
+            | SdkError::InvalidExtensionRef(_)
+            | SdkError::NotFound(_)
+            | SdkError::Conflict(_)
+            | SdkError::Service { .. }
+            | SdkError::Encode(_)
+            | SdkError::Decode(_)
+            | SdkError::SchemaVersionMismatch { .. }
+            | SdkError::NoCommonSchemaVersion { .. }
+            | SdkError::SchemaMismatch { .. }
+            | SdkError::MissingCapability(_)
+            | SdkError::IncompatibleAbi { .. } => return None,
+            SdkError::Invocation(context) => return context.error.code(),
+        };
+        Some(code)
+    }
+
+    /// Returns the error code the host failed an invocation with, if that's where this error came
+    /// from. Errors the SDK raised itself, such as a response that failed to decode or a
+    /// cancellation noticed by `job::check_cancelled`, have none.
+    pub fn host_code(&self) -> Option<ExtensionErrorCode> {
+        match self {
+            SdkError::Invocation(context) if context.status < 0 => {
+                Some(ExtensionErrorCode::from(context.status))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SdkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SdkError::ExtensionNotFound => write!(f, "extension not found"),
+            SdkError::ExtensionPanicked => write!(f, "extension panicked"),
+            SdkError::HostTrap => write!(f, "host trapped"),
+            SdkError::InvalidPayload => write!(f, "invalid payload"),
+            SdkError::PayloadTooLarge => write!(f, "payload too large"),
+            SdkError::AllocationFailed => write!(f, "allocation failed"),
+            SdkError::CorruptFrame {
+                ptr,
+                len: Some(len),
+                memory_size,
+            } => write!(
+                f,
+                "host buffer at {ptr:#x} claims {len} bytes, past the end of {memory_size} bytes of memory"
+            ),
+            SdkError::CorruptFrame {
+                ptr,
+                len: None,
+                memory_size,
+            } => write!(
+                f,
+                "host buffer at {ptr:#x} is past the end of {memory_size} bytes of memory"
+            ),
+            SdkError::TimedOut => write!(f, "timed out"),
+            SdkError::NoMatchingVersion => write!(f, "no installed version matches"),
+            SdkError::ChannelClosed => write!(f, "channel closed"),
+            SdkError::Cancelled => write!(f, "cancelled"),
+            SdkError::Io { message, .. } => write!(f, "I/O error: {message}"),
+            SdkError::InvalidExtensionRef(extension_ref) => {
+                write!(f, "invalid extension reference {extension_ref:?}")
+            }
+            SdkError::NotFound(message) => write!(f, "not found: {message}"),
+            SdkError::Conflict(message) => write!(f, "conflict: {message}"),
+            SdkError::Service { status, message } => {
+                write!(f, "service failed with status {status}: {message}")
+            }
+            SdkError::HostStatus(code) => write!(f, "host returned status {code}"),
+            SdkError::Encode(err) => write!(f, "failed to encode payload: {err}"),
+            SdkError::Decode(err) => write!(f, "failed to decode payload: {err}"),
+            SdkError::SchemaVersionMismatch { expected, actual } => write!(
+                f,
+                "expected a response with schema version {expected}, got {actual}"
+            ),
+            SdkError::NoCommonSchemaVersion { ours, theirs } => write!(
+                f,
+                "no common schema version (we support {ours:?}, extension supports {theirs:?})"
+            ),
+            SdkError::SchemaMismatch {
+                extension,
+                operation,
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "{extension} declares {operation} with schema hash {actual:#018x}, but the guest \
+                 was built against {expected:#018x}"
+            ),
+            SdkError::SchemaMismatch {
+                extension,
+                operation,
+                actual: None,
+                ..
+            } => write!(f, "{extension} doesn't declare the operation {operation}"),
+            SdkError::MissingCapability(capability) => {
+                write!(f, "the job hasn't been granted the {capability} capability")
+            }
+            SdkError::IncompatibleAbi {
+                guest_min,
+                guest_max,
+                host: Some(host),
+            } => write!(
+                f,
+                "the host speaks ABI version {host}, but the guest only speaks versions \
+                 {guest_min} to {guest_max}"
+            ),
+            SdkError::IncompatibleAbi {
+                guest_min,
+                guest_max,
+                host: None,
+            } => write!(
+                f,
+                "the host speaks none of ABI versions {guest_min} to {guest_max}, which the \
+                 guest does"
+            ),
+            SdkError::Invocation(context) => write!(
+                f,
+                "invoking {} with {} bytes failed with status {}: {}",
+                context.extension, context.payload_len, context.status, context.error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SdkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SdkError::Invocation(context) => Some(&context.error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SdkError {
+    fn from(err: std::io::Error) -> Self {
+        // `io::Error::other(sdk_error)` is how the SDK's own `Read`/`Write` impls report failures,
+        // so those come back out as the original error.
+        if err.get_ref().is_some_and(|inner| inner.is::<SdkError>()) {
+            let inner = err.into_inner().expect("checked above");
+            return *inner.downcast::<SdkError>().expect("checked above");
+        }
+        SdkError::Io {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<InvocationError> for SdkError {
+    fn from(context: InvocationError) -> Self {
+        SdkError::Invocation(Box::new(context))
+    }
+}
+
+impl From<ExtensionErrorCode> for SdkError {
+    fn from(code: ExtensionErrorCode) -> Self {
+        match code {
+            ExtensionErrorCode::NotFound => SdkError::ExtensionNotFound,
+            ExtensionErrorCode::Panicked => SdkError::ExtensionPanicked,
+            ExtensionErrorCode::PayloadTooLarge => SdkError::PayloadTooLarge,
+            ExtensionErrorCode::InvalidPayload => SdkError::InvalidPayload,
+            ExtensionErrorCode::AllocationFailed => SdkError::AllocationFailed,
+            ExtensionErrorCode::HostTrap => SdkError::HostTrap,
+            ExtensionErrorCode::TimedOut => SdkError::TimedOut,
+            ExtensionErrorCode::NoMatchingVersion => SdkError::NoMatchingVersion,
+            ExtensionErrorCode::ChannelClosed => SdkError::ChannelClosed,
+            ExtensionErrorCode::Cancelled => SdkError::Cancelled,
+            ExtensionErrorCode::Unknown(code) => SdkError::HostStatus(code),
+        }
+    }
+}
+
+/// The negative status codes the host may return from an invocation. This table is shared with
+/// the host implementation, so existing values must never be renumbered; new codes get appended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExtensionErrorCode {
+    /// -1: no extension is registered under the requested name.
+    NotFound,
+    /// -2: the extension panicked while handling the call.
+    Panicked,
+    /// -3: the request payload exceeds the host's size limit.
+    PayloadTooLarge,
+    /// -4: the extension rejected the request payload.
+    InvalidPayload,
+    /// -5: the host failed to allocate memory for the response, either on its side or by calling
+    /// into our `alloc`.
+    AllocationFailed,
+    /// -6: the host itself failed while handling the call.
+    HostTrap,
+    /// -7: the extension didn't respond before the call's deadline.
+    TimedOut,
+    /// -8: the extension exists, but no installed version satisfies the requested version.
+    NoMatchingVersion,
+    /// -9: the channel has been closed by the other side.
+    ChannelClosed,
+    /// -10: the job has been cancelled, so the host gave up on the call.
+    Cancelled,
+    /// Any negative code not in the table above.
+    Unknown(i32),
+}
+
+impl ExtensionErrorCode {
+    /// Returns the raw value used on the wire for this code.
+    pub fn as_raw(&self) -> i32 {
+        match self {
+            ExtensionErrorCode::NotFound => -1,
+            ExtensionErrorCode::Panicked => -2,
+            ExtensionErrorCode::PayloadTooLarge => -3,
+            ExtensionErrorCode::InvalidPayload => -4,
+            ExtensionErrorCode::AllocationFailed => -5,
+            ExtensionErrorCode::HostTrap => -6,
+            ExtensionErrorCode::TimedOut => -7,
+            ExtensionErrorCode::NoMatchingVersion => -8,
+            ExtensionErrorCode::ChannelClosed => -9,
+            ExtensionErrorCode::Cancelled => -10,
+            ExtensionErrorCode::Unknown(code) => *code,
+        }
+    }
+}
+
+impl From<i32> for ExtensionErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            -1 => ExtensionErrorCode::NotFound,
+            -2 => ExtensionErrorCode::Panicked,
+            -3 => ExtensionErrorCode::PayloadTooLarge,
+            -4 => ExtensionErrorCode::InvalidPayload,
+            -5 => ExtensionErrorCode::AllocationFailed,
+            -6 => ExtensionErrorCode::HostTrap,
+            -7 => ExtensionErrorCode::TimedOut,
+            -8 => ExtensionErrorCode::NoMatchingVersion,
+            -9 => ExtensionErrorCode::ChannelClosed,
+            -10 => ExtensionErrorCode::Cancelled,
+            code => ExtensionErrorCode::Unknown(code),
+        }
+    }
+}
+
+impl From<ExtensionErrorCode> for i32 {
+    fn from(code: ExtensionErrorCode) -> Self {
+        code.as_raw()
+    }
+}
+
+/// Details about the most recent failed call, as recorded by the host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastError {
+    /// The status code the failed call returned.
+    pub code: ExtensionErrorCode,
+    /// A human-readable description of what went wrong.
+    pub message: String,
+    /// The name of the extension that was being invoked, if the failure happened during an
+    /// invocation.
+    pub extension: Option<String>,
+}
+
+impl LastError {
+    /// Decodes the blob returned by `get_last_error`: an i32 code, followed by a length-prefixed
+    /// extension name (empty if there was none) and a length-prefixed message.
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let code = ExtensionErrorCode::from(reader.read_i32()?);
+        let extension = reader.read_str()?;
+        let message = reader.read_str()?.to_string();
+        Ok(Self {
+            code,
+            message,
+            extension: (!extension.is_empty()).then(|| extension.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for LastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.extension {
+            Some(extension) => write!(f, "{extension}: {} ({})", self.message, self.code.as_raw()),
+            None => write!(f, "{} ({})", self.message, self.code.as_raw()),
+        }
+    }
+}
+
+/// Asks the host for details about the most recent failed call. Returns `Ok(None)` if nothing has
+/// failed yet.
+pub fn last_error() -> Result<Option<LastError>> {
+    let ptr = unsafe { host::get_last_error() };
+    if ptr < 0 {
+        return Err(ExtensionErrorCode::from(ptr as i32).into());
+    }
+    if ptr == 0 {
+        return Ok(None);
+    }
+    let bytes = take_host_bytes(ptr as usize)?;
+    LastError::decode(&bytes).map(Some)
+}
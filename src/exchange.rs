@@ -0,0 +1,51 @@
+//! Keeps the buffers the host hands us out of our own linear memory. Normally the host allocates
+//! each response, entrypoint input and so on with our `alloc` and writes it straight into our heap,
+//! so a host bug that writes past the end of a buffer, or into one it already gave up, silently
+//! corrupts whatever the heap keeps next to it. With exchange memory the host instead writes them
+//! into a separate linear memory of its own and gives us offsets into that. We copy each buffer
+//! out with a single bounds-checked `exchange_read` into a block we allocated to its exact size,
+//! then release it with `exchange_free`.
+//!
+//! Rust can't address a second memory itself, which is why the copies go through imports. Hosts
+//! opt in by calling our `serval_exchange_enable` export before anything else; ones that don't
+//! (including every host that predates it) keep writing into our memory as before. They still
+//! have to satisfy the two imports, but stubs that trap will do, since nothing calls them until
+//! the host has opted in.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::abi::EXCHANGE_VERSION;
+use crate::{host, ExtensionErrorCode, Result};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Called by a host that places the buffers it passes us in its exchange memory, with the
+/// version of the protocol it speaks. Returns 1 if we speak it too and the host should go ahead,
+/// or 0 if it should keep writing into our memory.
+#[no_mangle]
+pub extern "C" fn serval_exchange_enable(version: u32) -> u32 {
+    let accepted = version == EXCHANGE_VERSION;
+    ENABLED.store(accepted, Ordering::Relaxed);
+    accepted as u32
+}
+
+/// Whether the host has switched to exchange memory.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Copies `dest.len()` bytes at `offset` in the host's exchange memory into `dest`.
+pub(crate) fn read(offset: usize, dest: &mut [u8]) -> Result<()> {
+    let status =
+        unsafe { host::exchange_read(offset, dest.as_mut_ptr() as usize, dest.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(())
+}
+
+/// Tells the host we're done with the buffer at `offset`.
+pub(crate) fn free(offset: usize) {
+    // A buffer the host can't free is the host's leak; our copy is intact either way.
+    unsafe { host::exchange_free(offset) };
+}
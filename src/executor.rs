@@ -0,0 +1,279 @@
+//! Async support for extension calls.
+//!
+//! `invoke_extension_async` returns a future that drives an `InvocationHandle`. It works with any
+//! executor, but there's no way for the host to wake a task when a call finishes, so other
+//! executors end up polling it in a loop. `block_on` knows about the calls its futures are
+//! waiting on and sleeps in `wait_any` until one of them finishes instead.
+//!
+//! `join_all` and `select_all` combine several futures for fan-out. The futures here are `Unpin`,
+//! so they can be used directly in `select!`-style macros as well.
+
+use std::cell::{Cell, RefCell};
+use std::future::{Future, IntoFuture};
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::{host, start_invoke, ExtensionErrorCode, InvocationHandle, Result, SdkError};
+
+thread_local! {
+    /// Calls that futures polled by `block_on` are waiting on, with the waker to notify when each
+    /// one finishes.
+    static WAITING: RefCell<Vec<(u32, Waker)>> = const { RefCell::new(Vec::new()) };
+    /// Calls that were waiting when `wait_any` failed or answered with an index out of range,
+    /// with the error each fails with the next time it's polled.
+    static FAILED: RefCell<Vec<(u32, SdkError)>> = const { RefCell::new(Vec::new()) };
+    static IN_BLOCK_ON: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Invokes the named extension and resolves to its response once the call has finished. The call
+/// is started straight away rather than on the first poll; dropping the future cancels it.
+pub fn invoke_extension_async(extension_name: &str, data: &[u8]) -> InvokeFuture {
+    InvokeFuture {
+        state: start_invoke(extension_name, data).map_err(Some),
+    }
+}
+
+/// The future returned by `invoke_extension_async`.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct InvokeFuture {
+    /// The call in flight, or the error from starting it until that's been returned.
+    state: std::result::Result<InvocationHandle, Option<SdkError>>,
+}
+
+impl Future for InvokeFuture {
+    type Output = Result<Vec<u8>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let handle = match &mut self.state {
+            Ok(handle) => handle,
+            Err(err) => {
+                return Poll::Ready(Err(err
+                    .take()
+                    .expect("InvokeFuture polled after completion")))
+            }
+        };
+
+        let id = handle.id();
+        if let Some(err) = take_failed(id) {
+            // Dropping the handle cancels the call, whose result we'd never be told about.
+            self.state = Err(None);
+            return Poll::Ready(Err(err));
+        }
+        let result = handle.poll();
+        if result.is_pending() {
+            if IN_BLOCK_ON.get() {
+                WAITING.with_borrow_mut(|waiting| {
+                    waiting.retain(|(waiting_id, _)| *waiting_id != id);
+                    waiting.push((id, cx.waker().clone()));
+                });
+            } else {
+                cx.waker().wake_by_ref();
+            }
+        }
+        result
+    }
+}
+
+impl From<InvocationHandle> for InvokeFuture {
+    fn from(handle: InvocationHandle) -> Self {
+        Self { state: Ok(handle) }
+    }
+}
+
+impl IntoFuture for InvocationHandle {
+    type Output = Result<Vec<u8>>;
+    type IntoFuture = InvokeFuture;
+
+    fn into_future(self) -> InvokeFuture {
+        self.into()
+    }
+}
+
+impl Drop for InvokeFuture {
+    fn drop(&mut self) {
+        if let Ok(handle) = &self.state {
+            let id = handle.id();
+            WAITING.with_borrow_mut(|waiting| waiting.retain(|(waiting_id, _)| *waiting_id != id));
+            take_failed(id);
+        }
+    }
+}
+
+/// The error `id` failed with, if it's one of the `FAILED` calls, forgetting it.
+fn take_failed(id: u32) -> Option<SdkError> {
+    FAILED.with_borrow_mut(|failed| {
+        let index = failed.iter().position(|(failed_id, _)| *failed_id == id)?;
+        Some(failed.swap_remove(index).1)
+    })
+}
+
+/// Fails every call that's waiting with `err`, waking their futures to find out.
+fn fail_waiting(err: SdkError) {
+    let waiting = WAITING.with_borrow_mut(std::mem::take);
+    FAILED.with_borrow_mut(|failed| {
+        failed.extend(waiting.iter().map(|(id, _)| (*id, err.clone())));
+    });
+    waiting.into_iter().for_each(|(_, waker)| waker.wake());
+}
+
+/// Polls all of `futures` concurrently and resolves to their outputs, in the same order, once every
+/// one of them has finished.
+pub fn join_all<F>(futures: impl IntoIterator<Item = F>) -> JoinAll<F>
+where
+    F: Future + Unpin,
+{
+    let futures: Vec<_> = futures.into_iter().map(Some).collect();
+    JoinAll {
+        outputs: futures.iter().map(|_| None).collect(),
+        futures,
+    }
+}
+
+/// The future returned by `join_all`.
+#[must_use = "futures do nothing unless polled"]
+pub struct JoinAll<F: Future> {
+    /// The futures that haven't finished yet; finished ones are dropped as soon as they're done.
+    futures: Vec<Option<F>>,
+    outputs: Vec<Option<F::Output>>,
+}
+
+// The outputs are never pinned, so `JoinAll` can move whatever they are.
+impl<F: Future + Unpin> Unpin for JoinAll<F> {}
+
+impl<F: Future + Unpin> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut pending = false;
+        for (slot, output) in this.futures.iter_mut().zip(&mut this.outputs) {
+            if let Some(future) = slot {
+                match Pin::new(future).poll(cx) {
+                    Poll::Ready(value) => {
+                        *output = Some(value);
+                        *slot = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            return Poll::Pending;
+        }
+
+        Poll::Ready(
+            this.outputs
+                .iter_mut()
+                .map(|output| output.take().expect("JoinAll polled after completion"))
+                .collect(),
+        )
+    }
+}
+
+/// Polls all of `futures` concurrently and resolves once the first of them finishes, to its
+/// output, its index and the futures that are still running, so the rest can be selected over
+/// again.
+///
+/// # Panics
+/// Panics if `futures` is empty.
+pub fn select_all<F>(futures: impl IntoIterator<Item = F>) -> SelectAll<F>
+where
+    F: Future + Unpin,
+{
+    let futures: Vec<F> = futures.into_iter().collect();
+    assert!(!futures.is_empty(), "select_all called without any futures");
+    SelectAll { futures }
+}
+
+/// The future returned by `select_all`.
+#[must_use = "futures do nothing unless polled"]
+pub struct SelectAll<F> {
+    futures: Vec<F>,
+}
+
+impl<F: Future + Unpin> Future for SelectAll<F> {
+    type Output = (F::Output, usize, Vec<F>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let finished = self
+            .futures
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, future)| match Pin::new(future).poll(cx) {
+                Poll::Ready(output) => Some((index, output)),
+                Poll::Pending => None,
+            });
+
+        match finished {
+            Some((index, output)) => {
+                // The finished future is dropped here; the rest keep their order.
+                let mut rest = std::mem::take(&mut self.futures);
+                rest.remove(index);
+                Poll::Ready((output, index, rest))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Runs a future to completion on the current thread. Whenever everything it's waiting on is an
+/// extension call, this blocks in the host until one of them finishes rather than spinning.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            IN_BLOCK_ON.set(self.0);
+        }
+    }
+
+    // Put back even if a future panics, so a caller that catches the panic doesn't find itself
+    // still "in" this `block_on`.
+    let _restore = Restore(IN_BLOCK_ON.replace(true));
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            break output;
+        }
+        if flag.0.swap(false, Ordering::Acquire) {
+            continue;
+        }
+
+        let ids: Vec<u32> =
+            WAITING.with_borrow(|waiting| waiting.iter().map(|(id, _)| *id).collect());
+        if ids.is_empty() {
+            // Waiting on something other than an extension call; all we can do is poll again.
+            continue;
+        }
+        let index = unsafe { host::wait_any(ids.as_ptr() as usize, ids.len() as u32) };
+        if index < 0 {
+            // Going back to `wait_any` would most likely fail the same way, forever.
+            fail_waiting(ExtensionErrorCode::from(index).into());
+            continue;
+        }
+        let woken = WAITING.with_borrow_mut(|waiting| {
+            let index = index as usize;
+            (index < waiting.len()).then(|| waiting.swap_remove(index))
+        });
+        match woken {
+            Some((_, waker)) => waker.wake(),
+            // There's no telling which call finished, so none of them can be trusted.
+            None => fail_waiting(SdkError::InvalidPayload),
+        }
+    }
+}
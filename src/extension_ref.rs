@@ -0,0 +1,77 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{invoke_extension, Result, SdkError};
+
+/// A reference to an extension, optionally pinned to versions matching a requirement, written
+/// `name@version_req` (e.g. `image-resize@2` or `image-resize@^2.1`). The requirement is passed to
+/// the host as-is; if no installed version satisfies it the call fails with
+/// `SdkError::NoMatchingVersion`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExtensionRef {
+    pub name: String,
+    pub version_req: Option<String>,
+}
+
+impl ExtensionRef {
+    /// A reference to whichever version of the extension the host picks.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version_req: None,
+        }
+    }
+
+    /// A reference to versions of the extension matching `version_req`.
+    pub fn versioned(name: impl Into<String>, version_req: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version_req: Some(version_req.into()),
+        }
+    }
+
+    /// Invokes the referenced extension.
+    pub fn invoke(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match &self.version_req {
+            Some(_) => invoke_extension(self.to_string(), data),
+            None => invoke_extension(&self.name, data),
+        }
+    }
+}
+
+impl fmt::Display for ExtensionRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version_req {
+            Some(version_req) => write!(f, "{}@{version_req}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl FromStr for ExtensionRef {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || SdkError::InvalidExtensionRef(s.to_string());
+        let (name, version_req) = match s.split_once('@') {
+            Some((name, version_req)) if !version_req.is_empty() && !version_req.contains('@') => {
+                (name, Some(version_req.to_string()))
+            }
+            Some(_) => return Err(invalid()),
+            None => (s, None),
+        };
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            version_req,
+        })
+    }
+}
+
+/// Invokes an extension identified by a string that may pin a version, e.g. `image-resize@2`.
+/// The reference is validated before anything is sent to the host.
+pub fn invoke(extension_ref: &str, data: &[u8]) -> Result<Vec<u8>> {
+    extension_ref.parse::<ExtensionRef>()?.invoke(data)
+}
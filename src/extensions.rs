@@ -0,0 +1,147 @@
+//! Discovering which extensions the node the guest runs on has installed, so a job can adapt to
+//! what's there (skip an optional step, pick another backend) instead of finding out from a
+//! `NotFound` at its first call. `describe` goes further and reports what an extension declares
+//! about itself, so a guest can check that the extension still speaks the schemas it was built
+//! against before relying on them.
+
+use crate::wire::Reader;
+use crate::{host, take_host_bytes, ExtensionErrorCode, Result, SdkError};
+
+/// An extension installed on the node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extension {
+    /// The name calls address it by.
+    pub name: String,
+    /// The version the host reports for it, or empty if the host doesn't version it.
+    pub version: String,
+}
+
+/// Returns the extensions available on this node, in the order the host lists them.
+pub fn list() -> Result<Vec<Extension>> {
+    let out_ptr = unsafe { host::list_extensions() };
+    if out_ptr < 0 {
+        return Err(ExtensionErrorCode::from(out_ptr as i32).into());
+    }
+    decode(&take_host_bytes(out_ptr as usize)?)
+}
+
+/// Decodes the layout `list_extensions` returns: a u32 count followed by that many length-prefixed
+/// name and version pairs.
+fn decode(bytes: &[u8]) -> Result<Vec<Extension>> {
+    let mut reader = Reader::new(bytes);
+    let count = reader.read_u32()?;
+    let mut extensions = Vec::new();
+    for _ in 0..count {
+        let name = reader.read_str()?.to_string();
+        let version = reader.read_str()?.to_string();
+        extensions.push(Extension { name, version });
+    }
+    Ok(extensions)
+}
+
+/// What an extension declares about itself; see `describe`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Description {
+    pub name: String,
+    /// The version the host reports for it, or empty if the host doesn't version it.
+    pub version: String,
+    /// The operations the extension declares, in the order it declares them.
+    pub operations: Vec<Operation>,
+    /// The content types it accepts payloads in, such as `content_type::JSON`.
+    pub codecs: Vec<String>,
+}
+
+/// An operation an extension declares.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    pub name: String,
+    /// A hash of the operation's request and response schemas, which changes whenever either
+    /// does. How it's computed is up to the extension; guests compare it against the hash they
+    /// were built against.
+    pub schema_hash: u64,
+}
+
+impl Description {
+    /// Returns the operation `name`, if the extension declares it.
+    pub fn operation(&self, name: &str) -> Option<&Operation> {
+        self.operations
+            .iter()
+            .find(|operation| operation.name == name)
+    }
+
+    /// Whether the extension accepts payloads of `content_type`.
+    pub fn supports_codec(&self, content_type: &str) -> bool {
+        self.codecs.iter().any(|codec| codec == content_type)
+    }
+
+    /// Checks that the extension declares `operation` with the schema hash `expected`, returning
+    /// `SdkError::SchemaMismatch` if it doesn't declare it or its schema has since changed.
+    pub fn check_schema(&self, operation: &str, expected: u64) -> Result<()> {
+        let actual = self
+            .operation(operation)
+            .map(|operation| operation.schema_hash);
+        if actual == Some(expected) {
+            return Ok(());
+        }
+        Err(SdkError::SchemaMismatch {
+            extension: self.name.clone(),
+            operation: operation.to_string(),
+            expected,
+            actual,
+        })
+    }
+
+    /// Decodes the layout `describe_extension` returns: the length-prefixed name and version, a
+    /// u32 operation count followed by that many length-prefixed names each followed by a u64
+    /// schema hash, then a u32 codec count followed by that many length-prefixed content types.
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let name = reader.read_str()?.to_string();
+        let version = reader.read_str()?.to_string();
+        let mut operations = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            let name = reader.read_str()?.to_string();
+            let schema_hash = reader.read_u64()?;
+            operations.push(Operation { name, schema_hash });
+        }
+        let mut codecs = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            codecs.push(reader.read_str()?.to_string());
+        }
+        Ok(Self {
+            name,
+            version,
+            operations,
+            codecs,
+        })
+    }
+
+    /// Encodes the description in the layout `decode` reads, for the stand-in hosts.
+    #[cfg(any(feature = "mock-host", feature = "harness"))]
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut writer = crate::wire::Writer::new();
+        writer.write_str(&self.name);
+        writer.write_str(&self.version);
+        writer.write_u32(self.operations.len() as u32);
+        for operation in &self.operations {
+            writer.write_str(&operation.name);
+            writer.write_u64(operation.schema_hash);
+        }
+        writer.write_u32(self.codecs.len() as u32);
+        for codec in &self.codecs {
+            writer.write_str(codec);
+        }
+        writer.into_bytes()
+    }
+}
+
+/// Returns what the extension `name` declares about itself, or `None` if it isn't installed on
+/// this node.
+pub fn describe(name: &str) -> Result<Option<Description>> {
+    let out_ptr = unsafe { host::describe_extension(name.as_ptr() as usize, name.len() as u32) };
+    match out_ptr {
+        0 => Ok(None),
+        out_ptr if out_ptr < 0 => Err(ExtensionErrorCode::from(out_ptr as i32).into()),
+        out_ptr => Description::decode(&take_host_bytes(out_ptr as usize)?).map(Some),
+    }
+}
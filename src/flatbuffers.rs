@@ -0,0 +1,24 @@
+//! Zero-copy access to FlatBuffers responses. Multi-megabyte responses can be read in place out of
+//! the buffer the host wrote them into, without copying them into a `Vec` first.
+
+use flatbuffers::{Follow, Verifiable};
+
+use crate::{invoke_extension_owned, CodecError, OwnedHostBytes, Result, SdkError};
+
+/// Invokes an extension whose response is a FlatBuffer, returning the host buffer as-is. Use
+/// `OwnedHostBytes::flatbuffer_root` to get a view over it.
+pub fn invoke_flatbuffer(extension_name: &str, data: &[u8]) -> Result<OwnedHostBytes> {
+    invoke_extension_owned(extension_name, data)
+}
+
+impl OwnedHostBytes {
+    /// Verifies the buffer and returns a view of its root table of type `T`. The view borrows
+    /// from the buffer, which keeps the underlying allocation alive for as long as it's in use.
+    pub fn flatbuffer_root<'a, T>(&'a self) -> Result<T::Inner>
+    where
+        T: Follow<'a> + Verifiable + 'a,
+    {
+        flatbuffers::root::<T>(self)
+            .map_err(|err| SdkError::Decode(CodecError::new(err.to_string())))
+    }
+}
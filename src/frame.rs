@@ -0,0 +1,366 @@
+//! The invocation frame used by `invoke_framed`: a small header carrying flags and tagged fields,
+//! followed by the payload itself. Responses to framed invocations use the same layout, so the
+//! host can tell us how it encoded its reply.
+//!
+//! Layout, with all integers little-endian:
+//!
+//! ```text
+//! u8   frame version (FRAME_VERSION)
+//! u8   flags (see `flags`)
+//! u16  number of header fields
+//!      each field: u8 tag, u32 length, value bytes
+//! ..   body (the rest of the frame)
+//! ```
+
+use crate::wire::{Reader, Writer};
+use crate::{check_ptr, host, take_host_bytes, CodecError, InvocationError, Result, SdkError};
+
+/// The version of the frame layout described above.
+pub const FRAME_VERSION: u8 = 1;
+
+/// Bits in the frame header's flags byte.
+pub mod flags {
+    /// The body is compressed with LZ4 (size-prepended block format).
+    pub const LZ4: u8 = 1 << 0;
+    /// The body is compressed with zstd.
+    pub const ZSTD: u8 = 1 << 1;
+    /// Set on requests: the guest can decompress an LZ4 response.
+    pub const ACCEPT_LZ4: u8 = 1 << 2;
+    /// Set on requests: the guest can decompress a zstd response.
+    pub const ACCEPT_ZSTD: u8 = 1 << 3;
+}
+
+/// Tags for the header fields the SDK knows about.
+pub mod tags {
+    /// A MIME-like content type describing the body, as UTF-8. See `crate::content_type`.
+    pub const CONTENT_TYPE: u8 = 1;
+    /// The name of the operation being invoked on an extension that exposes several, as UTF-8.
+    pub const OPERATION: u8 = 2;
+    /// How long the host should wait for the extension before failing the call with
+    /// `ExtensionErrorCode::TimedOut`, as a little-endian u32 number of milliseconds.
+    pub const TIMEOUT_MS: u8 = 3;
+    /// A guest callback the extension may call while the invocation is in flight, as a
+    /// little-endian u32 callback id followed by the UTF-8 name the extension knows it by. May
+    /// appear more than once. See `crate::Callback`.
+    pub const CALLBACK: u8 = 4;
+    /// Set on responses from platform services (`kv` and friends) to report whether the request
+    /// succeeded, as a little-endian u32; see `status`. A response without one succeeded.
+    pub const STATUS: u8 = 5;
+    /// The trace ID of the request the invocation is part of, as UTF-8. The SDK adds
+    /// `crate::current_trace_id` to the requests it sends that don't already carry one.
+    pub const TRACE_ID: u8 = 6;
+    /// An entry of the invocation's metadata: a length-prefixed UTF-8 key followed by a
+    /// length-prefixed UTF-8 value. May appear more than once, with the same key or different
+    /// ones. See `Frame::metadata`.
+    pub const METADATA: u8 = 7;
+}
+
+/// The values of the `tags::STATUS` field. Services may use codes from `SERVICE_SPECIFIC` upwards
+/// for failures of their own. A failed response's body is a UTF-8 description of the failure.
+pub mod status {
+    pub const OK: u32 = 0;
+    /// Nothing exists under the requested key or name.
+    pub const NOT_FOUND: u32 = 1;
+    /// A write's precondition no longer holds.
+    pub const CONFLICT: u32 = 2;
+    pub const SERVICE_SPECIFIC: u32 = 100;
+}
+
+/// A tagged value in a frame header. Tags identify what the value means; receivers skip tags they
+/// don't recognize.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderField {
+    pub tag: u8,
+    pub value: Vec<u8>,
+}
+
+/// A frame: header flags and fields plus a body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub flags: u8,
+    pub fields: Vec<HeaderField>,
+    pub body: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(body: Vec<u8>) -> Self {
+        Self {
+            body,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the value of the first field with the given tag.
+    pub fn field(&self, tag: u8) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|field| field.tag == tag)
+            .map(|field| field.value.as_slice())
+    }
+
+    pub fn push_field(&mut self, tag: u8, value: Vec<u8>) {
+        self.fields.push(HeaderField { tag, value });
+    }
+
+    /// Returns the body's content type, if the sender declared one.
+    pub fn content_type(&self) -> Option<&str> {
+        self.field(tags::CONTENT_TYPE)
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// Declares the body's content type, replacing any previously declared one.
+    pub fn set_content_type(&mut self, content_type: &str) {
+        self.fields.retain(|field| field.tag != tags::CONTENT_TYPE);
+        self.push_field(tags::CONTENT_TYPE, content_type.as_bytes().to_vec());
+    }
+
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.set_content_type(content_type);
+        self
+    }
+
+    /// Returns the operation this frame invokes, if it names one.
+    pub fn operation(&self) -> Option<&str> {
+        self.field(tags::OPERATION)
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// Names the operation this frame invokes, replacing any previously named one.
+    pub fn set_operation(&mut self, operation: &str) {
+        self.fields.retain(|field| field.tag != tags::OPERATION);
+        self.push_field(tags::OPERATION, operation.as_bytes().to_vec());
+    }
+
+    /// Returns the frame's metadata entries in the order they were added: key/value pairs that
+    /// travel alongside the body, like gRPC metadata, for auth tokens, tenancy and the like.
+    /// Malformed entries are skipped.
+    pub fn metadata(&self) -> Vec<(&str, &str)> {
+        self.fields
+            .iter()
+            .filter(|field| field.tag == tags::METADATA)
+            .filter_map(|field| {
+                let mut reader = Reader::new(&field.value);
+                Some((reader.read_str().ok()?, reader.read_str().ok()?))
+            })
+            .collect()
+    }
+
+    /// Returns the value of the first metadata entry with the given key.
+    pub fn metadata_value(&self, key: &str) -> Option<&str> {
+        self.metadata()
+            .into_iter()
+            .find(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Adds a metadata entry. Entries already under `key` are kept.
+    pub fn add_metadata(&mut self, key: &str, value: &str) {
+        self.push_field(tags::METADATA, metadata_entry(key, value));
+    }
+
+    /// `add_metadata` in builder form, so a request can be put together in one expression:
+    /// `Frame::new(body).with_metadata("tenant", "acme")`. Like it, this adds an entry rather than
+    /// replacing the ones already under `key`.
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.add_metadata(key, value);
+        self
+    }
+
+    /// Returns the trace ID the frame carries, if any.
+    pub fn trace_id(&self) -> Option<&str> {
+        self.field(tags::TRACE_ID)
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// Encodes the frame in the layout above. Fails with `SdkError::Encode` if it has more header
+    /// fields than the u16 count can hold.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        self.encode_with(None)
+    }
+
+    /// Encodes the frame as a request, adding `crate::current_trace_id` unless it already carries
+    /// a trace ID. For requests that go to the host some other way than `invoke_framed`.
+    pub(crate) fn encode_for_send(&self) -> Result<Vec<u8>> {
+        let trace_id = match self.field(tags::TRACE_ID) {
+            Some(_) => None,
+            None => crate::current_trace_id().map(|id| HeaderField {
+                tag: tags::TRACE_ID,
+                value: id.into_bytes(),
+            }),
+        };
+        self.encode_with(trace_id.as_ref())
+    }
+
+    /// Encodes the frame with `extra` appended to its header fields, without copying the body
+    /// into a new frame first.
+    fn encode_with(&self, extra: Option<&HeaderField>) -> Result<Vec<u8>> {
+        let field_count = u16::try_from(self.fields.len() + extra.is_some() as usize)
+            .map_err(|_| SdkError::Encode(CodecError::new("too many frame header fields")))?;
+        let mut writer = Writer::new();
+        writer.write_u8(FRAME_VERSION);
+        writer.write_u8(self.flags);
+        writer.write_u16(field_count);
+        for field in self.fields.iter().chain(extra) {
+            writer.write_u8(field.tag);
+            writer.write_prefixed(&field.value);
+        }
+        writer.write_bytes(&self.body);
+        Ok(writer.into_bytes())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_u8()? != FRAME_VERSION {
+            return Err(SdkError::InvalidPayload);
+        }
+        let flags = reader.read_u8()?;
+        let field_count = reader.read_u16()?;
+        let fields = (0..field_count)
+            .map(|_| {
+                Ok(HeaderField {
+                    tag: reader.read_u8()?,
+                    value: reader.read_prefixed()?.to_vec(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            flags,
+            fields,
+            body: reader.remaining().to_vec(),
+        })
+    }
+}
+
+/// Encodes a `tags::METADATA` field value.
+pub(crate) fn metadata_entry(key: &str, value: &str) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_str(key);
+    writer.write_str(value);
+    writer.into_bytes()
+}
+
+/// Invokes an extension with a full frame rather than a bare payload, and returns the host's
+/// response frame. Most callers want one of the higher-level invoke functions instead; this is
+/// the building block they share.
+pub fn invoke_framed(extension_name: &str, frame: &Frame) -> Result<Frame> {
+    crate::job::auto_heartbeat();
+    let encoded = frame.encode_for_send()?;
+    let started = crate::history::start();
+    let out_ptr = unsafe {
+        host::invoke_framed(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            encoded.as_ptr() as usize,
+            encoded.len() as u32,
+        )
+    };
+
+    let response = check_ptr(out_ptr, extension_name, encoded.len()).and_then(|ptr| {
+        take_host_bytes(ptr).map_err(|err| {
+            InvocationError::new(extension_name, encoded.len(), out_ptr as i32, err).into()
+        })
+    });
+    crate::cassette::record(extension_name, &encoded, &response);
+    crate::history::record(started, extension_name, encoded.len(), &response);
+    Frame::decode(&response?).map_err(|err| {
+        InvocationError::new(extension_name, encoded.len(), out_ptr as i32, err).into()
+    })
+}
+
+/// Sends a frame through the same pipeline `invoke_extension` uses, which currently means
+/// applying the compression config, if any.
+pub(crate) fn send_frame(extension_name: &str, frame: Frame) -> Result<Frame> {
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    return crate::compression::send_compressed(
+        extension_name,
+        frame,
+        crate::compression::compression(),
+    );
+    #[cfg(not(any(feature = "lz4", feature = "zstd")))]
+    invoke_framed(extension_name, &frame)
+}
+
+/// Invokes an extension, declaring the content type of the payload. The returned frame carries
+/// the response body along with the content type the extension declared for it, if any; see
+/// `Frame::content_type`.
+pub fn invoke_with_content_type(
+    extension_name: &str,
+    content_type: &str,
+    data: &[u8],
+) -> Result<Frame> {
+    send_frame(
+        extension_name,
+        Frame::new(data.to_vec()).with_content_type(content_type),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tags, Frame};
+    use crate::SdkError;
+
+    /// A response carrying two entries under the same key and a metadata field too short to hold
+    /// the entry it starts, in that order.
+    fn response_with_repeated_and_malformed_metadata() -> Frame {
+        let mut response = Frame::new(b"body".to_vec())
+            .with_metadata("set-cookie", "a=1")
+            .with_metadata("set-cookie", "b=2");
+        response.push_field(tags::METADATA, vec![10, 0, 0, 0, b'k']);
+        response
+    }
+
+    #[test]
+    fn decoded_metadata_keeps_repeated_keys_and_skips_malformed_entries() {
+        let encoded = response_with_repeated_and_malformed_metadata()
+            .encode()
+            .unwrap();
+        let response = Frame::decode(&encoded).unwrap();
+        assert_eq!(
+            response.metadata(),
+            vec![("set-cookie", "a=1"), ("set-cookie", "b=2")]
+        );
+        assert_eq!(response.metadata_value("set-cookie"), Some("a=1"));
+        assert_eq!(response.metadata_value("k"), None);
+        // The malformed field is skipped by `metadata`, not dropped from the frame.
+        assert_eq!(response.fields.len(), 3);
+    }
+
+    #[test]
+    fn metadata_entries_with_invalid_utf8_are_skipped() {
+        let mut response = Frame::new(Vec::new());
+        response.push_field(tags::METADATA, vec![1, 0, 0, 0, 0xff, 1, 0, 0, 0, b'v']);
+        response.add_metadata("tenant", "acme");
+        let response = Frame::decode(&response.encode().unwrap()).unwrap();
+        assert_eq!(response.metadata(), vec![("tenant", "acme")]);
+    }
+
+    #[test]
+    fn frames_with_more_fields_than_the_count_holds_fail_to_encode() {
+        let mut frame = Frame::new(b"body".to_vec());
+        for _ in 0..u16::MAX {
+            frame.push_field(200, Vec::new());
+        }
+        let decoded = Frame::decode(&frame.encode().unwrap()).unwrap();
+        assert_eq!(decoded.fields.len(), u16::MAX as usize);
+
+        frame.push_field(200, Vec::new());
+        assert!(matches!(frame.encode(), Err(SdkError::Encode(_))));
+    }
+
+    #[cfg(feature = "mock-host")]
+    #[test]
+    fn invocation_errors_and_history_both_report_the_encoded_size() {
+        crate::history::enable(1024);
+        crate::mock::respond_with("frame-sizes", Ok(vec![0xff]));
+        let frame = Frame::new(b"body".to_vec()).with_metadata("tenant", "acme");
+        let err = super::invoke_framed("frame-sizes", &frame).unwrap_err();
+
+        let sent = frame.encode().unwrap().len();
+        assert_eq!(err.context().unwrap().payload_len, sent);
+        let entries = crate::history::entries();
+        let entry = entries
+            .iter()
+            .find(|entry| entry.extension == "frame-sizes");
+        assert_eq!(entry.unwrap().request_len, sent);
+    }
+}
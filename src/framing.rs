@@ -0,0 +1,121 @@
+//! The length-prefixed framing used for every buffer exchanged with the host.
+//!
+//! A frame is the data's length as a **little-endian** u32, followed by the data itself:
+//!
+//! ```text
+//! +----------------+-----------------+
+//! | len: u32 (LE)  | data: len bytes |
+//! +----------------+-----------------+
+//! ```
+//!
+//! Responses from the host, entrypoint inputs and outputs, and the length-prefixed fields inside
+//! invocation frames (`crate::frame`) all use this layout, so guest exports and SDKs in other
+//! languages can share it. Data longer than `u32::MAX` bytes can't be framed.
+
+use crate::{Result, SdkError};
+
+/// The size of the length prefix in bytes.
+pub const PREFIX_LEN: usize = 4;
+
+/// Frames `data`: its length as a little-endian u32 followed by the data.
+///
+/// # Panics
+/// Panics if `data` is longer than `u32::MAX` bytes.
+pub fn encode_frame(data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(PREFIX_LEN + data.len());
+    write_frame(&mut frame, data);
+    frame
+}
+
+/// Like `encode_frame`, but appends the frame to `buf`.
+///
+/// # Panics
+/// Panics if `data` is longer than `u32::MAX` bytes.
+pub fn write_frame(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&encode_prefix(data.len()));
+    buf.extend_from_slice(data);
+}
+
+/// Splits the frame at the start of `bytes` into its data and whatever follows it, so a sequence
+/// of frames can be decoded one after the other. Fails with `SdkError::InvalidPayload` if `bytes`
+/// is shorter than the prefix or than the length it declares.
+pub fn decode_frame(bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    let (prefix, rest) = bytes
+        .split_first_chunk::<PREFIX_LEN>()
+        .ok_or(SdkError::InvalidPayload)?;
+    let len = decode_prefix(*prefix);
+    if rest.len() < len {
+        return Err(SdkError::InvalidPayload);
+    }
+    Ok(rest.split_at(len))
+}
+
+/// The length prefix for `len` bytes of data.
+///
+/// # Panics
+/// Panics if `len` doesn't fit in a u32.
+pub fn encode_prefix(len: usize) -> [u8; PREFIX_LEN] {
+    u32::try_from(len)
+        .expect("frames can't hold more than u32::MAX bytes")
+        .to_le_bytes()
+}
+
+/// The data length declared by a length prefix.
+pub fn decode_prefix(prefix: [u8; PREFIX_LEN]) -> usize {
+    u32::from_le_bytes(prefix) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::{decode_frame, encode_frame, write_frame, PREFIX_LEN};
+    use crate::SdkError;
+
+    proptest! {
+        #[test]
+        fn frames_round_trip(data in vec(any::<u8>(), 0..1024), rest in vec(any::<u8>(), 0..64)) {
+            let mut bytes = encode_frame(&data);
+            prop_assert_eq!(bytes.len(), PREFIX_LEN + data.len());
+            bytes.extend_from_slice(&rest);
+            prop_assert_eq!(decode_frame(&bytes)?, (&data[..], &rest[..]));
+        }
+
+        #[test]
+        fn consecutive_frames_decode_in_order(frames in vec(vec(any::<u8>(), 0..64), 0..8)) {
+            let mut bytes = Vec::new();
+            for data in &frames {
+                write_frame(&mut bytes, data);
+            }
+            let mut rest = &bytes[..];
+            for data in &frames {
+                let (decoded, tail) = decode_frame(rest)?;
+                prop_assert_eq!(decoded, &data[..]);
+                rest = tail;
+            }
+            prop_assert!(rest.is_empty());
+        }
+
+        #[test]
+        fn truncated_frames_are_rejected(
+            data in vec(any::<u8>(), 0..1024),
+            cut in any::<prop::sample::Index>(),
+        ) {
+            let bytes = encode_frame(&data);
+            let truncated = &bytes[..cut.index(bytes.len())];
+            prop_assert_eq!(decode_frame(truncated), Err(SdkError::InvalidPayload));
+        }
+
+        #[test]
+        fn declared_lengths_past_the_end_are_rejected(
+            len in 1u32..,
+            data in vec(any::<u8>(), 0..64),
+        ) {
+            prop_assume!(len as usize > data.len());
+            let mut bytes = len.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&data);
+            prop_assert_eq!(decode_frame(&bytes), Err(SdkError::InvalidPayload));
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Files in the job's storage on the host, shaped like `std::fs` so that file-oriented libraries
+//! can be ported with little more than a changed import.
+//!
+//! ```ignore
+//! use std::io::{Read, Write};
+//! use serval::fs::File;
+//!
+//! let mut file = File::create("reports/today.csv")?;
+//! file.write_all(b"id,total\n")?;
+//! ```
+//!
+//! Everything returns `io::Result`, like `std::fs` does. A host `NotFound` becomes
+//! `io::ErrorKind::NotFound` so callers can check for missing files the usual way; any other
+//! failure is an `io::Error` wrapping the `SdkError`. Paths are `/`-separated and relative to the
+//! root of the job's storage.
+
+use std::io;
+
+use crate::wire::Reader;
+use crate::{get_bytes_from_host, host, ExtensionErrorCode, SdkError};
+
+/// The flag bits `fs_open` takes.
+pub(crate) mod flags {
+    pub const READ: u32 = 1 << 0;
+    pub const WRITE: u32 = 1 << 1;
+    pub const CREATE: u32 = 1 << 2;
+    pub const TRUNCATE: u32 = 1 << 3;
+    pub const APPEND: u32 = 1 << 4;
+}
+
+/// The `whence` values `fs_seek` takes.
+pub(crate) mod whence {
+    pub const START: u32 = 0;
+    pub const CURRENT: u32 = 1;
+    pub const END: u32 = 2;
+}
+
+/// Options for opening a file, as in `std::fs::OpenOptions`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenOptions {
+    flags: u32,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.set(flags::READ, read)
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.set(flags::WRITE, write)
+    }
+
+    /// Creates the file if it doesn't exist. Requires `write` or `append`.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.set(flags::CREATE, create)
+    }
+
+    /// Empties the file if it exists. Requires `write`.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.set(flags::TRUNCATE, truncate)
+    }
+
+    /// Makes every write go to the end of the file, wherever its position is. Appending implies
+    /// `write`, as with `std::fs::OpenOptions`, but turning it off leaves `write` as it was.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.set(flags::APPEND, append)
+    }
+
+    pub fn open(&self, path: &str) -> io::Result<File> {
+        let id =
+            unsafe { host::fs_open(path.as_ptr() as usize, path.len() as u32, self.open_flags()) };
+        Ok(File {
+            id: check(id)?,
+            path: path.to_string(),
+            closed: false,
+        })
+    }
+
+    /// The flags `fs_open` is called with.
+    fn open_flags(&self) -> u32 {
+        if self.flags & flags::APPEND != 0 {
+            self.flags | flags::WRITE
+        } else {
+            self.flags
+        }
+    }
+
+    fn set(&mut self, flag: u32, enabled: bool) -> &mut Self {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+        self
+    }
+}
+
+/// An open file in the job's storage. Everything written is durable once the file has been
+/// closed, either explicitly with `close` or by dropping it.
+#[derive(Debug)]
+pub struct File {
+    id: u32,
+    path: String,
+    closed: bool,
+}
+
+impl File {
+    /// Opens an existing file for reading.
+    pub fn open(path: &str) -> io::Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Opens a file for writing, creating it if it doesn't exist and emptying it if it does.
+    pub fn create(path: &str) -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// The path the file was opened with.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Closes the file, reporting any error the host ran into making it durable. Dropping the
+    /// file closes it too, but ignores errors.
+    pub fn close(mut self) -> io::Result<()> {
+        self.closed = true;
+        let status = unsafe { host::fs_close(self.id) };
+        check(status).map(drop)
+    }
+}
+
+impl io::Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = unsafe { host::fs_read(self.id, buf.as_mut_ptr() as usize, buf.len() as u32) };
+        Ok(check(len)? as usize)
+    }
+}
+
+impl io::Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = unsafe { host::fs_write(self.id, buf.as_ptr() as usize, buf.len() as u32) };
+        Ok(check(len)? as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Seek for File {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let (whence, offset) = match pos {
+            io::SeekFrom::Start(offset) => (
+                whence::START,
+                i64::try_from(offset).map_err(|_| io::ErrorKind::InvalidInput)?,
+            ),
+            io::SeekFrom::Current(offset) => (whence::CURRENT, offset),
+            io::SeekFrom::End(offset) => (whence::END, offset),
+        };
+        let position = unsafe { host::fs_seek(self.id, whence, offset) };
+        if position < 0 {
+            // Error codes are small negative numbers, so this can't truncate.
+            return Err(to_io_error(position as i32));
+        }
+        Ok(position as u64)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if !self.closed {
+            unsafe { host::fs_close(self.id) };
+        }
+    }
+}
+
+/// An entry returned by `read_dir`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    /// The entry's name within its directory.
+    pub name: String,
+    pub is_dir: bool,
+    /// The file's size in bytes; 0 for directories.
+    pub len: u64,
+}
+
+/// Lists the entries of a directory. An empty path lists the root of the job's storage.
+pub fn read_dir(path: &str) -> io::Result<Vec<DirEntry>> {
+    let out_ptr = unsafe { host::fs_list(path.as_ptr() as usize, path.len() as u32) };
+    if out_ptr < 0 {
+        return Err(to_io_error(out_ptr as i32));
+    }
+    let bytes = get_bytes_from_host(out_ptr as usize).map_err(io::Error::other)?;
+    parse_entries(&bytes).map_err(io::Error::other)
+}
+
+/// Deletes a file.
+pub fn remove_file(path: &str) -> io::Result<()> {
+    let status = unsafe { host::fs_remove(path.as_ptr() as usize, path.len() as u32) };
+    check(status).map(drop)
+}
+
+/// Reads a whole file into memory.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    io::Read::read_to_end(&mut File::open(path)?, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads a whole file into memory as UTF-8.
+pub fn read_to_string(path: &str) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Replaces a file's contents with `contents`, creating it if it doesn't exist.
+pub fn write(path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    io::Write::write_all(&mut file, contents.as_ref())?;
+    file.close()
+}
+
+/// Parses the layout `fs_list` returns: a u32 count followed by that many entries.
+fn parse_entries(bytes: &[u8]) -> crate::Result<Vec<DirEntry>> {
+    let mut reader = Reader::new(bytes);
+    let count = reader.read_u32()?;
+    (0..count)
+        .map(|_| {
+            Ok(DirEntry {
+                name: reader.read_str()?.to_string(),
+                is_dir: reader.read_u8()? != 0,
+                len: reader.read_u64()?,
+            })
+        })
+        .collect()
+}
+
+/// Turns a file call's return value into the non-negative value it carries, or the error it
+/// reports.
+fn check(status: i32) -> io::Result<u32> {
+    if status < 0 {
+        return Err(to_io_error(status));
+    }
+    Ok(status as u32)
+}
+
+fn to_io_error(status: i32) -> io::Error {
+    match ExtensionErrorCode::from(status) {
+        ExtensionErrorCode::NotFound => io::ErrorKind::NotFound.into(),
+        code => io::Error::other(SdkError::from(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flags, OpenOptions};
+
+    #[test]
+    fn append_implies_write_without_clearing_it() {
+        let append = OpenOptions::new().append(true).open_flags();
+        assert_eq!(append, flags::APPEND | flags::WRITE);
+
+        let write = OpenOptions::new().write(true).append(false).open_flags();
+        assert_eq!(write, flags::WRITE);
+
+        let read = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .append(false)
+            .open_flags();
+        assert_eq!(read, flags::READ);
+    }
+}
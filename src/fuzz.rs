@@ -0,0 +1,132 @@
+//! Entrypoints for fuzzing the code that parses bytes from the host. Each takes arbitrary input,
+//! never fails on malformed data, and panics if it finds an invariant broken, so they plug
+//! straight into a fuzzer. With cargo-fuzz:
+//!
+//! ```ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| serval::fuzz::frame(data));
+//! ```
+//!
+//! They're most useful under a sanitizer, since the buffers exchanged with the host are where the
+//! crate's unsafe code lives.
+
+use crate::cassette::Cassette;
+use crate::envelope::Envelope;
+use crate::frame::Frame;
+use crate::framing::{decode_frame, encode_frame, PREFIX_LEN};
+use crate::take_host_bytes;
+
+/// Decodes `data` as an invocation frame. Frames that decode have to encode back to exactly
+/// `data`.
+pub fn frame(data: &[u8]) {
+    if let Ok(frame) = Frame::decode(data) {
+        // A decoded frame has at most `u16::MAX` fields, so it always encodes.
+        let encoded = frame.encode().expect("decoded frame didn't encode");
+        assert_eq!(encoded, data, "frame didn't re-encode to its input");
+    }
+}
+
+/// Decodes `data` as a sequence of length-prefixed frames, the way streams of chunks and the
+/// fields inside other layouts are read. The frames that decode have to encode back to the part
+/// of `data` they were read from.
+pub fn framing(data: &[u8]) {
+    let mut rest = data;
+    let mut consumed = 0;
+    while let Ok((frame, tail)) = decode_frame(rest) {
+        assert_eq!(frame.len() + tail.len() + PREFIX_LEN, rest.len());
+        assert_eq!(encode_frame(frame), &rest[..PREFIX_LEN + frame.len()]);
+        consumed += PREFIX_LEN + frame.len();
+        rest = tail;
+    }
+    assert!(consumed <= data.len());
+}
+
+/// Decodes `data` as an envelope, which has to survive an encode and decode round trip.
+pub fn envelope(data: &[u8]) {
+    if let Ok(envelope) = Envelope::decode(data) {
+        let encoded = envelope.encode();
+        assert_eq!(
+            encoded,
+            &data[..encoded.len()],
+            "envelope didn't re-encode to its input"
+        );
+        assert_eq!(Envelope::decode(&encoded).as_ref(), Ok(&envelope));
+    }
+}
+
+/// Decodes `data` as a recorded cassette, which has to survive an encode and decode round trip.
+pub fn cassette(data: &[u8]) {
+    if let Ok(cassette) = Cassette::decode(data) {
+        assert_eq!(Cassette::decode(&cassette.encode()).as_ref(), Ok(&cassette));
+    }
+}
+
+/// Passes `data` through a host buffer the way responses arrive: copied into a length-prefixed
+/// block from our allocator, taken over as `OwnedHostBytes` and turned into a `Vec`. The bytes
+/// have to come out unchanged, and the block has to be freed cleanly.
+pub fn host_bytes(data: &[u8]) {
+    // The mock host hands buffers out by id; see `mock::resolve`.
+    #[cfg(feature = "mock-host")]
+    let ptr = crate::mock::to_guest(data) as usize;
+    #[cfg(not(feature = "mock-host"))]
+    let ptr = crate::bytes_to_host(data);
+    let bytes = take_host_bytes(ptr).expect("a fresh host buffer was rejected");
+    assert_eq!(&*bytes, data);
+    if data.first().is_some_and(|byte| byte & 1 == 1) {
+        assert_eq!(bytes.into_vec(), data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use crate::envelope::Envelope;
+    use crate::frame::{Frame, HeaderField};
+    use crate::framing::encode_frame;
+
+    fn bytes() -> impl Strategy<Value = Vec<u8>> {
+        vec(any::<u8>(), 0..512)
+    }
+
+    proptest! {
+        #[test]
+        fn entrypoints_accept_arbitrary_input(data in bytes()) {
+            super::frame(&data);
+            super::framing(&data);
+            super::envelope(&data);
+            super::cassette(&data);
+            super::host_bytes(&data);
+        }
+
+        #[test]
+        fn frame_accepts_encoded_frames(
+            flags in any::<u8>(),
+            fields in vec((any::<u8>(), bytes()), 0..4),
+            body in bytes(),
+        ) {
+            let fields = fields
+                .into_iter()
+                .map(|(tag, value)| HeaderField { tag, value })
+                .collect();
+            super::frame(&Frame { flags, fields, body }.encode()?);
+        }
+
+        #[test]
+        fn framing_accepts_encoded_frames(frames in vec(bytes(), 0..4), tail in bytes()) {
+            let mut data: Vec<u8> = frames.iter().flat_map(|frame| encode_frame(frame)).collect();
+            data.extend_from_slice(&tail);
+            super::framing(&data);
+        }
+
+        #[test]
+        fn envelope_accepts_encoded_envelopes(
+            schema_version in any::<u32>(),
+            content_type in "[a-z]{1,8}/[a-z]{1,8}",
+            body in bytes(),
+        ) {
+            super::envelope(&Envelope::new(schema_version, content_type, body).encode());
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+use crate::{host, wire::Reader, wire::Writer, ExtensionErrorCode, Result, SdkError};
+
+/// The version of the envelope layout produced by `GuestError::encode`. Bump this whenever the
+/// layout changes so the host can tell envelopes apart.
+const ENVELOPE_VERSION: u8 = 1;
+
+/// An error raised by guest code, in a form we can hand to the host so the scheduler can show a
+/// real error message instead of "wasm trap".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuestError {
+    /// An application-defined error code. Codes coming from SDK failures reuse the
+    /// `ExtensionErrorCode` table.
+    pub code: i32,
+    pub message: String,
+    pub backtrace: Option<String>,
+}
+
+impl GuestError {
+    /// The code used for errors that don't carry one of their own, such as plain strings.
+    pub const UNSPECIFIED: i32 = 0;
+
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            backtrace: None,
+        }
+    }
+
+    /// Attaches a backtrace to the error.
+    pub fn with_backtrace(mut self, backtrace: impl Into<String>) -> Self {
+        self.backtrace = Some(backtrace.into());
+        self
+    }
+
+    /// Captures a backtrace at the call site (subject to `RUST_BACKTRACE`, and to the target
+    /// supporting backtraces at all) and attaches it to the error.
+    pub fn capture_backtrace(self) -> Self {
+        let backtrace = std::backtrace::Backtrace::capture();
+        match backtrace.status() {
+            std::backtrace::BacktraceStatus::Captured => self.with_backtrace(backtrace.to_string()),
+            _ => self,
+        }
+    }
+
+    /// Serializes the error into the envelope the host expects: a version byte, an i32 code, a
+    /// length-prefixed message and a length-prefixed backtrace (empty if there is none).
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u8(ENVELOPE_VERSION);
+        writer.write_i32(self.code);
+        writer.write_str(&self.message);
+        writer.write_str(self.backtrace.as_deref().unwrap_or_default());
+        writer.into_bytes()
+    }
+
+    /// Parses an envelope produced by `encode`.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_u8()? != ENVELOPE_VERSION {
+            return Err(SdkError::InvalidPayload);
+        }
+        let code = reader.read_i32()?;
+        let message = reader.read_str()?.to_string();
+        let backtrace = reader.read_str()?;
+        Ok(Self {
+            code,
+            message,
+            backtrace: (!backtrace.is_empty()).then(|| backtrace.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for GuestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for GuestError {}
+
+impl From<SdkError> for GuestError {
+    fn from(err: SdkError) -> Self {
+        let code = err
+            .code()
+            .unwrap_or(ExtensionErrorCode::InvalidPayload)
+            .as_raw();
+        GuestError::new(code, err.to_string())
+    }
+}
+
+impl From<String> for GuestError {
+    fn from(message: String) -> Self {
+        GuestError::new(GuestError::UNSPECIFIED, message)
+    }
+}
+
+impl From<&str> for GuestError {
+    fn from(message: &str) -> Self {
+        GuestError::new(GuestError::UNSPECIFIED, message)
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for GuestError {
+    fn from(err: Box<dyn std::error::Error>) -> Self {
+        GuestError::new(GuestError::UNSPECIFIED, err.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for GuestError {
+    fn from(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        GuestError::new(GuestError::UNSPECIFIED, err.to_string())
+    }
+}
+
+/// Reports a guest-side error to the host. Call this right before bailing out of an entrypoint so
+/// the failure shows up with a useful message.
+pub fn report_error(err: &GuestError) -> Result<()> {
+    let envelope = err.encode();
+    let status = unsafe { host::report_error(envelope.as_ptr() as usize, envelope.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(())
+}
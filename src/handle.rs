@@ -0,0 +1,123 @@
+//! Invocations that run on the host while the guest keeps working.
+//!
+//! `start_invoke` hands the call to the host and returns straight away with a handle; the host
+//! copies the payload, so the caller's buffer can be reused immediately. The result is collected
+//! later with `InvocationHandle::poll` or `InvocationHandle::wait`. For fan-out, `wait_all`
+//! collects the results of several handles and `wait_any` blocks until the first of them has
+//! finished. Handles also convert into futures; see `invoke_extension_async`.
+
+use std::task::Poll;
+
+use crate::{check_status, host, read_response, ExtensionErrorCode, Result, SdkError};
+
+/// An extension call that's in flight on the host. Dropping a handle before its result has been
+/// collected cancels the call.
+#[derive(Debug)]
+pub struct InvocationHandle {
+    id: u32,
+    extension: String,
+    payload_len: usize,
+    finished: bool,
+}
+
+/// Starts invoking the named extension and returns without waiting for it to respond.
+pub fn start_invoke(extension_name: &str, data: &[u8]) -> Result<InvocationHandle> {
+    let id = unsafe {
+        host::start_invoke(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            data.as_ptr() as usize,
+            data.len() as u32,
+        )
+    };
+
+    check_status(id, extension_name, data.len())?;
+    Ok(InvocationHandle {
+        id: id as u32,
+        extension: extension_name.to_string(),
+        payload_len: data.len(),
+        finished: false,
+    })
+}
+
+impl InvocationHandle {
+    /// The host's identifier for the call.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The extension this call was made to.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Checks whether the call has finished without blocking, returning its result if so.
+    ///
+    /// # Panics
+    /// Panics if called again after it has returned `Poll::Ready`.
+    pub fn poll(&mut self) -> Poll<Result<Vec<u8>>> {
+        assert!(!self.finished, "InvocationHandle polled after completion");
+        let out_ptr = unsafe { host::poll_invoke(self.id) };
+        if out_ptr == 0 {
+            return Poll::Pending;
+        }
+
+        self.finished = true;
+        Poll::Ready(read_response(out_ptr, &self.extension, self.payload_len))
+    }
+
+    /// Blocks until the call has finished and returns its result.
+    ///
+    /// # Panics
+    /// Panics if `poll` has already returned `Poll::Ready`.
+    pub fn wait(mut self) -> Result<Vec<u8>> {
+        assert!(
+            !self.finished,
+            "InvocationHandle waited on after completion"
+        );
+        let out_ptr = unsafe { host::wait_invoke(self.id) };
+        self.finished = true;
+        read_response(out_ptr, &self.extension, self.payload_len)
+    }
+}
+
+impl Drop for InvocationHandle {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Nobody is going to collect the result, so there's nothing useful to do if the host
+            // can't cancel the call.
+            unsafe { host::cancel_invoke(self.id) };
+        }
+    }
+}
+
+/// Blocks until at least one of `handles` has finished and returns its index. The result is then
+/// available without blocking from that handle's `wait` (or `poll`), e.g.
+/// `handles.swap_remove(index).wait()`.
+///
+/// # Panics
+/// Panics if `handles` is empty or any of them has already finished.
+pub fn wait_any(handles: &[InvocationHandle]) -> Result<usize> {
+    assert!(!handles.is_empty(), "wait_any called without any handles");
+    assert!(
+        handles.iter().all(|handle| !handle.finished),
+        "wait_any called with a finished InvocationHandle"
+    );
+    let ids: Vec<u32> = handles.iter().map(|handle| handle.id).collect();
+    let index = unsafe { host::wait_any(ids.as_ptr() as usize, ids.len() as u32) };
+
+    if index < 0 {
+        return Err(ExtensionErrorCode::from(index).into());
+    }
+    let index = index as usize;
+    if index >= ids.len() {
+        return Err(SdkError::InvalidPayload);
+    }
+    Ok(index)
+}
+
+/// Waits for every one of `handles` to finish and returns their results in the same order. The
+/// calls all run concurrently on the host, so this takes as long as the slowest one.
+pub fn wait_all(handles: impl IntoIterator<Item = InvocationHandle>) -> Vec<Result<Vec<u8>>> {
+    handles.into_iter().map(InvocationHandle::wait).collect()
+}
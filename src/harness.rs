@@ -0,0 +1,869 @@
+//! Runs a compiled guest under wasmtime with stand-in serval imports, for end-to-end tests of the
+//! actual `.wasm` on the developer's machine. This is host-side code: enable the `harness` feature
+//! in the test crate's dev-dependencies, never in the guest itself. Only wasm32 guests are
+//! supported.
+//!
+//! ```ignore
+//! let mut guest = serval::harness::Harness::new()
+//!     .extension("greeter", |name| Ok([b"hello ", name].concat()))
+//!     .env("REGION", "eu-west-1")
+//!     .load("target/wasm32-unknown-unknown/release/my_guest.wasm")?;
+//!
+//! let outcome = guest.run_main(b"ferris")?;
+//! assert_eq!(outcome.output.as_deref(), Some(&b"hello ferris"[..]));
+//! assert_eq!(guest.calls()[0].extension, "greeter");
+//! ```
+//!
+//! Like `mock`, extensions are closures registered with `extension`; anything else fails with
+//! `ExtensionErrorCode::NotFound`, and handlers behind `frame::invoke_framed` see and answer with
+//! encoded frames. The harness serves plain, framed and one-way invocations, job data,
+//! configuration, secrets, clocks, randomness, logs, progress and error reports. Imports it doesn't
+//! implement (streams, channels, async calls, batches, pipelines, sockets and the filesystem) trap
+//! when called, so a guest relying on them fails loudly rather than seeing made-up results.
+//!
+//! With `exchange_memory`, the harness offers to pass buffers through exchange memory the way a
+//! host supporting it would (see `serval::exchange`), and falls back to writing into the guest's
+//! memory if the guest doesn't take it up.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use wasmtime::{Caller, Engine, Extern, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+use crate::abi::{ABI_VERSION, EXCHANGE_VERSION, MIN_ABI_VERSION};
+use crate::caps::{self, Capability};
+use crate::entrypoint;
+use crate::extensions::Description;
+use crate::framing::{write_frame, PREFIX_LEN};
+use crate::inline;
+use crate::job::{JobMetadata, JobStatus};
+use crate::log::Level;
+use crate::wire::Writer;
+use crate::{ExtensionErrorCode, GuestError};
+
+/// What an extension answers a call with: a response body or the error code the host would report.
+pub type Reply = std::result::Result<Vec<u8>, ExtensionErrorCode>;
+
+type Handler = Box<dyn FnMut(&[u8]) -> Reply>;
+
+/// `ENTRYPOINT_FAILED` as a wasm32 guest returns it.
+const ENTRYPOINT_FAILED: i32 = entrypoint::ENTRYPOINT_FAILED as i32;
+
+#[derive(Debug)]
+pub enum HarnessError {
+    /// Loading, linking or running the module failed, including the guest trapping.
+    Wasmtime(wasmtime::Error),
+    /// The module doesn't export the named item, or exports it with the wrong type.
+    MissingExport(String),
+    /// The entrypoint returned `ENTRYPOINT_FAILED`, with the error it reported if it did.
+    Entrypoint(Option<GuestError>),
+    /// The guest's `serval_abi_version` is one the harness doesn't speak.
+    IncompatibleAbi(u32),
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HarnessError::Wasmtime(err) => write!(f, "{err:#}"),
+            HarnessError::MissingExport(name) => write!(f, "the guest doesn't export `{name}`"),
+            HarnessError::Entrypoint(Some(err)) => write!(f, "the entrypoint failed: {err}"),
+            HarnessError::Entrypoint(None) => write!(f, "the entrypoint failed"),
+            HarnessError::IncompatibleAbi(version) => write!(
+                f,
+                "the guest speaks ABI version {version}, but the harness only speaks versions \
+                 {MIN_ABI_VERSION} to {ABI_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HarnessError {}
+
+impl From<wasmtime::Error> for HarnessError {
+    fn from(err: wasmtime::Error) -> Self {
+        HarnessError::Wasmtime(err)
+    }
+}
+
+impl From<wasmtime::MemoryAccessError> for HarnessError {
+    fn from(err: wasmtime::MemoryAccessError) -> Self {
+        HarnessError::Wasmtime(err.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, HarnessError>;
+
+/// An invocation the guest made, as recorded by the harness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    pub extension: String,
+    /// The bytes the guest sent: a bare payload, or an encoded frame for framed invocations.
+    pub payload: Vec<u8>,
+    pub reply: Reply,
+}
+
+/// How a `#[serval::main]` entrypoint finished.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobOutcome {
+    /// The output the job published, if it did.
+    pub output: Option<Vec<u8>>,
+    /// The status and message the job completed with, if it did.
+    pub completion: Option<(JobStatus, String)>,
+    /// The error the guest reported while failing, if it failed.
+    pub error: Option<GuestError>,
+}
+
+/// Sets up the host a guest runs against; `load` then instantiates the guest.
+pub struct Harness {
+    state: State,
+    exchange_memory: bool,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self {
+            state: State {
+                handlers: HashMap::new(),
+                versions: HashMap::new(),
+                descriptions: HashMap::new(),
+                env: BTreeMap::new(),
+                secrets: HashMap::new(),
+                job_params: BTreeMap::new(),
+                job_body: Vec::new(),
+                job_metadata: JobMetadata {
+                    job_id: "harness-job".to_string(),
+                    run_id: "harness-run".to_string(),
+                    node_id: "harness-node".to_string(),
+                    tenant: "harness-tenant".to_string(),
+                    submitted_at: UNIX_EPOCH,
+                    labels: BTreeMap::new(),
+                },
+                job_trace_id: None,
+                cancelled: false,
+                capabilities: caps::everything(),
+                calls: Vec::new(),
+                job_output: None,
+                job_completion: None,
+                progress: Vec::new(),
+                logs: Vec::new(),
+                errors: Vec::new(),
+                started: Instant::now(),
+                rng: std::collections::hash_map::RandomState::new().hash_one(0u8) | 1,
+                exchange: None,
+            },
+            exchange_memory: false,
+        }
+    }
+
+    /// Registers `handler` as the extension `name`, replacing any handler registered before.
+    pub fn extension(mut self, name: &str, handler: impl FnMut(&[u8]) -> Reply + 'static) -> Self {
+        self.state
+            .handlers
+            .insert(name.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Sets the version `extensions::list` reports for the extension `name`. Extensions without one
+    /// are listed with an empty version.
+    pub fn extension_version(mut self, name: &str, version: &str) -> Self {
+        self.state
+            .versions
+            .insert(name.to_string(), version.to_string());
+        self
+    }
+
+    /// Sets what `extensions::describe` reports for the extension `description.name`, along with
+    /// the version `extensions::list` reports for it. It only takes effect while the extension is
+    /// registered; registered extensions without a description are described as declaring no
+    /// operations or codecs.
+    pub fn extension_description(mut self, description: Description) -> Self {
+        self.state
+            .versions
+            .insert(description.name.clone(), description.version.clone());
+        self.state
+            .descriptions
+            .insert(description.name.clone(), description);
+        self
+    }
+
+    /// Sets a configuration variable for `env::get` and `env::vars`.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.state.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets a secret for `secrets::get`.
+    pub fn secret(mut self, name: &str, value: impl Into<Vec<u8>>) -> Self {
+        self.state.secrets.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Adds a parameter to what `job::input` returns. Its body is the input given to `run_main`.
+    pub fn job_param(mut self, name: &str, value: &str) -> Self {
+        self.state
+            .job_params
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets what `job::metadata` returns.
+    pub fn job_metadata(mut self, metadata: JobMetadata) -> Self {
+        self.state.job_metadata = metadata;
+        self
+    }
+
+    /// Sets the trace ID the host assigned the job, which `current_trace_id` falls back to.
+    pub fn job_trace_id(mut self, id: &str) -> Self {
+        self.state.job_trace_id = Some(id.to_string());
+        self
+    }
+
+    /// Makes the host report the job as cancelled from the start.
+    pub fn cancelled(mut self, cancelled: bool) -> Self {
+        self.state.cancelled = cancelled;
+        self
+    }
+
+    /// Restricts the job to `capabilities`, which `caps` reports and checks against. By default
+    /// it's granted everything.
+    pub fn capabilities(mut self, capabilities: impl IntoIterator<Item = Capability>) -> Self {
+        self.state.capabilities = capabilities.into_iter().collect();
+        self
+    }
+
+    /// Compiles and instantiates the guest at `path`, a `.wasm` or `.wat` file.
+    pub fn load(self, path: impl AsRef<Path>) -> Result<Guest> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path)?;
+        self.instantiate(&engine, &module)
+    }
+
+    /// Offers the guest exchange memory when it's loaded. Guests built without the
+    /// `exchange-memory` feature turn it down, and get buffers written into their memory as usual.
+    pub fn exchange_memory(mut self, enabled: bool) -> Self {
+        self.exchange_memory = enabled;
+        self
+    }
+
+    /// Like `load`, for a module that's already in memory.
+    pub fn load_bytes(self, wasm: impl AsRef<[u8]>) -> Result<Guest> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm)?;
+        self.instantiate(&engine, &module)
+    }
+
+    fn instantiate(self, engine: &Engine, module: &Module) -> Result<Guest> {
+        let mut linker = Linker::new(engine);
+        define_imports(&mut linker)?;
+        linker.define_unknown_imports_as_traps(module)?;
+
+        let mut store = Store::new(engine, self.state);
+        let instance = linker.instantiate(&mut store, module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| HarnessError::MissingExport("memory".to_string()))?;
+        // Guests built before the ABI was versioned don't export their version.
+        if let Ok(abi_version) =
+            typed_export::<(), u32>(&instance, &mut store, "serval_abi_version")
+        {
+            let version = abi_version.call(&mut store, ())?;
+            if !(MIN_ABI_VERSION..=ABI_VERSION).contains(&version) {
+                return Err(HarnessError::IncompatibleAbi(version));
+            }
+        }
+        if self.exchange_memory {
+            if let Ok(enable) =
+                typed_export::<u32, u32>(&instance, &mut store, "serval_exchange_enable")
+            {
+                if enable.call(&mut store, EXCHANGE_VERSION)? == 1 {
+                    store.data_mut().exchange = Some(Exchange::default());
+                }
+            }
+        }
+        let alloc = typed_export(&instance, &mut store, "alloc")?;
+        let dealloc = typed_export(&instance, &mut store, "dealloc")?;
+        Ok(Guest {
+            store,
+            instance,
+            memory,
+            alloc,
+            dealloc,
+        })
+    }
+}
+
+fn typed_export<P: wasmtime::WasmParams, R: wasmtime::WasmResults>(
+    instance: &Instance,
+    store: &mut Store<State>,
+    name: &str,
+) -> Result<TypedFunc<P, R>> {
+    instance
+        .get_typed_func(store, name)
+        .map_err(|_| HarnessError::MissingExport(name.to_string()))
+}
+
+/// A guest instantiated by `Harness::load`. Its memory and the host's records persist across
+/// calls, the same way they would for a long-lived instance on a real host.
+pub struct Guest {
+    store: Store<State>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+    dealloc: TypedFunc<(u32, u32), ()>,
+}
+
+impl Guest {
+    /// Runs the `#[serval::main]` entrypoint with `input` as the job's body.
+    pub fn run_main(&mut self, input: &[u8]) -> Result<JobOutcome> {
+        let state = self.store.data_mut();
+        state.job_body = input.to_vec();
+        state.job_output = None;
+        state.job_completion = None;
+        let errors_before = state.errors.len();
+
+        // The main entrypoint hands its output over through the job rather than returning it, so
+        // the status only says whether it failed, which the completion already tells.
+        self.call_export("serval_main", input)?;
+        let state = self.store.data_mut();
+        let error = state
+            .errors
+            .get(errors_before..)
+            .and_then(|new| new.last().cloned());
+        Ok(JobOutcome {
+            output: state.job_output.take(),
+            completion: state.job_completion.take(),
+            error,
+        })
+    }
+
+    /// Calls the `#[serval::export]` function `name` with `input` and returns its output.
+    pub fn call(&mut self, name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let errors_before = self.store.data().errors.len();
+        match self.call_export(name, input)? {
+            0 => Ok(Vec::new()),
+            ENTRYPOINT_FAILED => {
+                let error = self.store.data().errors.get(errors_before..);
+                let error = error.and_then(|new| new.last().cloned());
+                Err(HarnessError::Entrypoint(error))
+            }
+            ptr => self.take_output(ptr as u32),
+        }
+    }
+
+    /// Every invocation the guest has made so far, in order.
+    pub fn calls(&self) -> &[Call] {
+        &self.store.data().calls
+    }
+
+    /// The progress updates the job has reported, in order.
+    pub fn progress(&self) -> &[(f32, String)] {
+        &self.store.data().progress
+    }
+
+    /// The messages the guest has logged, in order.
+    pub fn logs(&self) -> &[(Level, String)] {
+        &self.store.data().logs
+    }
+
+    /// The errors the guest has reported with `report_error`, in order.
+    pub fn errors(&self) -> &[GuestError] {
+        &self.store.data().errors
+    }
+
+    /// Makes the host report the job as cancelled, or not, from the next check on.
+    pub fn set_cancelled(&mut self, cancelled: bool) {
+        self.store.data_mut().cancelled = cancelled;
+    }
+
+    /// The size of the guest's linear memory in bytes.
+    pub fn memory_size(&self) -> usize {
+        self.memory.data_size(&self.store)
+    }
+
+    /// Whether the guest took up exchange memory; see `Harness::exchange_memory`.
+    pub fn uses_exchange_memory(&self) -> bool {
+        self.store.data().exchange.is_some()
+    }
+
+    /// How many buffers in exchange memory the guest hasn't freed yet.
+    pub fn live_exchange_buffers(&self) -> usize {
+        self.store
+            .data()
+            .exchange
+            .as_ref()
+            .map_or(0, |exchange| exchange.live.len())
+    }
+
+    /// Calls an entrypoint-style export with `input` copied into a buffer from the guest's `alloc`.
+    fn call_export(&mut self, name: &str, input: &[u8]) -> Result<i32> {
+        let entrypoint: TypedFunc<u32, i32> = typed_export(&self.instance, &mut self.store, name)?;
+        if let Some(exchange) = &mut self.store.data_mut().exchange {
+            let offset = exchange.place(input);
+            return Ok(entrypoint.call(&mut self.store, offset)?);
+        }
+        let len = (PREFIX_LEN + input.len()) as u32;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        if ptr == 0 {
+            return Err(wasmtime::Error::msg("the guest failed to allocate the input").into());
+        }
+        let mut framed = Vec::with_capacity(len as usize);
+        write_frame(&mut framed, input);
+        self.memory.write(&mut self.store, ptr as usize, &framed)?;
+        Ok(entrypoint.call(&mut self.store, ptr)?)
+    }
+
+    /// Copies out a length-prefixed buffer the guest returned and frees it with its `dealloc`, the
+    /// way the real host does.
+    fn take_output(&mut self, ptr: u32) -> Result<Vec<u8>> {
+        let mut prefix = [0; PREFIX_LEN];
+        self.memory.read(&self.store, ptr as usize, &mut prefix)?;
+        let len = u32::from_le_bytes(prefix);
+        let mut output = vec![0; len as usize];
+        self.memory
+            .read(&self.store, ptr as usize + PREFIX_LEN, &mut output)?;
+        self.dealloc
+            .call(&mut self.store, (ptr, PREFIX_LEN as u32 + len))?;
+        Ok(output)
+    }
+}
+
+struct State {
+    handlers: HashMap<String, Handler>,
+    versions: HashMap<String, String>,
+    descriptions: HashMap<String, Description>,
+    env: BTreeMap<String, String>,
+    secrets: HashMap<String, Vec<u8>>,
+    job_params: BTreeMap<String, String>,
+    job_body: Vec<u8>,
+    job_metadata: JobMetadata,
+    job_trace_id: Option<String>,
+    cancelled: bool,
+    capabilities: Vec<Capability>,
+    calls: Vec<Call>,
+    job_output: Option<Vec<u8>>,
+    job_completion: Option<(JobStatus, String)>,
+    progress: Vec<(f32, String)>,
+    logs: Vec<(Level, String)>,
+    errors: Vec<GuestError>,
+    started: Instant,
+    rng: u64,
+    exchange: Option<Exchange>,
+}
+
+/// The harness's exchange memory: a separate arena the buffers handed to the guest are placed in,
+/// bump-allocated and never reused, so that lookups of stale offsets fail rather than hit newer
+/// buffers.
+#[derive(Default)]
+struct Exchange {
+    memory: Vec<u8>,
+    /// The offset and size of every buffer the guest hasn't freed yet.
+    live: BTreeMap<u32, u32>,
+}
+
+impl Exchange {
+    /// Places `bytes` in a length-prefixed buffer and returns its offset.
+    fn place(&mut self, bytes: &[u8]) -> u32 {
+        // Offset 0 means no buffer, as a null pointer does.
+        if self.memory.is_empty() {
+            self.memory.resize(8, 0);
+        }
+        let offset = self.memory.len() as u32;
+        write_frame(&mut self.memory, bytes);
+        self.live.insert(offset, (PREFIX_LEN + bytes.len()) as u32);
+        offset
+    }
+
+    /// The `len` bytes at `offset`, if they're all within one live buffer.
+    fn get(&self, offset: u32, len: u32) -> Option<&[u8]> {
+        let (&start, &size) = self.live.range(..=offset).next_back()?;
+        let end = offset.checked_add(len)?;
+        (end <= start + size).then(|| &self.memory[offset as usize..end as usize])
+    }
+}
+
+impl State {
+    fn invoke(&mut self, extension: &str, payload: &[u8]) -> Reply {
+        let reply = match self.handlers.get_mut(extension) {
+            Some(handler) => handler(payload),
+            None => Err(ExtensionErrorCode::NotFound),
+        };
+        self.calls.push(Call {
+            extension: extension.to_string(),
+            payload: payload.to_vec(),
+            reply: reply.clone(),
+        });
+        reply
+    }
+}
+
+fn memory(caller: &mut Caller<'_, State>) -> wasmtime::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => Err(wasmtime::Error::msg("the guest doesn't export its memory")),
+    }
+}
+
+/// Copies `len` bytes of guest memory at `ptr` out.
+fn read(caller: &mut Caller<'_, State>, ptr: u32, len: u32) -> wasmtime::Result<Vec<u8>> {
+    let mut bytes = vec![0; len as usize];
+    memory(caller)?.read(&*caller, ptr as usize, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(caller: &mut Caller<'_, State>, ptr: u32, len: u32) -> wasmtime::Result<String> {
+    Ok(String::from_utf8_lossy(&read(caller, ptr, len)?).into_owned())
+}
+
+/// Hands `bytes` to the guest in a length-prefixed buffer from its `alloc`, returning the pointer
+/// or `ExtensionErrorCode::AllocationFailed`. Once the guest has taken up exchange memory, the
+/// buffer goes there instead.
+fn to_guest(caller: &mut Caller<'_, State>, bytes: &[u8]) -> wasmtime::Result<i32> {
+    if let Some(exchange) = &mut caller.data_mut().exchange {
+        return Ok(exchange.place(bytes) as i32);
+    }
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| wasmtime::Error::msg("the guest doesn't export `alloc`"))?
+        .typed::<u32, u32>(&*caller)?;
+    let mut framed = Vec::with_capacity(PREFIX_LEN + bytes.len());
+    write_frame(&mut framed, bytes);
+    let ptr = alloc.call(&mut *caller, framed.len() as u32)?;
+    if ptr == 0 {
+        return Ok(ExtensionErrorCode::AllocationFailed.as_raw());
+    }
+    memory(caller)?.write(&mut *caller, ptr as usize, &framed)?;
+    Ok(ptr as i32)
+}
+
+fn reply_to_guest(caller: &mut Caller<'_, State>, reply: Reply) -> wasmtime::Result<i32> {
+    match reply {
+        Ok(bytes) => to_guest(caller, &bytes),
+        Err(code) => Ok(code.as_raw()),
+    }
+}
+
+fn invoke(
+    caller: &mut Caller<'_, State>,
+    name_ptr: u32,
+    name_len: u32,
+    data_ptr: u32,
+    data_len: u32,
+) -> wasmtime::Result<Reply> {
+    let name = read_string(caller, name_ptr, name_len)?;
+    let payload = read(caller, data_ptr, data_len)?;
+    Ok(caller.data_mut().invoke(&name, &payload))
+}
+
+fn define_imports(linker: &mut Linker<State>) -> wasmtime::Result<()> {
+    const MODULE: &str = "serval";
+
+    linker.func_wrap(
+        MODULE,
+        "invoke_raw",
+        |mut caller: Caller<'_, State>, name_ptr, name_len, data_ptr, data_len| {
+            let reply = invoke(&mut caller, name_ptr, name_len, data_ptr, data_len)?;
+            reply_to_guest(&mut caller, reply)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "invoke_raw_inline",
+        |mut caller: Caller<'_, State>, name_ptr, name_len, data_ptr, data_len| {
+            let reply = invoke(&mut caller, name_ptr, name_len, data_ptr, data_len)?;
+            match reply.as_deref().ok().and_then(inline::pack) {
+                Some(value) => Ok(value),
+                None => reply_to_guest(&mut caller, reply).map(i64::from),
+            }
+        },
+    )?;
+    // Handlers answer straight away, so there's no deadline to enforce.
+    linker.func_wrap(
+        MODULE,
+        "invoke_raw_with_timeout",
+        |mut caller: Caller<'_, State>, name_ptr, name_len, data_ptr, data_len, _: u32| {
+            let reply = invoke(&mut caller, name_ptr, name_len, data_ptr, data_len)?;
+            reply_to_guest(&mut caller, reply)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "invoke_raw_oneway",
+        |mut caller: Caller<'_, State>, name_ptr, name_len, data_ptr, data_len| {
+            let reply = invoke(&mut caller, name_ptr, name_len, data_ptr, data_len)?;
+            Ok(reply.map_or_else(|code| code.as_raw(), |_| 0))
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "invoke_framed",
+        |mut caller: Caller<'_, State>, name_ptr, name_len, frame_ptr, frame_len| {
+            let reply = invoke(&mut caller, name_ptr, name_len, frame_ptr, frame_len)?;
+            reply_to_guest(&mut caller, reply)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "list_extensions",
+        |mut caller: Caller<'_, State>| {
+            let state = caller.data();
+            let mut names: Vec<&String> = state.handlers.keys().collect();
+            names.sort();
+            let mut writer = Writer::new();
+            writer.write_u32(names.len() as u32);
+            for name in names {
+                writer.write_str(name);
+                writer.write_str(state.versions.get(name).map_or("", String::as_str));
+            }
+            to_guest(&mut caller, &writer.into_bytes())
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "describe_extension",
+        |mut caller: Caller<'_, State>, name_ptr, name_len| {
+            let name = read_string(&mut caller, name_ptr, name_len)?;
+            let state = caller.data();
+            let registered = state.handlers.contains_key(&name);
+            let description = registered.then(|| {
+                state
+                    .descriptions
+                    .get(&name)
+                    .cloned()
+                    .unwrap_or_else(|| Description {
+                        name: name.clone(),
+                        version: state.versions.get(&name).cloned().unwrap_or_default(),
+                        ..Description::default()
+                    })
+            });
+            match description {
+                Some(description) => to_guest(&mut caller, &description.encode()),
+                None => Ok(0),
+            }
+        },
+    )?;
+    linker.func_wrap(MODULE, "negotiate_segment_size", |preferred: u32| {
+        preferred as i32
+    })?;
+    linker.func_wrap(MODULE, "abi_negotiate", |min: u32, max: u32| {
+        let version = max.min(ABI_VERSION);
+        if version >= min.max(MIN_ABI_VERSION) {
+            version as i32
+        } else {
+            ExtensionErrorCode::NoMatchingVersion.as_raw()
+        }
+    })?;
+    linker.func_wrap(MODULE, "caps_granted", |mut caller: Caller<'_, State>| {
+        let granted = caps::encode(&caller.data().capabilities);
+        to_guest(&mut caller, &granted)
+    })?;
+    linker.func_wrap(
+        MODULE,
+        "caps_can_invoke",
+        |mut caller: Caller<'_, State>, name_ptr, name_len| {
+            let name = read_string(&mut caller, name_ptr, name_len)?;
+            Ok(caps::permits(&caller.data().capabilities, &name) as i32)
+        },
+    )?;
+    linker.func_wrap(MODULE, "get_last_error", || 0i32)?;
+    linker.func_wrap(MODULE, "report_memory_stats", |_: u32, _: u32| 0i32)?;
+    linker.func_wrap(MODULE, "metrics_flush", |_: u32, _: u32| 0i32)?;
+    linker.func_wrap(MODULE, "trace_record", |_: u32, _: u32| 0i32)?;
+
+    linker.func_wrap(MODULE, "job_metadata", |mut caller: Caller<'_, State>| {
+        let metadata = caller.data().job_metadata.encode();
+        to_guest(&mut caller, &metadata)
+    })?;
+    linker.func_wrap(
+        MODULE,
+        "job_trace_id",
+        |mut caller: Caller<'_, State>| match caller.data().job_trace_id.clone() {
+            Some(id) => to_guest(&mut caller, id.as_bytes()),
+            None => Ok(0),
+        },
+    )?;
+    linker.func_wrap(MODULE, "job_input", |mut caller: Caller<'_, State>| {
+        let state = caller.data();
+        let mut writer = Writer::new();
+        writer.write_u32(state.job_params.len() as u32);
+        for (name, value) in &state.job_params {
+            writer.write_str(name);
+            writer.write_str(value);
+        }
+        writer.write_bytes(&state.job_body);
+        to_guest(&mut caller, &writer.into_bytes())
+    })?;
+    linker.func_wrap(
+        MODULE,
+        "job_set_output",
+        |mut caller: Caller<'_, State>, ptr, len| {
+            let output = read(&mut caller, ptr, len)?;
+            caller.data_mut().job_output = Some(output);
+            Ok(0i32)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "job_complete",
+        |mut caller: Caller<'_, State>, status: u32, message_ptr, message_len| {
+            let status = match status {
+                0 => JobStatus::Succeeded,
+                1 => JobStatus::Failed,
+                _ => return Ok(ExtensionErrorCode::InvalidPayload.as_raw()),
+            };
+            let message = read_string(&mut caller, message_ptr, message_len)?;
+            caller.data_mut().job_completion = Some((status, message));
+            Ok(0)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "job_progress",
+        |mut caller: Caller<'_, State>, percent: f32, message_ptr, message_len| {
+            let message = read_string(&mut caller, message_ptr, message_len)?;
+            caller.data_mut().progress.push((percent, message));
+            Ok(0i32)
+        },
+    )?;
+    linker.func_wrap(MODULE, "job_cancelled", |caller: Caller<'_, State>| {
+        caller.data().cancelled as i32
+    })?;
+    linker.func_wrap(MODULE, "job_heartbeat", || 0i32)?;
+
+    linker.func_wrap(
+        MODULE,
+        "secret_get",
+        |mut caller: Caller<'_, State>, name_ptr, name_len| {
+            let name = read_string(&mut caller, name_ptr, name_len)?;
+            match caller.data().secrets.get(&name).cloned() {
+                Some(value) => to_guest(&mut caller, &value),
+                None => Ok(0),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "env_get",
+        |mut caller: Caller<'_, State>, key_ptr, key_len| {
+            let key = read_string(&mut caller, key_ptr, key_len)?;
+            match caller.data().env.get(&key).cloned() {
+                Some(value) => to_guest(&mut caller, value.as_bytes()),
+                None => Ok(0),
+            }
+        },
+    )?;
+    linker.func_wrap(MODULE, "env_vars", |mut caller: Caller<'_, State>| {
+        let env = &caller.data().env;
+        let mut writer = Writer::new();
+        writer.write_u32(env.len() as u32);
+        for (key, value) in env {
+            writer.write_str(key);
+            writer.write_str(value);
+        }
+        to_guest(&mut caller, &writer.into_bytes())
+    })?;
+
+    linker.func_wrap(MODULE, "wall_clock_now", || {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        u64::try_from(now.as_nanos()).unwrap_or(u64::MAX)
+    })?;
+    linker.func_wrap(MODULE, "monotonic_now", |caller: Caller<'_, State>| {
+        u64::try_from(caller.data().started.elapsed().as_nanos()).unwrap_or(u64::MAX)
+    })?;
+    linker.func_wrap(MODULE, "sleep", |nanos: u64| {
+        std::thread::sleep(Duration::from_nanos(nanos))
+    })?;
+    linker.func_wrap(MODULE, "yield_now", std::thread::yield_now)?;
+    linker.func_wrap(
+        MODULE,
+        "random_fill",
+        |mut caller: Caller<'_, State>, ptr: u32, len: u32| {
+            let mut buf = vec![0; len as usize];
+            let state = caller.data_mut();
+            for chunk in buf.chunks_mut(8) {
+                let mut x = state.rng;
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                state.rng = x;
+                chunk.copy_from_slice(&x.to_le_bytes()[..chunk.len()]);
+            }
+            memory(&mut caller)?.write(&mut caller, ptr as usize, &buf)?;
+            Ok(0i32)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "log_raw",
+        |mut caller: Caller<'_, State>, level: u32, ptr, len| {
+            let level = match level {
+                0 => Level::Trace,
+                1 => Level::Debug,
+                2 => Level::Info,
+                3 => Level::Warn,
+                _ => Level::Error,
+            };
+            let message = read_string(&mut caller, ptr, len)?;
+            caller.data_mut().logs.push((level, message));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "exchange_read",
+        |mut caller: Caller<'_, State>, offset: u32, dst_ptr: u32, len: u32| {
+            let bytes = caller
+                .data()
+                .exchange
+                .as_ref()
+                .and_then(|exchange| exchange.get(offset, len))
+                .map(<[u8]>::to_vec);
+            let Some(bytes) = bytes else {
+                return Ok(ExtensionErrorCode::InvalidPayload.as_raw());
+            };
+            memory(&mut caller)?.write(&mut caller, dst_ptr as usize, &bytes)?;
+            Ok(0)
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "exchange_free",
+        |mut caller: Caller<'_, State>, offset: u32| {
+            let freed = caller
+                .data_mut()
+                .exchange
+                .as_mut()
+                .and_then(|exchange| exchange.live.remove(&offset));
+            Ok(match freed {
+                Some(_) => 0,
+                None => ExtensionErrorCode::InvalidPayload.as_raw(),
+            })
+        },
+    )?;
+    linker.func_wrap(
+        MODULE,
+        "report_error",
+        |mut caller: Caller<'_, State>, ptr, len| {
+            let envelope = read(&mut caller, ptr, len)?;
+            match GuestError::decode(&envelope) {
+                Ok(err) => {
+                    caller.data_mut().errors.push(err);
+                    Ok(0)
+                }
+                Err(_) => Ok(ExtensionErrorCode::InvalidPayload.as_raw()),
+            }
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! An opt-in record of the guest's most recent invocations, for working out after the fact why a
+//! job failed on a particular node.
+//!
+//! ```ignore
+//! serval::history::enable(32);
+//! ```
+//!
+//! Once enabled, every call made through `invoke_extension` and its siblings (`_owned`,
+//! `_with_timeout`, and `frame::invoke_framed`, which the service clients use) is recorded with its
+//! payload and response sizes, outcome and duration, keeping only the last `capacity`. When an
+//! entrypoint fails, the record is written to the host's log before the error is reported; `dump`
+//! does the same on demand.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::log::Level;
+use crate::time::Instant;
+use crate::Result;
+
+/// A recorded invocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub extension: String,
+    /// The size of the payload the guest sent, or of the whole frame for framed invocations.
+    pub request_len: usize,
+    /// The size of the response on success, or the error the call failed with.
+    pub outcome: std::result::Result<usize, String>,
+    /// How long the call took, as measured by the guest.
+    pub duration: Duration,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Ok(response_len) => write!(
+                f,
+                "{}: {} bytes in, {response_len} bytes out, {:?}",
+                self.extension, self.request_len, self.duration
+            ),
+            Err(err) => write!(
+                f,
+                "{}: {} bytes in, failed after {:?}: {err}",
+                self.extension, self.request_len, self.duration
+            ),
+        }
+    }
+}
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+static ENTRIES: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::new());
+
+/// Starts recording the last `capacity` invocations, or stops recording if it's 0. Shrinking the
+/// capacity drops the oldest entries that no longer fit.
+pub fn enable(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::Relaxed);
+    let mut entries = ENTRIES.lock().unwrap_or_else(|err| err.into_inner());
+    while entries.len() > capacity {
+        entries.pop_front();
+    }
+}
+
+/// Stops recording and forgets everything recorded so far.
+pub fn disable() {
+    enable(0);
+}
+
+/// The recorded invocations, oldest first.
+pub fn entries() -> Vec<Entry> {
+    let entries = ENTRIES.lock().unwrap_or_else(|err| err.into_inner());
+    entries.iter().cloned().collect()
+}
+
+/// Writes the recorded invocations to the host's log at `level`, oldest first. Nothing is logged
+/// if nothing has been recorded.
+pub fn dump(level: Level) {
+    let entries = entries();
+    for (i, entry) in entries.iter().enumerate() {
+        let message = format!("invocation {} of {}: {entry}", i + 1, entries.len());
+        crate::log::log(level, &message);
+    }
+}
+
+/// Called by the entrypoint glue when an entrypoint fails.
+pub(crate) fn at_failure() {
+    dump(Level::Error);
+}
+
+/// When recording is on, the time an invocation is starting at, to hand to `record` once it's
+/// done.
+pub(crate) fn start() -> Option<Instant> {
+    (CAPACITY.load(Ordering::Relaxed) > 0).then(Instant::now)
+}
+
+/// Records an invocation that `start` said to record.
+pub(crate) fn record<T: AsRef<[u8]>>(
+    started: Option<Instant>,
+    extension: &str,
+    request_len: usize,
+    response: &Result<T>,
+) {
+    let Some(started) = started else {
+        return;
+    };
+    let entry = Entry {
+        extension: extension.to_string(),
+        request_len,
+        outcome: match response {
+            Ok(response) => Ok(response.as_ref().len()),
+            Err(err) => Err(err.to_string()),
+        },
+        duration: started.elapsed(),
+    };
+
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let mut entries = ENTRIES.lock().unwrap_or_else(|err| err.into_inner());
+    while entries.len() >= capacity {
+        entries.pop_front();
+    }
+    entries.push_back(entry);
+}
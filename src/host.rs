@@ -0,0 +1,389 @@
+//! Declarations for every function the Serval host provides to us. Everything in here is unsafe to
+//! call and takes raw offsets into our linear memory; the rest of the crate wraps these in safe
+//! APIs.
+//!
+//! Pointers are passed as `usize`, which is the same 32-bit value as a `u32` on wasm32 but lets the
+//! mock host (see `mock`) receive real addresses when the crate is built natively. Imports that
+//! hand back a buffer return an `isize` for the same reason: an `i32` on wasm32, and wide enough
+//! for any address in a wasm64 guest's memory. Lengths stay `u32` on every target, as the framing
+//! caps a single buffer at `u32::MAX` bytes anyway.
+
+#[cfg(not(any(feature = "mock-host", feature = "component")))]
+#[link(wasm_import_module = "serval")]
+extern "C" {
+    /// Invokes the named extension with the given payload. Returns a pointer to a length-prefixed
+    /// response on success, or a negative `ExtensionErrorCode` on failure.
+    #[link_name = "invoke_raw"]
+    pub fn invoke_raw(name_ptr: usize, name_len: u32, data_ptr: usize, data_len: u32) -> isize;
+
+    /// Same as `invoke_raw`, but responses of up to 7 bytes are packed into the return value
+    /// instead of a buffer; see `inline` for the encoding. Only imported with the `inline-returns`
+    /// feature, and only called on hosts that negotiated ABI version 2 or later.
+    #[cfg(feature = "inline-returns")]
+    #[link_name = "invoke_raw_inline"]
+    pub fn invoke_raw_inline(name_ptr: usize, name_len: u32, data_ptr: usize, data_len: u32)
+        -> i64;
+
+    /// Same as `invoke_raw`, but the host aborts the call with `ExtensionErrorCode::TimedOut` if
+    /// the extension hasn't responded within `timeout_ms` milliseconds. `u32::MAX` means no
+    /// timeout.
+    #[link_name = "invoke_raw_with_timeout"]
+    pub fn invoke_raw_with_timeout(
+        name_ptr: usize,
+        name_len: u32,
+        data_ptr: usize,
+        data_len: u32,
+        timeout_ms: u32,
+    ) -> isize;
+
+    /// Same as `invoke_raw`, but the extension's response is discarded instead of being copied
+    /// back to us. Returns 0 once the host has accepted the call, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "invoke_raw_oneway"]
+    pub fn invoke_raw_oneway(name_ptr: usize, name_len: u32, data_ptr: usize, data_len: u32)
+        -> i32;
+
+    /// Same as `invoke_raw`, but the data is an invocation frame (see `frame::Frame`) rather than a
+    /// bare payload, and the response is a length-prefixed frame as well.
+    #[link_name = "invoke_framed"]
+    pub fn invoke_framed(name_ptr: usize, name_len: u32, frame_ptr: usize, frame_len: u32)
+        -> isize;
+
+    /// Performs every invocation in an encoded batch (see `batch`). Returns a pointer to a
+    /// length-prefixed batch response, or a negative `ExtensionErrorCode` if the batch as a whole
+    /// couldn't be run.
+    #[link_name = "invoke_batch"]
+    pub fn invoke_batch(batch_ptr: usize, batch_len: u32) -> isize;
+
+    /// Runs an encoded pipeline of extension calls (see `pipeline`), passing each stage's output to
+    /// the next. Returns a pointer to the length-prefixed output of the last stage, or a negative
+    /// `ExtensionErrorCode` from the first stage that failed.
+    #[link_name = "invoke_pipeline"]
+    pub fn invoke_pipeline(pipeline_ptr: usize, pipeline_len: u32) -> isize;
+
+    /// Starts invoking the named extension without waiting for it to respond. The host copies the
+    /// payload before returning. Returns a non-negative handle for the call, or a negative
+    /// `ExtensionErrorCode` if it couldn't be started.
+    #[link_name = "start_invoke"]
+    pub fn start_invoke(name_ptr: usize, name_len: u32, data_ptr: usize, data_len: u32) -> i32;
+
+    /// Checks on a call started with `start_invoke`. Returns 0 if it's still running, otherwise a
+    /// pointer to its length-prefixed response or a negative `ExtensionErrorCode`, after which the
+    /// handle is released.
+    #[link_name = "poll_invoke"]
+    pub fn poll_invoke(handle: u32) -> isize;
+
+    /// Same as `poll_invoke`, but blocks until the call has finished instead of returning 0.
+    #[link_name = "wait_invoke"]
+    pub fn wait_invoke(handle: u32) -> isize;
+
+    /// Blocks until at least one of the `count` u32 handles at `handles_ptr` has finished. Returns
+    /// the index of a finished handle, or a negative `ExtensionErrorCode`.
+    #[link_name = "wait_any"]
+    pub fn wait_any(handles_ptr: usize, count: u32) -> i32;
+
+    /// Abandons a call started with `start_invoke` and releases its handle. Returns 0 on success
+    /// or a negative `ExtensionErrorCode`.
+    #[link_name = "cancel_invoke"]
+    pub fn cancel_invoke(handle: u32) -> i32;
+
+    /// Same as `invoke_raw`, but the response stays with the host to be fetched in chunks with
+    /// `stream_next`. Returns a non-negative handle for the response stream, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "invoke_streaming"]
+    pub fn invoke_streaming(name_ptr: usize, name_len: u32, data_ptr: usize, data_len: u32) -> i32;
+
+    /// Returns a pointer to the next length-prefixed chunk of a response stream, 0 once the whole
+    /// response has been read, or a negative `ExtensionErrorCode`. The stream is released after 0
+    /// or an error is returned.
+    #[link_name = "stream_next"]
+    pub fn stream_next(stream: u32) -> isize;
+
+    /// Allows the host to deliver `credit` more bytes of a response stream. The host never sends
+    /// more than it has been granted, and a chunk never exceeds the credit available when it's
+    /// sent. Returns 0 on success or a negative `ExtensionErrorCode`.
+    #[link_name = "stream_grant"]
+    pub fn stream_grant(stream: u32, credit: u32) -> i32;
+
+    /// Releases a response stream that hasn't been read to the end. Returns 0 on success or a
+    /// negative `ExtensionErrorCode`.
+    #[link_name = "stream_close"]
+    pub fn stream_close(stream: u32) -> i32;
+
+    /// Opens a request to the named extension whose payload will be supplied with
+    /// `request_write`. Returns a non-negative handle for the request, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "open_request"]
+    pub fn open_request(name_ptr: usize, name_len: u32) -> i32;
+
+    /// Appends `data_len` bytes at `data_ptr` to an open request's payload. Returns 0 on success
+    /// or a negative `ExtensionErrorCode`.
+    #[link_name = "request_write"]
+    pub fn request_write(request: u32, data_ptr: usize, data_len: u32) -> i32;
+
+    /// Invokes the extension with an open request's payload and releases the request. Returns the
+    /// same as `invoke_raw`.
+    #[link_name = "request_finish"]
+    pub fn request_finish(request: u32) -> isize;
+
+    /// Same as `request_finish`, but the response stays with the host to be fetched in chunks,
+    /// like `invoke_streaming`. Returns a non-negative handle for the response stream, or a
+    /// negative `ExtensionErrorCode`.
+    #[link_name = "request_finish_streaming"]
+    pub fn request_finish_streaming(request: u32) -> i32;
+
+    /// Agrees on a segment size for chunked transfers, given the largest one the guest wants to
+    /// hold in memory at once. Returns the size the host picked, at most `preferred`, or a negative
+    /// `ExtensionErrorCode` if the host doesn't support chunked transfers.
+    #[link_name = "negotiate_segment_size"]
+    pub fn negotiate_segment_size(preferred: u32) -> i32;
+
+    /// Releases an open request without invoking the extension. Returns 0 on success or a
+    /// negative `ExtensionErrorCode`.
+    #[link_name = "request_abort"]
+    pub fn request_abort(request: u32) -> i32;
+
+    /// Opens a duplex channel to the named extension. Returns a non-negative handle for the
+    /// channel, or a negative `ExtensionErrorCode`.
+    #[link_name = "open_channel"]
+    pub fn open_channel(name_ptr: usize, name_len: u32) -> i32;
+
+    /// Sends the `data_len` bytes at `data_ptr` as one message on a channel. Returns 0 on success
+    /// or a negative `ExtensionErrorCode`.
+    #[link_name = "channel_send"]
+    pub fn channel_send(channel: u32, data_ptr: usize, data_len: u32) -> i32;
+
+    /// Blocks until the extension sends a message on a channel. Returns a pointer to the
+    /// length-prefixed message, 0 if the extension has closed its side, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "channel_recv"]
+    pub fn channel_recv(channel: u32) -> isize;
+
+    /// Same as `stream_grant`, for the messages the extension sends on a channel.
+    #[link_name = "channel_grant"]
+    pub fn channel_grant(channel: u32, credit: u32) -> i32;
+
+    /// Closes the guest's sending side of a channel. Returns 0 on success or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "channel_close_send"]
+    pub fn channel_close_send(channel: u32) -> i32;
+
+    /// Closes both sides of a channel and releases it. Returns 0 on success or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "channel_close"]
+    pub fn channel_close(channel: u32) -> i32;
+
+    /// Returns a pointer to a length-prefixed buffer listing the extensions installed on the node as
+    /// a u32 count followed by length-prefixed name and version pairs, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "list_extensions"]
+    pub fn list_extensions() -> isize;
+
+    /// Returns a pointer to a length-prefixed blob describing the extension `name`, 0 if it isn't
+    /// installed, or a negative `ExtensionErrorCode`; see `extensions::Description`.
+    #[link_name = "describe_extension"]
+    pub fn describe_extension(name_ptr: usize, name_len: u32) -> isize;
+
+    /// Returns a pointer to a length-prefixed buffer listing the capabilities granted to the job as
+    /// a u32 count followed by length-prefixed names (see `caps::Capability`), or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "caps_granted"]
+    pub fn caps_granted() -> isize;
+
+    /// Returns 1 if the job may invoke the extension `name`, 0 if it may not, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "caps_can_invoke"]
+    pub fn caps_can_invoke(name_ptr: usize, name_len: u32) -> i32;
+
+    /// Asks the host to pick the newest ABI version in `min..=max` that it speaks, too; see `abi`.
+    /// Returns the version, or `ExtensionErrorCode::NoMatchingVersion` if there isn't one.
+    #[link_name = "abi_negotiate"]
+    pub fn abi_negotiate(min: u32, max: u32) -> i32;
+
+    /// Returns a pointer to a length-prefixed blob describing the most recent failed call, 0 if
+    /// nothing has failed yet, or a negative `ExtensionErrorCode` if the details can't be fetched.
+    #[link_name = "get_last_error"]
+    pub fn get_last_error() -> isize;
+
+    /// Hands the host the guest's memory statistics: five little-endian u32s giving the memory
+    /// size in pages, live allocations, live bytes, peak bytes and pooled bytes. Returns 0 on
+    /// success or a negative `ExtensionErrorCode`.
+    #[link_name = "report_memory_stats"]
+    pub fn report_memory_stats(ptr: usize, len: u32) -> i32;
+
+    /// Returns a pointer to a length-prefixed blob describing the running job; see
+    /// `job::JobMetadata`. Returns a negative `ExtensionErrorCode` if it can't be fetched.
+    #[link_name = "job_metadata"]
+    pub fn job_metadata() -> isize;
+
+    /// Returns a pointer to a length-prefixed blob holding the job's input: named parameters and
+    /// a body; see `job::JobInput`. Returns a negative `ExtensionErrorCode` if it can't be fetched.
+    #[link_name = "job_input"]
+    pub fn job_input() -> isize;
+
+    /// Returns a pointer to the length-prefixed trace ID the job was assigned, 0 if it wasn't
+    /// assigned one, or a negative `ExtensionErrorCode`.
+    #[link_name = "job_trace_id"]
+    pub fn job_trace_id() -> isize;
+
+    /// Publishes the job's output. Returns 0 on success or a negative `ExtensionErrorCode`.
+    #[link_name = "job_set_output"]
+    pub fn job_set_output(ptr: usize, len: u32) -> i32;
+
+    /// Marks the job as complete with the given status (0 succeeded, 1 failed) and a UTF-8
+    /// message. Returns 0 on success or a negative `ExtensionErrorCode`.
+    #[link_name = "job_complete"]
+    pub fn job_complete(status: u32, message_ptr: usize, message_len: u32) -> i32;
+
+    /// Reports how far along the job is, as a percentage and a UTF-8 message. Returns 0 on success
+    /// or a negative `ExtensionErrorCode`.
+    #[link_name = "job_progress"]
+    pub fn job_progress(percent: f32, message_ptr: usize, message_len: u32) -> i32;
+
+    /// Returns 1 if the job has been cancelled and 0 if it hasn't.
+    #[link_name = "job_cancelled"]
+    pub fn job_cancelled() -> i32;
+
+    /// Tells the scheduler the job is still alive. Returns 0 on success or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "job_heartbeat"]
+    pub fn job_heartbeat() -> i32;
+
+    /// Hands the host a batch of metric updates; see `metrics` for the layout. Returns 0 on success
+    /// or a negative `ExtensionErrorCode`.
+    #[link_name = "metrics_flush"]
+    pub fn metrics_flush(ptr: usize, len: u32) -> i32;
+
+    /// Fetches a secret. Returns a pointer to the length-prefixed value, 0 if there's no secret by
+    /// that name, or a negative `ExtensionErrorCode`. Secrets are kept apart from configuration
+    /// variables so they can be access controlled and audited separately.
+    #[link_name = "secret_get"]
+    pub fn secret_get(name_ptr: usize, name_len: u32) -> isize;
+
+    /// Looks up a configuration variable. Returns a pointer to the length-prefixed value, 0 if the
+    /// variable isn't set, or a negative `ExtensionErrorCode`.
+    #[link_name = "env_get"]
+    pub fn env_get(key_ptr: usize, key_len: u32) -> isize;
+
+    /// Returns a pointer to a length-prefixed buffer holding every configuration variable as a u32
+    /// count followed by length-prefixed key and value pairs, or a negative `ExtensionErrorCode`.
+    #[link_name = "env_vars"]
+    pub fn env_vars() -> isize;
+
+    /// Returns the host's wall-clock time in nanoseconds since the UNIX epoch.
+    #[link_name = "wall_clock_now"]
+    pub fn wall_clock_now() -> u64;
+
+    /// Returns the host's monotonic clock in nanoseconds, counted from an arbitrary starting
+    /// point.
+    #[link_name = "monotonic_now"]
+    pub fn monotonic_now() -> u64;
+
+    /// Opens a socket of the given kind (0 for TCP, 1 for UDP) connected to the `host:port`
+    /// address. Returns the socket's id or a negative `ExtensionErrorCode`.
+    #[link_name = "socket_connect"]
+    pub fn socket_connect(kind: u32, addr_ptr: usize, addr_len: u32) -> i32;
+
+    /// Blocks until data arrives on a socket and copies up to `buf_len` bytes of it into the
+    /// buffer. Returns the number of bytes copied, 0 at the end of a TCP stream, or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "socket_read"]
+    pub fn socket_read(socket: u32, buf_ptr: usize, buf_len: u32) -> i32;
+
+    /// Sends data on a socket. Returns the number of bytes sent, which may be fewer than `len`
+    /// for TCP, or a negative `ExtensionErrorCode`.
+    #[link_name = "socket_write"]
+    pub fn socket_write(socket: u32, ptr: usize, len: u32) -> i32;
+
+    /// Closes a socket. Returns 0 on success or a negative `ExtensionErrorCode`.
+    #[link_name = "socket_close"]
+    pub fn socket_close(socket: u32) -> i32;
+
+    /// Opens a file in the job's storage with the given `fs::OpenOptions` flags. Returns the
+    /// file's id or a negative `ExtensionErrorCode`, `NotFound` if the file doesn't exist and
+    /// wasn't to be created.
+    #[link_name = "fs_open"]
+    pub fn fs_open(path_ptr: usize, path_len: u32, flags: u32) -> i32;
+
+    /// Copies up to `buf_len` bytes from a file's current position into the buffer and advances
+    /// the position past them. Returns the number of bytes copied, 0 at the end of the file, or a
+    /// negative `ExtensionErrorCode`.
+    #[link_name = "fs_read"]
+    pub fn fs_read(file: u32, buf_ptr: usize, buf_len: u32) -> i32;
+
+    /// Writes data at a file's current position, or at its end if it was opened for appending.
+    /// Returns the number of bytes written or a negative `ExtensionErrorCode`.
+    #[link_name = "fs_write"]
+    pub fn fs_write(file: u32, ptr: usize, len: u32) -> i32;
+
+    /// Moves a file's position to `offset` bytes from its start (`whence` 0), its current position
+    /// (1) or its end (2). Returns the new position or a negative `ExtensionErrorCode`.
+    #[link_name = "fs_seek"]
+    pub fn fs_seek(file: u32, whence: u32, offset: i64) -> i64;
+
+    /// Closes a file, making everything written to it durable. Returns 0 on success or a negative
+    /// `ExtensionErrorCode`.
+    #[link_name = "fs_close"]
+    pub fn fs_close(file: u32) -> i32;
+
+    /// Lists a directory in the job's storage. Returns a pointer to a length-prefixed buffer
+    /// holding a u32 count followed by that many entries, each a length-prefixed name, a u8 that's
+    /// 1 for directories and a u64 size; or a negative `ExtensionErrorCode`, `NotFound` if there's
+    /// no such directory.
+    #[link_name = "fs_list"]
+    pub fn fs_list(path_ptr: usize, path_len: u32) -> isize;
+
+    /// Deletes a file from the job's storage. Returns 0 on success or a negative
+    /// `ExtensionErrorCode`, `NotFound` if there's no such file.
+    #[link_name = "fs_remove"]
+    pub fn fs_remove(path_ptr: usize, path_len: u32) -> i32;
+
+    /// Suspends the guest for at least the given number of nanoseconds.
+    #[link_name = "sleep"]
+    pub fn sleep(nanos: u64);
+
+    /// Gives the host a chance to run other work before the guest continues.
+    #[link_name = "yield_now"]
+    pub fn yield_now();
+
+    /// Fills the buffer with cryptographically secure random bytes. Returns 0 on success or a
+    /// negative `ExtensionErrorCode`.
+    #[link_name = "random_fill"]
+    pub fn random_fill(ptr: usize, len: u32) -> i32;
+
+    /// Writes a UTF-8 message to the host's logs. `level` is a `log::Level`, from 0 (trace) to 4
+    /// (error).
+    #[link_name = "log_raw"]
+    pub fn log_raw(level: u32, ptr: usize, len: u32);
+
+    /// Hands the host a span or event record produced by `tracing::ServalLayer`; see that module
+    /// for the layout. Returns 0 on success or a negative `ExtensionErrorCode`.
+    #[cfg(feature = "tracing")]
+    #[link_name = "trace_record"]
+    pub fn trace_record(ptr: usize, len: u32) -> i32;
+
+    /// Hands the host an encoded `GuestError` envelope describing why the guest is failing.
+    /// Returns 0 on success or a negative `ExtensionErrorCode`.
+    #[link_name = "report_error"]
+    pub fn report_error(ptr: usize, len: u32) -> i32;
+
+    /// Copies `len` bytes at `offset` in the host's exchange memory into ours at `dst_ptr`.
+    /// Returns 0 on success, or `ExtensionErrorCode::InvalidPayload` if the range isn't within a
+    /// buffer the host handed us. Only called once the host has enabled exchange memory; see
+    /// `exchange`.
+    #[cfg(feature = "exchange-memory")]
+    #[link_name = "exchange_read"]
+    pub fn exchange_read(offset: usize, dst_ptr: usize, len: u32) -> i32;
+
+    /// Releases the buffer at `offset` in the host's exchange memory. Returns 0 on success or a
+    /// negative `ExtensionErrorCode`.
+    #[cfg(feature = "exchange-memory")]
+    #[link_name = "exchange_free"]
+    pub fn exchange_free(offset: usize) -> i32;
+}
+
+#[cfg(feature = "mock-host")]
+pub(crate) use crate::mock::imports::*;
+
+#[cfg(all(feature = "component", not(feature = "mock-host")))]
+pub(crate) use crate::component::imports::*;
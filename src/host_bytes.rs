@@ -0,0 +1,256 @@
+//! Buffers exchanged with the host through our linear memory.
+//!
+//! Since we can only communicate by passing around single numbers, the way the Serval host
+//! environment passes us data is by asking us (the guest) to allocate N + 4 bytes of memory, where
+//! N is the number of bytes of data it's trying to send us. The host writes the data into the
+//! block as a frame (see `crate::framing`) and hands us a pointer to it. Buffers we pass to the
+//! host use the same layout, and the host frees them with our `dealloc`.
+//!
+//! Every unsafe access to such a buffer lives in this module.
+
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+
+use crate::framing::{decode_prefix, encode_prefix, PREFIX_LEN};
+use crate::pool::block_size;
+use crate::{guest_alloc, guest_dealloc, scrub, Result, SdkError};
+
+/// A length-prefixed buffer the host allocated in our linear memory (by calling our `alloc`) to
+/// pass data to us. Rather than copying the data out, this takes ownership of the allocation,
+/// derefs to the bytes in place, and frees the whole block when dropped.
+pub struct OwnedHostBytes {
+    /// Points at the u32 length prefix, i.e. the start of the allocation.
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl OwnedHostBytes {
+    /// Takes ownership of the host allocation at `ptr`.
+    ///
+    /// # Safety
+    /// `ptr` must point to a block allocated by our `alloc` that starts with a little-endian u32
+    /// length N followed by N bytes of data, and nothing else may free or use that block
+    /// afterwards.
+    pub(crate) unsafe fn from_host(ptr: usize) -> Self {
+        Self {
+            ptr: ptr as *mut u8,
+            len: read_prefix(ptr),
+        }
+    }
+
+    /// Turns the buffer into a `Vec` without allocating: the data is moved down over the length
+    /// prefix and the allocation is handed to the `Vec` as is, spare capacity included. The block
+    /// leaves the buffer pool for good.
+    #[track_caller]
+    pub fn into_vec(self) -> Vec<u8> {
+        let this = ManuallyDrop::new(self);
+        // From here on the block is an ordinary `Vec` rather than a buffer from the host.
+        crate::memory::record_free(PREFIX_LEN + this.len);
+        #[cfg(feature = "alloc-tracking")]
+        crate::alloc_tracking::record_dealloc(
+            this.ptr,
+            PREFIX_LEN + this.len,
+            Some(std::panic::Location::caller()),
+        );
+        // Safety: `alloc` gave the block the layout of a `Vec<u8>` with capacity
+        // `block_size(PREFIX_LEN + len)`, and after the move its first `len` bytes are the
+        // data.
+        unsafe {
+            std::ptr::copy(this.ptr.add(PREFIX_LEN), this.ptr, this.len);
+            // The move leaves a stale copy of the last few bytes in the spare capacity.
+            scrub(this.ptr.add(this.len), PREFIX_LEN);
+            let capacity = block_size(PREFIX_LEN + this.len);
+            Vec::from_raw_parts(this.ptr, this.len, capacity)
+        }
+    }
+
+    /// Grows or shrinks the buffer to `new_len` bytes, through the same path as our
+    /// `serval_realloc` export, so the block is resized in place when the allocator can manage it.
+    /// The first `min(len, new_len)` bytes are kept and any new ones are zeroed. If the block
+    /// can't be resized, the buffer is left as it was.
+    pub fn resize(&mut self, new_len: usize) -> Result<()> {
+        let size = PREFIX_LEN
+            .checked_add(new_len)
+            .filter(|_| u32::try_from(new_len).is_ok())
+            .ok_or(SdkError::AllocationFailed)?;
+        // Safety: we own the block, which came from our `alloc` with exactly `PREFIX_LEN + len`
+        // bytes, and `size` is never zero, so it isn't freed.
+        let ptr = unsafe { crate::realloc(self.ptr, PREFIX_LEN + self.len, size) };
+        if ptr.is_null() {
+            return Err(SdkError::AllocationFailed);
+        }
+        // Safety: the resized block holds `size` bytes, of which the first `PREFIX_LEN + len` (or
+        // all of them when shrinking) were carried over.
+        unsafe {
+            if new_len > self.len {
+                std::ptr::write_bytes(ptr.add(PREFIX_LEN + self.len), 0, new_len - self.len);
+            }
+            std::ptr::copy_nonoverlapping(encode_prefix(new_len).as_ptr(), ptr, PREFIX_LEN);
+        }
+        self.ptr = ptr;
+        self.len = new_len;
+        Ok(())
+    }
+}
+
+impl Deref for OwnedHostBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: from_host's contract guarantees `len` initialized bytes follow the prefix, and
+        // we own the block until we're dropped.
+        unsafe { std::slice::from_raw_parts(self.ptr.add(PREFIX_LEN), self.len) }
+    }
+}
+
+impl DerefMut for OwnedHostBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // Safety: as for `deref`, and `&mut self` makes this the only view of the block.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.add(PREFIX_LEN), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for OwnedHostBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Drop for OwnedHostBytes {
+    fn drop(&mut self) {
+        unsafe { guest_dealloc(self.ptr, PREFIX_LEN + self.len) };
+    }
+}
+
+impl fmt::Debug for OwnedHostBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedHostBytes")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Reads the little-endian u32 length prefix at `ptr`.
+///
+/// # Safety
+/// `ptr` must point to at least 4 readable bytes.
+unsafe fn read_prefix(ptr: usize) -> usize {
+    let mut prefix = [0u8; PREFIX_LEN];
+    std::ptr::copy_nonoverlapping(ptr as *const u8, prefix.as_mut_ptr(), PREFIX_LEN);
+    decode_prefix(prefix)
+}
+
+/// Takes ownership of a buffer the host passed us a pointer to. A null pointer means the host
+/// couldn't get memory from our `alloc` to write the data into, and a buffer that runs off the end
+/// of linear memory is rejected with `SdkError::CorruptFrame` rather than read.
+pub(crate) fn take_host_bytes(ptr: usize) -> Result<OwnedHostBytes> {
+    if ptr == 0 {
+        return Err(SdkError::AllocationFailed);
+    }
+    #[cfg(feature = "exchange-memory")]
+    if crate::exchange::enabled() {
+        return take_exchange_bytes(ptr);
+    }
+    #[cfg(feature = "mock-host")]
+    let ptr = crate::mock::resolve(ptr)?;
+    check_bounds(ptr)?;
+    // Safety: the host only ever passes us pointers to length-prefixed blocks from our `alloc`,
+    // and gives up ownership of them when it does.
+    Ok(unsafe { OwnedHostBytes::from_host(ptr) })
+}
+
+/// `take_host_bytes` for a buffer at `offset` in the host's exchange memory (see `exchange`):
+/// copies it into a block of our own, laid out the same as one the host wrote into, and releases
+/// the original.
+#[cfg(feature = "exchange-memory")]
+fn take_exchange_bytes(offset: usize) -> Result<OwnedHostBytes> {
+    let mut prefix = [0u8; PREFIX_LEN];
+    let copied = crate::exchange::read(offset, &mut prefix).and_then(|()| {
+        let len = decode_prefix(prefix);
+        // On wasm32 a prefix near u32::MAX doesn't fit alongside the prefix itself, and must
+        // not wrap around to a tiny block that the data is then written past.
+        let size = PREFIX_LEN
+            .checked_add(len)
+            .ok_or_else(|| SdkError::CorruptFrame {
+                ptr: offset,
+                len: Some(len),
+                memory_size: crate::memory_stats().memory_pages * crate::PAGE_SIZE,
+            })?;
+        let block = guest_alloc(size);
+        if block.is_null() {
+            return Err(SdkError::AllocationFailed);
+        }
+        // Safety: `block` is a fresh allocation of `PREFIX_LEN + len` bytes, which the prefix and
+        // the data fill exactly. The data is zeroed before making a slice of it, as the block may
+        // be uninitialized.
+        let data = unsafe {
+            std::ptr::copy_nonoverlapping(prefix.as_ptr(), block, PREFIX_LEN);
+            std::ptr::write_bytes(block.add(PREFIX_LEN), 0, len);
+            std::slice::from_raw_parts_mut(block.add(PREFIX_LEN), len)
+        };
+        let buffer = OwnedHostBytes { ptr: block, len };
+        crate::exchange::read(offset + PREFIX_LEN, data)?;
+        Ok(buffer)
+    });
+    crate::exchange::free(offset);
+    copied
+}
+
+/// Checks that the length prefix at `ptr` and the data it describes lie within linear memory.
+#[cfg(target_family = "wasm")]
+fn check_bounds(ptr: usize) -> Result<()> {
+    const PAGE_SIZE: usize = 64 * 1024;
+    let memory_size = crate::arch::memory_size(0) * PAGE_SIZE;
+    let in_bounds = |end: Option<usize>| end.is_some_and(|end| end <= memory_size);
+
+    let data_start = ptr.checked_add(PREFIX_LEN);
+    if !in_bounds(data_start) {
+        return Err(SdkError::CorruptFrame {
+            ptr,
+            len: None,
+            memory_size,
+        });
+    }
+    // Safety: the prefix was just checked to be within linear memory, all of which is readable.
+    let len = unsafe { read_prefix(ptr) };
+    if !in_bounds(data_start.and_then(|start| start.checked_add(len))) {
+        return Err(SdkError::CorruptFrame {
+            ptr,
+            len: Some(len),
+            memory_size,
+        });
+    }
+    Ok(())
+}
+
+/// Outside of wasm there's no linear memory to check against.
+#[cfg(not(target_family = "wasm"))]
+fn check_bounds(_ptr: usize) -> Result<()> {
+    Ok(())
+}
+
+/// Retrieves a blob of bytes that the host environment is trying to pass to us, freeing the host's
+/// buffer in the process.
+#[track_caller]
+pub(crate) fn get_bytes_from_host(ptr: usize) -> Result<Vec<u8>> {
+    Ok(take_host_bytes(ptr)?.into_vec())
+}
+
+/// The mirror image of `get_bytes_from_host`: copies `bytes` into a fresh allocation, prefixed with
+/// their length as a u32, and returns a pointer to it. Ownership passes to the host, which is
+/// expected to read the data and then free the allocation with our `dealloc`.
+#[track_caller]
+pub(crate) fn bytes_to_host(bytes: &[u8]) -> usize {
+    let alloc_size = PREFIX_LEN + bytes.len();
+    let ptr = guest_alloc(alloc_size);
+    if ptr.is_null() {
+        std::alloc::handle_alloc_error(std::alloc::Layout::array::<u8>(alloc_size).unwrap());
+    }
+    // Safety: `ptr` is a fresh allocation of `alloc_size` bytes.
+    unsafe {
+        std::ptr::copy_nonoverlapping(encode_prefix(bytes.len()).as_ptr(), ptr, PREFIX_LEN);
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.add(PREFIX_LEN), bytes.len());
+    }
+    ptr as usize
+}
@@ -0,0 +1,249 @@
+//! An HTTP client over the platform's HTTP extension.
+//!
+//! ```ignore
+//! use serval::http::Request;
+//!
+//! let response = Request::post("https://api.example.com/jobs")
+//!     .header("content-type", "application/json")
+//!     .body(payload)
+//!     .timeout(Duration::from_secs(10))
+//!     .send()?;
+//! if response.is_success() {
+//!     let body = response.bytes()?;
+//! }
+//! ```
+//!
+//! A request is a frame with the `request` operation sent to the `EXTENSION` extension (or the
+//! one passed to `Request::send_to`), whose body is the method, the URL, a u16 header count and
+//! that many name/value pairs, followed by the request body. Strings are length-prefixed and
+//! integers little-endian. A timeout goes in the frame's `tags::TIMEOUT_MS` field.
+//!
+//! The response comes back as a stream. Its first chunk is a frame reporting through
+//! `tags::STATUS` whether the extension managed to make the request at all, with a body holding the
+//! u16 HTTP status, a u16 header count and that many name/value pairs. Every chunk after that is
+//! part of the response body.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::frame::{tags, Frame};
+use crate::wire::{Reader, Writer};
+use crate::{invoke_streaming, service, ResponseStream, Result, SdkError};
+
+/// The extension the platform's HTTP client is registered under.
+pub const EXTENSION: &str = "http";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Options,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A builder for an HTTP request.
+#[derive(Clone, Debug)]
+pub struct Request {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    timeout: Option<Duration>,
+}
+
+impl Request {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::Get, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::Post, url)
+    }
+
+    pub fn put(url: impl Into<String>) -> Self {
+        Self::new(Method::Put, url)
+    }
+
+    pub fn delete(url: impl Into<String>) -> Self {
+        Self::new(Method::Delete, url)
+    }
+
+    /// Adds a header. Headers with the same name are all sent, in the order they were added.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Fails the request with `SdkError::TimedOut` if the response headers haven't arrived in
+    /// time.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends the request through the platform's HTTP extension.
+    pub fn send(&self) -> Result<Response> {
+        self.send_to(EXTENSION)
+    }
+
+    /// Sends the request through the HTTP extension registered under `extension_name`. Fails with
+    /// `SdkError::Encode` if the request has more than `u16::MAX` headers.
+    pub fn send_to(&self, extension_name: &str) -> Result<Response> {
+        let mut stream = invoke_streaming(extension_name, &self.encode()?.encode_for_send()?)?;
+        let head = match stream.next() {
+            Some(head) => head?,
+            None => return Err(SdkError::InvalidPayload),
+        };
+        let head = service::response_body(Frame::decode(&head)?)?;
+
+        let mut reader = Reader::new(&head);
+        let status = reader.read_u16()?;
+        let headers = read_headers(&mut reader)?;
+        Ok(Response {
+            status,
+            headers,
+            body: stream,
+        })
+    }
+
+    fn encode(&self) -> Result<Frame> {
+        let mut body = Writer::new();
+        body.write_str(self.method.as_str());
+        body.write_str(&self.url);
+        body.write_u16_count(self.headers.len(), "headers")?;
+        for (name, value) in &self.headers {
+            body.write_str(name);
+            body.write_str(value);
+        }
+        body.write_bytes(&self.body);
+
+        let mut frame = service::request("request", body.into_bytes());
+        if let Some(timeout) = self.timeout {
+            let timeout_ms = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+            frame.push_field(tags::TIMEOUT_MS, timeout_ms.to_le_bytes().to_vec());
+        }
+        Ok(frame)
+    }
+}
+
+fn read_headers(reader: &mut Reader<'_>) -> Result<Vec<(String, String)>> {
+    let count = reader.read_u16()?;
+    (0..count)
+        .map(|_| {
+            Ok((
+                reader.read_str()?.to_string(),
+                reader.read_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// An HTTP response. The body is fetched from the host as it's read, either through `io::Read`
+/// or all at once with `bytes` or `text`.
+#[derive(Debug)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: ResponseStream,
+}
+
+impl Response {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Whether the status is in the 2xx range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Every header in the response, in the order the server sent them.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// The value of the first header with the given name, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body as a stream of chunks, for handing to code that wants a `ResponseStream`.
+    pub fn into_body(self) -> ResponseStream {
+        self.body
+    }
+
+    /// Reads the rest of the body into a single buffer.
+    pub fn bytes(self) -> Result<Vec<u8>> {
+        self.body.collect_bytes()
+    }
+
+    /// Reads the rest of the body as UTF-8 text.
+    pub fn text(self) -> Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|_| SdkError::InvalidPayload)
+    }
+}
+
+impl io::Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Request;
+    use crate::SdkError;
+
+    #[test]
+    fn requests_with_more_headers_than_the_count_holds_fail_to_encode() {
+        let mut request = Request::get("https://example.com");
+        for _ in 0..u16::MAX {
+            request = request.header("x", "y");
+        }
+        assert!(request.encode().is_ok());
+        let request = request.header("x", "y");
+        assert!(matches!(request.encode(), Err(SdkError::Encode(_))));
+    }
+}
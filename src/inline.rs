@@ -0,0 +1,84 @@
+//! Inline returns for small responses. Many extensions answer with a handful of bytes (a status, a
+//! counter), for which having the host allocate a buffer with our `alloc`, copy the response into
+//! it and us copy it out again and free it costs far more than the response itself. With the
+//! `inline-returns` feature, on hosts that negotiated ABI version 2 or later, `invoke_extension`
+//! calls the `invoke_raw_inline` import instead of `invoke_raw`, which packs responses of up to
+//! `MAX_LEN` bytes into the i64 it returns:
+//!
+//! - A negative value is an `ExtensionErrorCode`, as from `invoke_raw`.
+//! - Otherwise the top byte is a tag. 0 means the low 56 bits are a pointer to a length-prefixed
+//!   buffer, as `invoke_raw` would return. A tag from 1 to `MAX_LEN + 1` means the response is
+//!   inline: it's `tag - 1` bytes long, stored little-endian in the low 56 bits.
+//!
+//! The feature is opt-in because it makes `invoke_raw_inline` an import of the module, which hosts
+//! that predate version 2 can't satisfy. Version 1 hosts that stub it out can still run the guest;
+//! it's called through `invoke_raw` on them.
+
+/// The largest response that fits in the return value.
+pub(crate) const MAX_LEN: usize = 7;
+
+/// The ABI version from which hosts provide `invoke_raw_inline`.
+#[cfg(feature = "inline-returns")]
+const MIN_ABI_VERSION: u32 = 2;
+
+const TAG_SHIFT: u32 = 56;
+
+/// What `invoke_raw_inline` returned.
+#[cfg(feature = "inline-returns")]
+enum Packed {
+    /// The response itself.
+    Inline(Vec<u8>),
+    /// The value `invoke_raw` would have returned: a pointer, or a negative error code.
+    Pointer(isize),
+}
+
+/// Invokes the extension through `invoke_raw_inline`, or returns `None` if the host doesn't
+/// provide it and the call should go through `invoke_raw`.
+#[cfg(feature = "inline-returns")]
+pub(crate) fn invoke(extension_name: &str, data: &[u8]) -> Option<crate::Result<Vec<u8>>> {
+    if !crate::abi::handshake().is_ok_and(|version| version >= MIN_ABI_VERSION) {
+        return None;
+    }
+    let value = unsafe {
+        crate::host::invoke_raw_inline(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            data.as_ptr() as usize,
+            data.len() as u32,
+        )
+    };
+    Some(match unpack(value) {
+        Packed::Inline(response) => Ok(response),
+        Packed::Pointer(out_ptr) => crate::read_response(out_ptr, extension_name, data.len()),
+    })
+}
+
+#[cfg(feature = "inline-returns")]
+fn unpack(value: i64) -> Packed {
+    if value < 0 {
+        return Packed::Pointer(i32::try_from(value).unwrap_or(i32::MIN) as isize);
+    }
+    match (value >> TAG_SHIFT) as usize {
+        0 => Packed::Pointer(value as isize),
+        tag if tag <= MAX_LEN + 1 => Packed::Inline(value.to_le_bytes()[..tag - 1].to_vec()),
+        // No host packs a longer response; treat it as malformed rather than guessing.
+        _ => Packed::Pointer(crate::ExtensionErrorCode::InvalidPayload.as_raw() as isize),
+    }
+}
+
+/// Packs `bytes` into a return value, if they fit; for the stand-in hosts.
+#[cfg(any(
+    feature = "harness",
+    all(
+        feature = "inline-returns",
+        any(feature = "mock-host", feature = "component")
+    )
+))]
+pub(crate) fn pack(bytes: &[u8]) -> Option<i64> {
+    if bytes.len() > MAX_LEN {
+        return None;
+    }
+    let mut value = [0; 8];
+    value[..bytes.len()].copy_from_slice(bytes);
+    Some(i64::from_le_bytes(value) | ((bytes.len() as i64 + 1) << TAG_SHIFT))
+}
@@ -0,0 +1,195 @@
+use std::borrow::Cow;
+use std::time::Duration;
+
+use crate::frame::{tags, Frame, HeaderField};
+use crate::{invoke_extension, invoke_extension_with_timeout, Callback, Result, RetryPolicy};
+
+/// A builder for a single extension call, for when the call needs options beyond what
+/// `invoke_extension` offers:
+///
+/// ```ignore
+/// let response = Invocation::to("image-resize")
+///     .payload(bytes)
+///     .timeout(Duration::from_secs(2))
+///     .retries(3)
+///     .send()?;
+/// ```
+///
+/// Calls that need header fields (a content type, an operation, raw headers, compression) are
+/// sent as frames with `invoke_framed`; plain calls go through the same host functions as
+/// `invoke_extension` and `invoke_extension_with_timeout`.
+#[derive(Clone, Debug)]
+pub struct Invocation<'a> {
+    extension: Cow<'a, str>,
+    payload: Cow<'a, [u8]>,
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    fields: Vec<HeaderField>,
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    compression: Option<crate::compression::Compression>,
+}
+
+impl<'a> Invocation<'a> {
+    /// Starts building a call to the named extension, with an empty payload.
+    pub fn to(extension: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            extension: extension.into(),
+            payload: Cow::Borrowed(&[]),
+            timeout: None,
+            retry: None,
+            fields: Vec::new(),
+            #[cfg(any(feature = "lz4", feature = "zstd"))]
+            compression: crate::compression::compression(),
+        }
+    }
+
+    pub fn payload(mut self, payload: impl Into<Cow<'a, [u8]>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    /// Fails the call with `SdkError::TimedOut` if the extension hasn't responded in time. With
+    /// retries, the timeout applies to each attempt separately.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries transient failures up to `retries` times using the default `RetryPolicy`.
+    pub fn retries(self, retries: u32) -> Self {
+        let policy = self.retry.clone().unwrap_or_default();
+        self.retry_policy(policy.max_attempts(retries.saturating_add(1)))
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Declares the payload's content type; see `crate::content_type`.
+    pub fn content_type(self, content_type: &str) -> Self {
+        self.header(tags::CONTENT_TYPE, content_type.as_bytes().to_vec())
+    }
+
+    /// Names the operation to invoke on an extension that exposes several.
+    pub fn operation(self, operation: &str) -> Self {
+        self.header(tags::OPERATION, operation.as_bytes().to_vec())
+    }
+
+    /// Adds a raw header field to the frame. A field with the same tag replaces any earlier one.
+    pub fn header(mut self, tag: u8, value: Vec<u8>) -> Self {
+        self.fields.retain(|field| field.tag != tag);
+        self.fields.push(HeaderField { tag, value });
+        self
+    }
+
+    /// Adds a metadata entry to the request, such as an auth token, without touching the
+    /// payload's schema. Any number can be added, including several under the same key. The
+    /// extension's response metadata is on the frame `send_frame` returns; see `Frame::metadata`.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.fields.push(HeaderField {
+            tag: tags::METADATA,
+            value: crate::frame::metadata_entry(key, value),
+        });
+        self
+    }
+
+    /// Lets the extension call `callback` while this invocation is in flight. Any number of
+    /// callbacks can be attached.
+    pub fn callback(mut self, callback: &Callback) -> Self {
+        self.fields.push(HeaderField {
+            tag: tags::CALLBACK,
+            value: callback.header_value(),
+        });
+        self
+    }
+
+    /// Overrides the compression config for this call. Defaults to the one installed with
+    /// `set_compression` when the builder was created.
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    pub fn compression(mut self, compression: Option<crate::compression::Compression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Switches to encoding a typed request with `codec`; see `TypedInvocation::send`.
+    #[cfg(feature = "codec")]
+    pub fn codec<C: crate::Codec>(self, codec: C) -> TypedInvocation<'a, C> {
+        TypedInvocation {
+            invocation: self.content_type(codec.content_type()),
+            codec,
+        }
+    }
+
+    /// Sends the call and returns the response payload.
+    pub fn send(&self) -> Result<Vec<u8>> {
+        if !self.needs_frame() {
+            return self.with_retries(|| match self.timeout {
+                Some(timeout) => {
+                    invoke_extension_with_timeout(&self.extension, &self.payload, timeout)
+                }
+                None => invoke_extension(&self.extension, &self.payload),
+            });
+        }
+        Ok(self.send_frame()?.body)
+    }
+
+    /// Sends the call as a frame and returns the full response frame, for access to the headers
+    /// the extension sent back.
+    pub fn send_frame(&self) -> Result<Frame> {
+        let mut frame = Frame::new(self.payload.to_vec());
+        frame.fields = self.fields.clone();
+        if let Some(timeout) = self.timeout {
+            let timeout_ms = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+            frame.push_field(tags::TIMEOUT_MS, timeout_ms.to_le_bytes().to_vec());
+        }
+
+        self.with_retries(|| {
+            #[cfg(any(feature = "lz4", feature = "zstd"))]
+            return crate::compression::send_compressed(
+                &self.extension,
+                frame.clone(),
+                self.compression,
+            );
+            #[cfg(not(any(feature = "lz4", feature = "zstd")))]
+            crate::frame::invoke_framed(&self.extension, &frame)
+        })
+    }
+
+    fn needs_frame(&self) -> bool {
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        if self.compression.is_some() {
+            return true;
+        }
+        !self.fields.is_empty()
+    }
+
+    fn with_retries<T>(&self, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        match &self.retry {
+            Some(policy) => policy.run(f),
+            None => f(),
+        }
+    }
+}
+
+/// An `Invocation` that encodes its request and decodes its response with a `Codec`.
+#[cfg(feature = "codec")]
+#[derive(Clone, Debug)]
+pub struct TypedInvocation<'a, C> {
+    invocation: Invocation<'a>,
+    codec: C,
+}
+
+#[cfg(feature = "codec")]
+impl<C: crate::Codec> TypedInvocation<'_, C> {
+    /// Encodes `request`, sends the call and decodes the response.
+    pub fn send<Req, Resp>(self, request: &Req) -> Result<Resp>
+    where
+        Req: serde::Serialize + ?Sized,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let payload = self.codec.encode(request)?;
+        let response = self.invocation.payload(payload).send()?;
+        self.codec.decode(&response)
+    }
+}
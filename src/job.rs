@@ -0,0 +1,337 @@
+//! Information about the job the guest is running as, and the channels it reports back to the
+//! scheduler through.
+//!
+//! `input` gives every job the same way to read what it was submitted with, instead of each
+//! entrypoint inventing its own convention for packing parameters into its input bytes.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::wire::Reader;
+use crate::{host, take_host_bytes, ExtensionErrorCode, Result, SdkError};
+
+/// Identifies the job the guest is running as and where.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JobMetadata {
+    /// The job, which stays the same across retries and reruns.
+    pub job_id: String,
+    /// This particular run of the job.
+    pub run_id: String,
+    /// The node the run was scheduled on.
+    pub node_id: String,
+    /// The tenant that submitted the job.
+    pub tenant: String,
+    pub submitted_at: SystemTime,
+    /// Labels attached to the job when it was submitted.
+    pub labels: BTreeMap<String, String>,
+}
+
+impl JobMetadata {
+    /// Decodes the blob `job_metadata` returns: length-prefixed job, run, node and tenant ids, a
+    /// u64 submission time in nanoseconds since the UNIX epoch, then a u32 label count followed by
+    /// that many length-prefixed key and value pairs.
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let job_id = reader.read_str()?.to_string();
+        let run_id = reader.read_str()?.to_string();
+        let node_id = reader.read_str()?.to_string();
+        let tenant = reader.read_str()?.to_string();
+        let submitted_at = UNIX_EPOCH + Duration::from_nanos(reader.read_u64()?);
+        let label_count = reader.read_u32()?;
+        let mut labels = BTreeMap::new();
+        for _ in 0..label_count {
+            let key = reader.read_str()?.to_string();
+            labels.insert(key, reader.read_str()?.to_string());
+        }
+        Ok(Self {
+            job_id,
+            run_id,
+            node_id,
+            tenant,
+            submitted_at,
+            labels,
+        })
+    }
+
+    /// Encodes the metadata in the layout `decode` reads, for the stand-in hosts.
+    #[cfg(any(feature = "mock-host", feature = "harness"))]
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut writer = crate::wire::Writer::new();
+        for id in [&self.job_id, &self.run_id, &self.node_id, &self.tenant] {
+            writer.write_str(id);
+        }
+        let submitted_at = self
+            .submitted_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        writer.write_u64(u64::try_from(submitted_at.as_nanos()).unwrap_or(u64::MAX));
+        writer.write_u32(self.labels.len() as u32);
+        for (key, value) in &self.labels {
+            writer.write_str(key);
+            writer.write_str(value);
+        }
+        writer.into_bytes()
+    }
+}
+
+/// Returns the metadata of the job the guest is running as. It doesn't change during a run, so
+/// it's only fetched from the host once.
+pub fn metadata() -> Result<JobMetadata> {
+    static METADATA: OnceLock<JobMetadata> = OnceLock::new();
+    if let Some(metadata) = METADATA.get() {
+        return Ok(metadata.clone());
+    }
+
+    let out_ptr = unsafe { host::job_metadata() };
+    if out_ptr < 0 {
+        return Err(ExtensionErrorCode::from(out_ptr as i32).into());
+    }
+    let metadata = JobMetadata::decode(&take_host_bytes(out_ptr as usize)?)?;
+    Ok(METADATA.get_or_init(|| metadata).clone())
+}
+
+/// The input the job was submitted with: a body plus named parameters.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JobInput {
+    params: BTreeMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl JobInput {
+    /// Decodes the blob `job_input` returns: a u32 parameter count followed by that many
+    /// length-prefixed name and value pairs, then the body, which is the rest of the blob.
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let param_count = reader.read_u32()?;
+        let mut params = BTreeMap::new();
+        for _ in 0..param_count {
+            let name = reader.read_str()?.to_string();
+            params.insert(name, reader.read_str()?.to_string());
+        }
+        Ok(Self {
+            params,
+            body: reader.remaining().to_vec(),
+        })
+    }
+
+    /// The raw body.
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// Decodes the body as JSON.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        crate::json::from_payload_json(&self.body)
+    }
+
+    /// The value of the named parameter, if the job was submitted with it.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Every parameter the job was submitted with.
+    pub fn params(&self) -> &BTreeMap<String, String> {
+        &self.params
+    }
+}
+
+/// Fetches the input the job was submitted with. Each call fetches a fresh copy from the host, so
+/// hold on to the result rather than calling this repeatedly.
+pub fn input() -> Result<JobInput> {
+    let out_ptr = unsafe { host::job_input() };
+    if out_ptr < 0 {
+        return Err(ExtensionErrorCode::from(out_ptr as i32).into());
+    }
+    JobInput::decode(&take_host_bytes(out_ptr as usize)?)
+}
+
+/// Set once the job has published its output itself, so the `#[serval::main]` return value
+/// doesn't replace it.
+static OUTPUT_SET: AtomicBool = AtomicBool::new(false);
+static COMPLETED: AtomicBool = AtomicBool::new(false);
+
+/// Publishes `output` as the job's result. It's visible to whoever is waiting on the job right
+/// away, even though the job keeps running. Publishing again replaces the earlier output.
+pub fn set_output(output: &[u8]) -> Result<()> {
+    let status = unsafe { host::job_set_output(output.as_ptr() as usize, output.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    OUTPUT_SET.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Like `set_output`, encoding `output` as JSON.
+#[cfg(feature = "json")]
+pub fn set_output_typed<T: serde::Serialize + ?Sized>(output: &T) -> Result<()> {
+    set_output(&crate::json::to_payload_json(output)?)
+}
+
+/// How a job ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+}
+
+/// Tells the scheduler the job is done, with `message` describing the outcome (it may be empty).
+/// The guest can keep running afterwards to clean up, but the job's output and status are final.
+/// `#[serval::main]` calls this when the entrypoint returns, unless the job already has.
+pub fn complete(status: JobStatus, message: &str) -> Result<()> {
+    let code = match status {
+        JobStatus::Succeeded => 0,
+        JobStatus::Failed => 1,
+    };
+    let result =
+        unsafe { host::job_complete(code, message.as_ptr() as usize, message.len() as u32) };
+    if result < 0 {
+        return Err(ExtensionErrorCode::from(result).into());
+    }
+    COMPLETED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Publishes a `#[serval::main]` entrypoint's return value and completes the job, skipping
+/// whichever of the two the job already did itself.
+pub(crate) fn finish(output: &[u8]) -> Result<()> {
+    if !OUTPUT_SET.load(Ordering::Relaxed) {
+        set_output(output)?;
+    }
+    if !COMPLETED.load(Ordering::Relaxed) {
+        complete(JobStatus::Succeeded, "")?;
+    }
+    Ok(())
+}
+
+/// Tells operators how far along the job is: `percent` is clamped to 0..=100, and `message` says
+/// what the job is doing at the moment (it may be empty). Progress is for display only, so report
+/// it as often as is useful but not on every iteration of a hot loop.
+pub fn report_progress(percent: f32, message: &str) -> Result<()> {
+    let percent = if percent.is_nan() {
+        0.0
+    } else {
+        percent.clamp(0.0, 100.0)
+    };
+    let status =
+        unsafe { host::job_progress(percent, message.as_ptr() as usize, message.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(())
+}
+
+/// Set once the host has reported the job as cancelled; cancellation can't be undone.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Forgets the output, completion and cancellation flags, so that the mock host can start each test
+/// from a fresh job.
+#[cfg(feature = "mock-host")]
+pub(crate) fn reset() {
+    OUTPUT_SET.store(false, Ordering::Relaxed);
+    COMPLETED.store(false, Ordering::Relaxed);
+    CANCELLED.store(false, Ordering::Relaxed);
+}
+
+/// Returns true if an operator has cancelled the job. A cancelled job should stop what it's doing,
+/// clean up and return; the SDK's own loops around streams, uploads and retries stop with
+/// `SdkError::Cancelled` once it is.
+pub fn is_cancelled() -> bool {
+    if CANCELLED.load(Ordering::Relaxed) {
+        return true;
+    }
+    let cancelled = unsafe { host::job_cancelled() } > 0;
+    if cancelled {
+        CANCELLED.store(true, Ordering::Relaxed);
+    }
+    cancelled
+}
+
+/// Fails with `SdkError::Cancelled` if the job has been cancelled.
+pub(crate) fn check_cancelled() -> Result<()> {
+    if is_cancelled() {
+        return Err(SdkError::Cancelled);
+    }
+    Ok(())
+}
+
+/// A flag for stopping a piece of work early, either because the guest decided to or because the
+/// job was cancelled. Clones share the same flag, so one can be handed to the work and another
+/// kept to cancel it with.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token and every clone of it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token or the job has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || is_cancelled()
+    }
+
+    /// Fails with `SdkError::Cancelled` if the token or the job has been cancelled, for use with
+    /// `?` at the points where the work can stop cleanly.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(SdkError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Tells the scheduler the job is alive and making progress, so that a slow job isn't mistaken
+/// for a hung one.
+pub fn heartbeat() -> Result<()> {
+    let status = unsafe { host::job_heartbeat() };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    LAST_HEARTBEAT.store(unsafe { host::monotonic_now() }, Ordering::Relaxed);
+    Ok(())
+}
+
+/// The interval for automatic heartbeats in nanoseconds, 0 if they're off.
+static AUTO_HEARTBEAT_NANOS: AtomicU64 = AtomicU64::new(0);
+/// When the last heartbeat was sent, on the host's monotonic clock.
+static LAST_HEARTBEAT: AtomicU64 = AtomicU64::new(0);
+
+/// Has the SDK send heartbeats on its own, at most once per `interval`, whenever it starts or
+/// finishes an extension call or moves a chunk of a stream. That keeps a job whose time is spent
+/// in long invocations visibly alive without it having to call `heartbeat` itself. `None` turns
+/// automatic heartbeats off, which is the default.
+pub fn set_auto_heartbeat(interval: Option<Duration>) {
+    let nanos = interval.map_or(0, |interval| {
+        u64::try_from(interval.as_nanos())
+            .unwrap_or(u64::MAX)
+            .max(1)
+    });
+    AUTO_HEARTBEAT_NANOS.store(nanos, Ordering::Relaxed);
+}
+
+/// Sends a heartbeat if automatic heartbeats are on and one is due.
+pub(crate) fn auto_heartbeat() {
+    let interval = AUTO_HEARTBEAT_NANOS.load(Ordering::Relaxed);
+    if interval == 0 {
+        return;
+    }
+    let now = unsafe { host::monotonic_now() };
+    if now.saturating_sub(LAST_HEARTBEAT.load(Ordering::Relaxed)) >= interval {
+        // A missed heartbeat isn't worth failing the call it's piggybacking on.
+        let _ = heartbeat();
+    }
+}
@@ -0,0 +1,53 @@
+//! Helpers for extensions that speak JSON.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::{invoke_with_codec, Json};
+use crate::{CodecError, Result, SdkError};
+
+/// Encodes a value as a JSON payload.
+pub fn to_payload_json<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    write_payload_json(value, &mut payload)?;
+    Ok(payload)
+}
+
+/// Like `to_payload_json`, but appends the payload to `buf`.
+pub fn write_payload_json<T: Serialize + ?Sized>(value: &T, buf: &mut Vec<u8>) -> Result<()> {
+    serde_json::to_writer(buf, value)
+        .map_err(|err| SdkError::Encode(CodecError::new(err.to_string())))
+}
+
+/// Decodes a JSON payload. If parsing fails, the error carries the byte offset at which the
+/// parser gave up.
+pub fn from_payload_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    serde_json::from_slice(bytes).map_err(|err| {
+        let offset = byte_offset(bytes, err.line(), err.column());
+        SdkError::Decode(CodecError::new(err.to_string()).with_offset(offset))
+    })
+}
+
+/// Invokes an extension with a JSON-encoded request and decodes its JSON response.
+pub fn invoke_json<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    invoke_with_codec(extension_name, &Json, request)
+}
+
+/// serde_json reports errors as a one-based line and column; turn that back into an offset into
+/// the payload.
+fn byte_offset(bytes: &[u8], line: usize, column: usize) -> usize {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        bytes
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte == b'\n')
+            .nth(line - 2)
+            .map_or(bytes.len(), |(i, _)| i + 1)
+    };
+    (line_start + column.saturating_sub(1)).min(bytes.len())
+}
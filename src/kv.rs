@@ -0,0 +1,245 @@
+//! A client for the platform's key-value store.
+//!
+//! ```ignore
+//! use serval::kv;
+//!
+//! kv::put("jobs/42/state", b"running")?;
+//! let state = kv::get("jobs/42/state")?;
+//! for entry in kv::scan("jobs/42/") {
+//!     let (key, value) = entry?;
+//! }
+//! ```
+//!
+//! Every write bumps the key's version. `put_if_version` only writes if the version is still the
+//! one the caller read, failing with `SdkError::Conflict` otherwise, which is enough to build
+//! read-modify-write cycles that don't lose concurrent updates.
+//!
+//! Requests are frames sent to the `EXTENSION` extension (or the one a `Store` names), with the
+//! operation in the frame header and a body laid out as follows, integers little-endian and
+//! strings length-prefixed:
+//!
+//! | operation | request body | response body |
+//! |---|---|---|
+//! | `get` | key | u64 version, value |
+//! | `put` | key, u8 condition, u64 version, length-prefixed value | u64 new version |
+//! | `delete` | key | empty |
+//! | `scan` | prefix, start after key, u32 limit, u8 keys only | u32 count, that many (key, length-prefixed value) pairs, u8 more |
+//!
+//! A `put` condition of 0 writes unconditionally; 1 writes only if the key's version equals the
+//! given version, with version 0 meaning the key must not exist yet. Missing keys are reported with
+//! `frame::status::NOT_FOUND` and failed conditions with `frame::status::CONFLICT`.
+
+use crate::service;
+use crate::wire::{Reader, Writer};
+use crate::{Result, SdkError};
+
+/// The extension the platform's key-value store is registered under.
+pub const EXTENSION: &str = "kv";
+
+/// How many entries `scan` and `list` fetch per call by default.
+pub const DEFAULT_PAGE_SIZE: u32 = 256;
+
+/// A value along with the version it was read at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub value: Vec<u8>,
+    pub version: u64,
+}
+
+/// A handle to a key-value store. The free functions in this module use the default one; create a
+/// `Store` to talk to a store registered under a different extension name.
+#[derive(Clone, Debug)]
+pub struct Store {
+    extension: String,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self::new(EXTENSION)
+    }
+}
+
+impl Store {
+    pub fn new(extension_name: impl Into<String>) -> Self {
+        Self {
+            extension: extension_name.into(),
+        }
+    }
+
+    /// The extension the store's requests go to.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Returns the value stored under `key`, or `None` if there isn't one.
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.get_entry(key)?.map(|entry| entry.value))
+    }
+
+    /// Like `get`, but also returns the version the value was read at, for `put_if_version`.
+    pub fn get_entry(&self, key: &str) -> Result<Option<Entry>> {
+        let mut writer = Writer::new();
+        writer.write_str(key);
+        let response = match service::call(&self.extension, "get", writer.into_bytes()) {
+            Err(SdkError::NotFound(_)) => return Ok(None),
+            response => response?,
+        };
+
+        let mut reader = Reader::new(&response);
+        let version = reader.read_u64()?;
+        Ok(Some(Entry {
+            value: reader.remaining().to_vec(),
+            version,
+        }))
+    }
+
+    /// Stores `value` under `key`, replacing any existing value, and returns its new version.
+    pub fn put(&self, key: &str, value: &[u8]) -> Result<u64> {
+        self.send_put(key, value, None)
+    }
+
+    /// Stores `value` under `key` only if the key is still at `version` (0 meaning it must not
+    /// exist yet), and returns its new version. Fails with `SdkError::Conflict` if it isn't.
+    pub fn put_if_version(&self, key: &str, value: &[u8], version: u64) -> Result<u64> {
+        self.send_put(key, value, Some(version))
+    }
+
+    fn send_put(&self, key: &str, value: &[u8], version: Option<u64>) -> Result<u64> {
+        let mut writer = Writer::new();
+        writer.write_str(key);
+        writer.write_u8(version.is_some() as u8);
+        writer.write_u64(version.unwrap_or(0));
+        writer.write_prefixed(value);
+        let response = service::call(&self.extension, "put", writer.into_bytes())?;
+        Reader::new(&response).read_u64()
+    }
+
+    /// Removes the value stored under `key`. Fails with `SdkError::NotFound` if there isn't one.
+    pub fn delete(&self, key: &str) -> Result<()> {
+        let mut writer = Writer::new();
+        writer.write_str(key);
+        service::call(&self.extension, "delete", writer.into_bytes())?;
+        Ok(())
+    }
+
+    /// Iterates over every key starting with `prefix` and its value, in key order. Entries are
+    /// fetched a page at a time as the iterator is consumed.
+    pub fn scan(&self, prefix: &str) -> Scan {
+        Scan::new(self.clone(), prefix, false)
+    }
+
+    /// Like `scan`, but only fetches the keys.
+    pub fn list(&self, prefix: &str) -> impl Iterator<Item = Result<String>> {
+        Scan::new(self.clone(), prefix, true).map(|entry| entry.map(|(key, _)| key))
+    }
+}
+
+/// Returns the value stored under `key` in the default store; see `Store::get`.
+pub fn get(key: &str) -> Result<Option<Vec<u8>>> {
+    Store::default().get(key)
+}
+
+/// See `Store::get_entry`.
+pub fn get_entry(key: &str) -> Result<Option<Entry>> {
+    Store::default().get_entry(key)
+}
+
+/// See `Store::put`.
+pub fn put(key: &str, value: &[u8]) -> Result<u64> {
+    Store::default().put(key, value)
+}
+
+/// See `Store::put_if_version`.
+pub fn put_if_version(key: &str, value: &[u8], version: u64) -> Result<u64> {
+    Store::default().put_if_version(key, value, version)
+}
+
+/// See `Store::delete`.
+pub fn delete(key: &str) -> Result<()> {
+    Store::default().delete(key)
+}
+
+/// See `Store::scan`.
+pub fn scan(prefix: &str) -> Scan {
+    Store::default().scan(prefix)
+}
+
+/// See `Store::list`.
+pub fn list(prefix: &str) -> impl Iterator<Item = Result<String>> {
+    Store::default().list(prefix)
+}
+
+/// The iterator returned by `scan`. Yields key/value pairs until the prefix is exhausted or a
+/// request fails, after which it stops.
+#[derive(Debug)]
+pub struct Scan {
+    store: Store,
+    prefix: String,
+    keys_only: bool,
+    page_size: u32,
+    /// The last key handed out, which the next page starts after.
+    cursor: String,
+    page: std::vec::IntoIter<(String, Vec<u8>)>,
+    more: bool,
+}
+
+impl Scan {
+    fn new(store: Store, prefix: &str, keys_only: bool) -> Self {
+        Self {
+            store,
+            prefix: prefix.to_string(),
+            keys_only,
+            page_size: DEFAULT_PAGE_SIZE,
+            cursor: String::new(),
+            page: Vec::new().into_iter(),
+            more: true,
+        }
+    }
+
+    /// Sets how many entries to fetch per call. Defaults to `DEFAULT_PAGE_SIZE`.
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    fn fetch_page(&mut self) -> Result<()> {
+        let mut writer = Writer::new();
+        writer.write_str(&self.prefix);
+        writer.write_str(&self.cursor);
+        writer.write_u32(self.page_size);
+        writer.write_u8(self.keys_only as u8);
+        let response = service::call(&self.store.extension, "scan", writer.into_bytes())?;
+
+        let mut reader = Reader::new(&response);
+        let count = reader.read_u32()?;
+        let mut page = Vec::new();
+        for _ in 0..count {
+            let key = reader.read_str()?.to_string();
+            let value = reader.read_prefixed()?.to_vec();
+            page.push((key, value));
+        }
+        self.more = reader.read_u8()? != 0 && !page.is_empty();
+        self.page = page.into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for Scan {
+    type Item = Result<(String, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, value)) = self.page.next() {
+                self.cursor.clone_from(&key);
+                return Some(Ok((key, value)));
+            }
+            if !self.more {
+                return None;
+            }
+            if let Err(err) = self.fetch_page() {
+                self.more = false;
+                return Some(Err(err));
+            }
+        }
+    }
+}
@@ -1,107 +1,624 @@
-use std::mem::size_of;
+// wasm64 guests are built with nightly, which still gates its intrinsics.
+#![cfg_attr(target_arch = "wasm64", feature(simd_wasm64))]
 
-// Declare all of the host functions we need
-#[link(wasm_import_module = "serval")]
-extern "C" {
-    #[link_name = "invoke_raw"]
-    fn invoke_raw(name_ptr: u32, name_len: u32, data_ptr: u32, data_len: u32) -> i32;
-}
+use std::sync::atomic::{compiler_fence, AtomicBool, Ordering};
+use std::time::Duration;
+
+pub mod abi;
+#[cfg(feature = "alloc-tracking")]
+mod alloc_tracking;
+#[cfg(any(feature = "dlmalloc", feature = "bump-allocator"))]
+pub mod allocator;
+mod arena;
+mod batch;
+#[cfg(feature = "bincode")]
+pub mod bincode;
+#[cfg(feature = "blobs")]
+pub mod blobs;
+#[cfg(feature = "build")]
+pub mod build;
+mod callback;
+pub mod caps;
+pub mod cassette;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+mod channel;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "component")]
+pub mod component;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub mod compression;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod content_type;
+mod entrypoint;
+pub mod env;
+pub mod envelope;
+mod error;
+#[cfg(feature = "exchange-memory")]
+pub mod exchange;
+mod executor;
+mod extension_ref;
+pub mod extensions;
+#[cfg(feature = "flatbuffers")]
+pub mod flatbuffers;
+pub mod frame;
+pub mod framing;
+pub mod fs;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+mod guest_error;
+mod handle;
+#[cfg(feature = "harness")]
+pub mod harness;
+pub mod history;
+mod host;
+mod host_bytes;
+pub mod http;
+#[cfg(any(feature = "inline-returns", feature = "harness"))]
+mod inline;
+mod invocation;
+pub mod job;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod kv;
+pub mod log;
+mod memory;
+pub mod metrics;
+#[cfg(feature = "mock-host")]
+pub mod mock;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod net;
+#[cfg(feature = "panic-report")]
+mod panic;
+pub mod permissions;
+mod pipeline;
+pub mod pool;
+#[cfg(feature = "postcard")]
+pub mod postcard;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "prost")]
+pub mod proto;
+pub mod pubsub;
+pub mod queue;
+pub mod rand;
+mod retry;
+pub mod secrets;
+mod service;
+pub mod sql;
+mod stream;
+pub mod time;
+mod trace_context;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+#[cfg(feature = "serde")]
+mod typed;
+pub mod vector;
+#[cfg(all(feature = "wasi-shim", target_os = "wasi"))]
+mod wasi;
+mod wire;
 
-#[derive(Clone, Debug)]
-pub enum ServalSDKError {
-    // Calling into invoke_raw failed with the given error code; at some point we may want to break
-    // the error code out into separate errors but this is fine for now.
-    InvokeExtensionError(i32),
+#[cfg(feature = "bincode")]
+pub use crate::bincode::invoke_bincode;
+#[cfg(feature = "flatbuffers")]
+pub use crate::flatbuffers::invoke_flatbuffer;
+#[cfg(feature = "postcard")]
+pub use crate::postcard::invoke_postcard;
+#[cfg(feature = "alloc-tracking")]
+pub use alloc_tracking::{
+    assert_no_leaks, memory_report, LiveAllocation, MemoryReport, MismatchedFree, Site,
+};
+pub use arena::{with_request_arena, RequestArena};
+pub use batch::invoke_batch;
+pub use callback::{serval_callback, Callback};
+#[cfg(feature = "cbor")]
+pub use cbor::invoke_cbor;
+pub use channel::Channel;
+#[cfg(feature = "codec")]
+pub use codec::{invoke_operation, invoke_with_codec, Codec};
+#[cfg(feature = "json")]
+pub use entrypoint::Typed;
+pub use error::{
+    last_error, CodecError, ExtensionErrorCode, InvocationError, LastError, Result, SdkError,
+};
+pub use executor::{
+    block_on, invoke_extension_async, join_all, select_all, InvokeFuture, JoinAll, SelectAll,
+};
+pub use extension_ref::{invoke, ExtensionRef};
+pub use guest_error::{report_error, GuestError};
+pub use handle::{start_invoke, wait_all, wait_any, InvocationHandle};
+pub use host_bytes::OwnedHostBytes;
+pub(crate) use host_bytes::{bytes_to_host, get_bytes_from_host, take_host_bytes};
+pub use invocation::Invocation;
+#[cfg(feature = "codec")]
+pub use invocation::TypedInvocation;
+#[cfg(feature = "json")]
+pub use json::invoke_json;
+pub use memory::{
+    memory_stats, report_memory_stats, set_report_memory_stats_at_exit, MemoryStats, PAGE_SIZE,
+};
+#[cfg(feature = "msgpack")]
+pub use msgpack::invoke_msgpack;
+#[cfg(feature = "panic-report")]
+pub use panic::install_panic_hook;
+pub use pipeline::Pipeline;
+#[cfg(feature = "prost")]
+pub use proto::invoke_proto;
+pub use retry::{invoke_extension_with_retry, RetryPolicy};
+#[cfg(all(feature = "macros", feature = "mock-host"))]
+pub use serval_macros::test;
+#[cfg(feature = "macros")]
+pub use serval_macros::{export, extension_client, main, manifest};
+/// The wasm intrinsics for the width of memory we're built for.
+#[cfg(target_arch = "wasm32")]
+pub(crate) use std::arch::wasm32 as arch;
+#[cfg(target_arch = "wasm64")]
+pub(crate) use std::arch::wasm64 as arch;
+pub use stream::{
+    invoke_chunked, invoke_streaming, InvocationWriter, ResponseStream, DEFAULT_SEGMENT_SIZE,
+    DEFAULT_WINDOW_SIZE,
+};
+pub use trace_context::{current_trace_id, with_trace_id};
+#[cfg(feature = "serde")]
+pub use typed::invoke_extension_typed;
+
+/// Support code for the macros in `serval-macros`. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::entrypoint::{
+        run_entrypoint, run_job, EntrypointOutput, FromInput, ENTRYPOINT_FAILED,
+    };
+    pub use crate::permissions::{encode as encode_manifest, encoded_len as manifest_len};
 }
 
 /// Invokes the extension with the give name, passing along an arbitrary blob of data. returns the
 /// data returned by the extension.
-pub fn invoke_extension(extension_name: String, data: &Vec<u8>) -> Result<Vec<u8>, ServalSDKError> {
-    let extension_name_bytes = extension_name.into_bytes();
-    let extension_name_ptr = extension_name_bytes.as_ptr() as u32;
+///
+/// Both arguments are borrowed, so anything from a `&'static str` and a byte array to a `String`
+/// and a `Vec<u8>` can be passed without cloning; the only allocation is the response.
+pub fn invoke_extension(
+    extension_name: impl AsRef<str>,
+    data: impl AsRef<[u8]>,
+) -> Result<Vec<u8>> {
+    invoke_bytes(extension_name.as_ref(), data.as_ref())
+}
+
+fn invoke_bytes(extension_name: &str, data: &[u8]) -> Result<Vec<u8>> {
+    job::auto_heartbeat();
 
-    let data_ptr = data.as_ptr() as u32;
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    if compression::compression().is_some() {
+        return compression::invoke_compressed(extension_name, data);
+    }
 
+    let extension_name_ptr = extension_name.as_ptr() as usize;
+
+    let data_ptr = data.as_ptr() as usize;
+
+    let started = history::start();
+    let invoke_raw = || {
+        let out_ptr = unsafe {
+            host::invoke_raw(
+                extension_name_ptr,
+                extension_name.len() as u32,
+                data_ptr,
+                data.len() as u32,
+            )
+        };
+        read_response(out_ptr, extension_name, data.len())
+    };
+    #[cfg(feature = "inline-returns")]
+    let response = inline::invoke(extension_name, data).unwrap_or_else(invoke_raw);
+    #[cfg(not(feature = "inline-returns"))]
+    let response = invoke_raw();
+    cassette::record(extension_name, data, &response);
+    history::record(started, extension_name, data.len(), &response);
+    response
+}
+
+/// Like `invoke_extension`, but hands back the host's response buffer itself instead of copying
+/// it into a `Vec`. The buffer is freed when the returned value is dropped.
+pub fn invoke_extension_owned(extension_name: &str, data: &[u8]) -> Result<OwnedHostBytes> {
+    let started = history::start();
     let out_ptr = unsafe {
-        invoke_raw(
-            extension_name_ptr,
-            extension_name_bytes.len() as u32,
-            data_ptr,
+        host::invoke_raw(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            data.as_ptr() as usize,
             data.len() as u32,
         )
     };
 
-    if out_ptr < 0 {
-        // A return value of 0 is used to signal that an error occurred.
-        // TODO: We should probably start returning a signed integer instead and use negative
-        // numbers to signal specific errors.
-        return Err(ServalSDKError::InvokeExtensionError(out_ptr));
+    let response = check_ptr(out_ptr, extension_name, data.len()).and_then(|ptr| {
+        take_host_bytes(ptr).map_err(|err| {
+            InvocationError::new(extension_name, data.len(), out_ptr as i32, err).into()
+        })
+    });
+    cassette::record(extension_name, data, &response);
+    history::record(started, extension_name, data.len(), &response);
+    response
+}
+
+/// Like `invoke_extension`, but returns `Ok(None)` if the extension isn't registered on this node
+/// so callers can degrade gracefully. Every other failure is still returned as an error.
+pub fn invoke_extension_opt(extension_name: &str, data: &[u8]) -> Result<Option<Vec<u8>>> {
+    match invoke_bytes(extension_name, data) {
+        Ok(response) => Ok(Some(response)),
+        Err(err) if *err.root() == SdkError::ExtensionNotFound => Ok(None),
+        Err(err) => Err(err),
     }
+}
+
+/// Like `invoke_extension`, but asks the host to give up on the call once `timeout` has elapsed,
+/// in which case `SdkError::TimedOut` is returned. Timeouts are passed to the host with millisecond
+/// precision; anything longer than `u32::MAX` milliseconds is treated as "no timeout".
+pub fn invoke_extension_with_timeout(
+    extension_name: &str,
+    data: &[u8],
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let timeout_ms = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+
+    let started = history::start();
+    let out_ptr = unsafe {
+        host::invoke_raw_with_timeout(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            data.as_ptr() as usize,
+            data.len() as u32,
+            timeout_ms,
+        )
+    };
 
-    get_bytes_from_host(out_ptr as usize)
+    let response = read_response(out_ptr, extension_name, data.len());
+    cassette::record(extension_name, data, &response);
+    history::record(started, extension_name, data.len(), &response);
+    response
+}
+
+/// Invokes an extension without waiting for a response. The host is told not to allocate a reply
+/// buffer, so this skips the response copy entirely; only whether the host accepted the call is
+/// reported back.
+pub fn invoke_extension_oneway(extension_name: &str, data: &[u8]) -> Result<()> {
+    let status = unsafe {
+        host::invoke_raw_oneway(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            data.as_ptr() as usize,
+            data.len() as u32,
+        )
+    };
+
+    check_status(status, extension_name, data.len())
+}
+
+/// Turns the value returned by one of the invoke host functions into the response bytes. Any
+/// error is wrapped with the details of the call that produced it.
+fn read_response(out_ptr: isize, extension_name: &str, payload_len: usize) -> Result<Vec<u8>> {
+    let ptr = check_ptr(out_ptr, extension_name, payload_len)?;
+    get_bytes_from_host(ptr).map_err(|err| {
+        InvocationError::new(extension_name, payload_len, out_ptr as i32, err).into()
+    })
+}
+
+/// `check_status` for the imports that return a pointer on success, passing the pointer on. Their
+/// failures are the same i32 codes whatever the pointer width.
+pub(crate) fn check_ptr(out_ptr: isize, extension_name: &str, payload_len: usize) -> Result<usize> {
+    let status = if out_ptr < 0 {
+        i32::try_from(out_ptr).unwrap_or(i32::MIN)
+    } else {
+        0
+    };
+    check_status(status, extension_name, payload_len)?;
+    Ok(out_ptr as usize)
+}
+
+/// Returns an error carrying the details of the call if the host reported a failure. Every host
+/// call made for an invocation passes through here, which makes it the place to send automatic
+/// heartbeats from.
+pub(crate) fn check_status(status: i32, extension_name: &str, payload_len: usize) -> Result<()> {
+    job::auto_heartbeat();
+    if status < 0 {
+        // Negative return values are used to signal that an error occurred; see
+        // ExtensionErrorCode for the table of codes shared with the host.
+        let err = ExtensionErrorCode::from(status).into();
+        return Err(InvocationError::new(extension_name, payload_len, status, err).into());
+    }
+    Ok(())
 }
 
 /// Allocate memory into the module's linear memory and return the offset to the start of the block.
 /// Source: https://radu-matei.com/blog/practical-guide-to-wasm-memory/#exchanging-strings-between-modules-and-runtimes
+///
+/// Offset 0 is reserved to mean "allocation failed": Rust's allocator never hands out a null
+/// pointer for a successful allocation, so instead of aborting when it runs out of memory we return
+/// null and let the caller report `ExtensionErrorCode::AllocationFailed`. Zero-length requests get
+/// a dangling but non-null pointer, the same one an empty `Vec` would use.
 #[no_mangle]
 pub fn alloc(len: usize) -> *mut u8 {
-    // create a new mutable buffer with capacity `len`
-    let mut buf = Vec::with_capacity(len);
-    // take a mutable pointer to the buffer
-    let ptr = buf.as_mut_ptr();
-    // take ownership of the memory block and ensure that its destructor is not called when the
-    // object goes out of scope at the end of the function
-    std::mem::forget(buf);
-    // todo: ensure the pointer doesn't happen to be at offset 0, since that is used to signal an error
-    // return the pointer so the runtime can write data at this offset
+    let ptr = allocate(len);
+    #[cfg(feature = "alloc-tracking")]
+    alloc_tracking::record_alloc(ptr, len, None);
+    ptr
+}
+
+/// Like `alloc`, but the block is filled with zeroes.
+#[no_mangle]
+pub fn alloc_zeroed(len: usize) -> *mut u8 {
+    let ptr = alloc(len);
+    if !ptr.is_null() {
+        // Blocks can come out of the buffer pool with a previous buffer's contents.
+        unsafe { std::ptr::write_bytes(ptr, 0, len) };
+    }
+    ptr
+}
+
+fn allocate(len: usize) -> *mut u8 {
+    if len == 0 {
+        return std::ptr::NonNull::dangling().as_ptr();
+    }
+    let ptr = pool::allocate(len);
+    if !ptr.is_null() {
+        memory::record_alloc(len);
+    }
+    ptr
+}
+
+/// `alloc` for blocks the SDK allocates itself, attributed to the caller when allocations are
+/// tracked.
+#[track_caller]
+pub(crate) fn guest_alloc(len: usize) -> *mut u8 {
+    let ptr = allocate(len);
+    #[cfg(feature = "alloc-tracking")]
+    alloc_tracking::record_alloc(ptr, len, Some(std::panic::Location::caller()));
     ptr
 }
 
-/// Deallocates a chunk of memory that was originally allocated with our `alloc` function.
+/// Deallocates a chunk of memory that was originally allocated with our `alloc` function. The
+/// block goes back to the buffer pool (see `pool`) if there's room for it.
 /// Source: https://radu-matei.com/blog/practical-guide-to-wasm-memory/#exchanging-strings-between-modules-and-runtimes
 /// # Safety
-/// See the docs on [Vec#from_raw_parts](https://doc.rust-lang.org/std/vec/struct.Vec.html#method.from_raw_parts)
+/// `ptr` must have come from `alloc` called with `size`, and not have been freed already.
 #[no_mangle]
 pub unsafe fn dealloc(ptr: *mut u8, size: usize) {
-    let data = Vec::from_raw_parts(ptr, size, size);
-
-    std::mem::drop(data);
-}
-
-/// Retrieves a blob of bytes that the host environment is trying to pass to us. Since we can only
-/// communicate by passing around single numbers, the way the Serval host envioronment works is by
-/// asking us (the guest) to allocate N + 4 bytes of memory, where N is the number of bytes of data
-/// that they're trying to send us. The host writes N as a u32 into the first 4 bytes of the memory
-/// range. When we receive a pointer, we read a u32 from it to figure out how many bytes of data to
-/// read, read the data, and then clean up the entire memory allocation afterwards.
-fn get_bytes_from_host(ptr: usize) -> Result<Vec<u8>, ServalSDKError> {
-    // TODO: figure out how to make this unsafe stuff sufficiently safe to sleep at night.
-
-    // ptr points to a u32, followed by N bytes of data intended for us. That first u32 tells us
-    // what the value of N is.
-    let mut len_buf = [0u8; size_of::<i32>()];
-    let num_bytes = unsafe {
-        let ptr = &*(ptr as *const u8);
-        std::ptr::copy(ptr, len_buf.as_mut_ptr(), size_of::<u32>());
-        u32::from_le_bytes(len_buf)
-    };
+    #[cfg(feature = "alloc-tracking")]
+    alloc_tracking::record_dealloc(ptr, size, None);
+    deallocate(ptr, size);
+}
 
-    // Now that we know how many bytes of data there are, we can read 'em into a buffer
-    let bytes: Vec<u8> = unsafe {
-        let mut buf = vec![0; num_bytes as usize];
-        let ptr = &*((ptr + size_of::<u32>()) as *const u8);
-        std::ptr::copy(ptr, buf.as_mut_ptr(), num_bytes as usize);
-        buf
-    };
+unsafe fn deallocate(ptr: *mut u8, size: usize) {
+    if size > 0 {
+        memory::record_free(size);
+        scrub(ptr, size);
+        pool::release(ptr, size);
+    }
+}
 
-    // The block of memory at ptr was allocated by the host calling into our alloc function; now
-    // that we have read the data they were trying to pass to us, we can clean up that temporary
-    // allocation.
-    let alloc_size = size_of::<u32>() + num_bytes as usize;
-    unsafe {
-        let ptr = ptr as *mut u8;
-        dealloc(ptr, alloc_size);
-    };
+static SCRUB_ON_FREE: AtomicBool = AtomicBool::new(false);
+
+/// Turns on zeroing every block freed through `dealloc` (and the other deallocation exports) before
+/// it's released, for guests handling secrets that shouldn't linger in linear memory after a call.
+/// Off by default, since it costs a write per freed byte.
+///
+/// Buffers the SDK hands out as a `Vec` (e.g. from `invoke_extension`) belong to the global
+/// allocator and aren't covered; use `invoke_extension_owned` to keep secret responses in
+/// `OwnedHostBytes`, which are scrubbed when dropped.
+pub fn set_scrub_on_free(enabled: bool) {
+    SCRUB_ON_FREE.store(enabled, Ordering::Relaxed);
+}
+
+/// Zeroes `len` bytes at `ptr` if scrubbing is on, in a way the compiler can't optimize away.
+///
+/// # Safety
+/// `ptr` must be valid for `len` bytes of writes.
+pub(crate) unsafe fn scrub(ptr: *mut u8, len: usize) {
+    if SCRUB_ON_FREE.load(Ordering::Relaxed) {
+        zero(ptr, len);
+    }
+}
+
+/// Zeroes `len` bytes at `ptr` regardless of the scrubbing mode, in a way the compiler can't
+/// optimize away.
+///
+/// # Safety
+/// `ptr` must be valid for `len` bytes of writes.
+pub(crate) unsafe fn zero(ptr: *mut u8, len: usize) {
+    for i in 0..len {
+        ptr.add(i).write_volatile(0);
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// `dealloc` for blocks the SDK frees itself, attributed to the caller when allocations are
+/// tracked.
+/// # Safety
+/// Same as `dealloc`.
+#[track_caller]
+pub(crate) unsafe fn guest_dealloc(ptr: *mut u8, size: usize) {
+    #[cfg(feature = "alloc-tracking")]
+    alloc_tracking::record_dealloc(ptr, size, Some(std::panic::Location::caller()));
+    deallocate(ptr, size);
+}
+
+/// Like `alloc`, but the block starts at a multiple of `align`, for data such as flatbuffers or
+/// Arrow arrays that must be read in place with 8- or 16-byte alignment. Returns 0 if `align`
+/// isn't a power of two or the allocation fails. Blocks must be freed with `dealloc_aligned`, not
+/// `dealloc`.
+#[no_mangle]
+pub fn alloc_aligned(len: usize, align: usize) -> *mut u8 {
+    match std::alloc::Layout::from_size_align(len, align) {
+        // A zero-length block is never read or written; any suitably aligned non-null address will
+        // do.
+        Ok(_) if len == 0 => align as *mut u8,
+        Ok(layout) => {
+            let ptr = unsafe { std::alloc::alloc(layout) };
+            #[cfg(feature = "alloc-tracking")]
+            alloc_tracking::record_alloc(ptr, len, None);
+            if !ptr.is_null() {
+                memory::record_alloc(len);
+            }
+            ptr
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a block allocated with `alloc_aligned`.
+/// # Safety
+/// `ptr` must have come from `alloc_aligned` with the same `len` and `align`, and not have been
+/// freed already.
+#[no_mangle]
+pub unsafe fn dealloc_aligned(ptr: *mut u8, len: usize, align: usize) {
+    if len == 0 {
+        return;
+    }
+    if let Ok(layout) = std::alloc::Layout::from_size_align(len, align) {
+        #[cfg(feature = "alloc-tracking")]
+        alloc_tracking::record_dealloc(ptr, len, None);
+        memory::record_free(len);
+        scrub(ptr, len);
+        std::alloc::dealloc(ptr, layout);
+    }
+}
+
+/// Resizes a block allocated with our `alloc` from `old_len` to `new_len` bytes, so the host can
+/// grow a buffer it underestimated the size of instead of allocating a second one and copying the
+/// data over. The first `min(old_len, new_len)` bytes are preserved. Returns the new offset of the
+/// block, which may or may not have moved, or 0 if it couldn't be resized, in which case the
+/// original block is left untouched and must still be freed.
+///
+/// A null or zero-length `ptr` behaves like `alloc(new_len)`; resizing to zero frees the block.
+///
+/// This is exported as `serval_realloc`, since a plain `realloc` symbol would collide with the C
+/// library's on WASI targets. Within the guest, `OwnedHostBytes::resize` is the safe way to grow a
+/// buffer the host passed in.
+///
+/// # Safety
+/// `ptr` must be null or a live block from our `alloc` (or a previous resize) of exactly `old_len`
+/// bytes. After a successful resize only the returned pointer may be used.
+#[export_name = "serval_realloc"]
+pub unsafe fn realloc(ptr: *mut u8, old_len: usize, new_len: usize) -> *mut u8 {
+    if ptr.is_null() || old_len == 0 {
+        return alloc(new_len);
+    }
+    if new_len == 0 {
+        dealloc(ptr, old_len);
+        return std::ptr::NonNull::dangling().as_ptr();
+    }
+    if pool::block_size(old_len) == pool::block_size(new_len) {
+        // Both sizes round to the same block, so it already has room.
+        #[cfg(feature = "alloc-tracking")]
+        {
+            alloc_tracking::record_dealloc(ptr, old_len, None);
+            alloc_tracking::record_alloc(ptr, new_len, None);
+        }
+        memory::record_free(old_len);
+        memory::record_alloc(new_len);
+        return ptr;
+    }
+    // Blocks this big never go through the pool, so the allocator can often grow them in place.
+    // With scrubbing on they're copied instead, so the old block is zeroed when it's freed.
+    if old_len > pool::MAX_POOLED_SIZE
+        && new_len > pool::MAX_POOLED_SIZE
+        && !SCRUB_ON_FREE.load(Ordering::Relaxed)
+    {
+        if std::alloc::Layout::array::<u8>(new_len).is_err() {
+            return std::ptr::null_mut();
+        }
+        let layout = std::alloc::Layout::array::<u8>(pool::block_size(old_len))
+            .expect("the block was allocated with this layout");
+        let new_ptr = std::alloc::realloc(ptr, layout, pool::block_size(new_len));
+        if !new_ptr.is_null() {
+            #[cfg(feature = "alloc-tracking")]
+            {
+                alloc_tracking::record_dealloc(ptr, old_len, None);
+                alloc_tracking::record_alloc(new_ptr, new_len, None);
+            }
+            memory::record_free(old_len);
+            memory::record_alloc(new_len);
+        }
+        return new_ptr;
+    }
+    let new_ptr = alloc(new_len);
+    if !new_ptr.is_null() {
+        std::ptr::copy_nonoverlapping(ptr, new_ptr, old_len.min(new_len));
+        dealloc(ptr, old_len);
+    }
+    new_ptr
+}
 
-    Ok(bytes)
+#[cfg(test)]
+mod tests {
+    use super::{alloc, alloc_zeroed, bytes_to_host, dealloc, get_bytes_from_host, realloc};
+    use super::{take_host_bytes, OwnedHostBytes, SdkError};
+
+    #[test]
+    fn zero_length_alloc_is_dangling_but_not_null() {
+        let ptr = alloc(0);
+        assert!(!ptr.is_null());
+        assert_eq!(ptr, std::ptr::NonNull::<u8>::dangling().as_ptr());
+        // Freeing it is a no-op, like freeing an empty `Vec`.
+        unsafe { dealloc(ptr, 0) };
+    }
+
+    #[test]
+    fn alloc_returns_null_when_allocation_fails() {
+        assert!(alloc(usize::MAX).is_null());
+        assert!(alloc(isize::MAX as usize + 1).is_null());
+        assert!(alloc_zeroed(usize::MAX).is_null());
+    }
+
+    #[test]
+    fn get_bytes_from_host_reports_a_null_pointer_as_allocation_failure() {
+        assert_eq!(get_bytes_from_host(0), Err(SdkError::AllocationFailed));
+        assert_eq!(
+            take_host_bytes(0).map(OwnedHostBytes::into_vec),
+            Err(SdkError::AllocationFailed)
+        );
+    }
+
+    #[test]
+    fn realloc_preserves_data_across_the_pool_limit() {
+        let big = crate::pool::MAX_POOLED_SIZE + 1;
+        for (old_len, new_len) in [(16, 100), (100, big), (big, 3 * big), (3 * big, 200)] {
+            let ptr = alloc(old_len);
+            unsafe {
+                std::ptr::write_bytes(ptr, 0xab, old_len);
+                let ptr = realloc(ptr, old_len, new_len);
+                assert!(!ptr.is_null());
+                let kept = std::slice::from_raw_parts(ptr, old_len.min(new_len));
+                assert!(kept.iter().all(|byte| *byte == 0xab));
+                dealloc(ptr, new_len);
+            }
+        }
+    }
+
+    #[test]
+    fn resizing_host_bytes_keeps_the_data_and_zeroes_the_rest() {
+        let big = crate::pool::MAX_POOLED_SIZE + 1;
+        // Safety: `bytes_to_host` hands over a length-prefixed block from our `alloc`.
+        let mut bytes = unsafe { OwnedHostBytes::from_host(bytes_to_host(b"data")) };
+        bytes.resize(10).unwrap();
+        assert_eq!(&*bytes, b"data\0\0\0\0\0\0");
+        bytes[4..].copy_from_slice(b" grown");
+        for new_len in [big, 3 * big, 200] {
+            bytes.resize(new_len).unwrap();
+            assert_eq!(bytes.len(), new_len);
+            assert_eq!(&bytes[..10], b"data grown");
+            assert!(bytes[10..].iter().all(|byte| *byte == 0));
+        }
+        bytes.resize(4).unwrap();
+        assert_eq!(bytes.into_vec(), b"data");
+    }
+
+    #[test]
+    fn resizing_host_bytes_beyond_what_a_frame_holds_leaves_them_alone() {
+        // Safety: as above.
+        let mut bytes = unsafe { OwnedHostBytes::from_host(bytes_to_host(b"kept")) };
+        assert_eq!(bytes.resize(usize::MAX), Err(SdkError::AllocationFailed));
+        assert_eq!(&*bytes, b"kept");
+    }
 }
@@ -0,0 +1,140 @@
+//! Diagnostics for the host's logs. Each call hands the message to the host along with its level;
+//! what happens to it from there (filtering, where it's written) is up to the host.
+//!
+//! ```ignore
+//! serval::log::info("resizing image");
+//! serval::log::warn(&format!("falling back to {codec}"));
+//! ```
+//!
+//! With the `log` feature, `ServalLogger` forwards records from the `log` crate's macros here too,
+//! so libraries that already use `log::info!` and friends work unchanged.
+
+use std::fmt;
+
+use crate::host;
+
+/// How severe a log message is, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u32)]
+pub enum Level {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        })
+    }
+}
+
+/// Logs `message` at `level`. Logging never fails; a message the host can't take is dropped.
+pub fn log(level: Level, message: &str) {
+    unsafe {
+        host::log_raw(
+            level as u32,
+            message.as_ptr() as usize,
+            message.len() as u32,
+        )
+    };
+}
+
+pub fn trace(message: &str) {
+    log(Level::Trace, message);
+}
+
+pub fn debug(message: &str) {
+    log(Level::Debug, message);
+}
+
+pub fn info(message: &str) {
+    log(Level::Info, message);
+}
+
+pub fn warn(message: &str) {
+    log(Level::Warn, message);
+}
+
+pub fn error(message: &str) {
+    log(Level::Error, message);
+}
+
+/// A `log::Log` implementation that hands every enabled record to the host, prefixed with its
+/// target.
+#[cfg(feature = "log")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ServalLogger;
+
+#[cfg(feature = "log")]
+static LOGGER: ServalLogger = ServalLogger;
+
+/// Installs `ServalLogger` as the `log` crate's logger, letting records at every level through.
+/// Fails if another logger has already been installed.
+#[cfg(feature = "log")]
+pub fn init() -> Result<(), ::log::SetLoggerError> {
+    init_with_max_level(::log::LevelFilter::Trace)
+}
+
+/// Like `init`, but drops records less severe than `max_level` before they reach the host. The
+/// level can be changed later with `::log::set_max_level`.
+#[cfg(feature = "log")]
+pub fn init_with_max_level(max_level: ::log::LevelFilter) -> Result<(), ::log::SetLoggerError> {
+    ::log::set_logger(&LOGGER)?;
+    ::log::set_max_level(max_level);
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+impl From<::tracing::Level> for Level {
+    fn from(level: ::tracing::Level) -> Self {
+        match level {
+            ::tracing::Level::TRACE => Level::Trace,
+            ::tracing::Level::DEBUG => Level::Debug,
+            ::tracing::Level::INFO => Level::Info,
+            ::tracing::Level::WARN => Level::Warn,
+            ::tracing::Level::ERROR => Level::Error,
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<::log::Level> for Level {
+    fn from(level: ::log::Level) -> Self {
+        match level {
+            ::log::Level::Trace => Level::Trace,
+            ::log::Level::Debug => Level::Debug,
+            ::log::Level::Info => Level::Info,
+            ::log::Level::Warn => Level::Warn,
+            ::log::Level::Error => Level::Error,
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+impl ::log::Log for ServalLogger {
+    fn enabled(&self, metadata: &::log::Metadata<'_>) -> bool {
+        metadata.level() <= ::log::max_level()
+    }
+
+    fn log(&self, record: &::log::Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = match (record.target(), record.args().as_str()) {
+            ("", Some(message)) => return log(record.level().into(), message),
+            ("", None) => record.args().to_string(),
+            (target, _) => format!("{target}: {}", record.args()),
+        };
+        log(record.level().into(), &message);
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,99 @@
+//! Statistics about the guest's memory use, for right-sizing the memory limits of each job class.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::wire::Writer;
+use crate::{host, ExtensionErrorCode, Result};
+
+static LIVE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static REPORT_AT_EXIT: AtomicBool = AtomicBool::new(false);
+
+/// The size of a page of wasm linear memory.
+pub const PAGE_SIZE: usize = 64 * 1024;
+
+/// A snapshot of the guest's memory use; see `memory_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The size of linear memory in pages of `PAGE_SIZE` bytes. Always 0 outside of wasm.
+    pub memory_pages: usize,
+    /// Blocks handed out by `alloc` and its siblings that haven't been freed yet.
+    pub live_allocations: usize,
+    /// The total size of those blocks, as requested.
+    pub live_bytes: usize,
+    /// The highest `live_bytes` has been.
+    pub peak_bytes: usize,
+    /// Memory held by the buffer pool for reuse; see `crate::pool`.
+    pub pooled_bytes: usize,
+}
+
+/// Returns the current memory statistics. The allocation counts only cover the buffers exchanged
+/// with the host, not everything the guest allocates.
+pub fn memory_stats() -> MemoryStats {
+    MemoryStats {
+        memory_pages: memory_pages(),
+        live_allocations: LIVE_ALLOCATIONS.load(Ordering::Relaxed),
+        live_bytes: LIVE_BYTES.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        pooled_bytes: crate::pool::pooled_bytes(),
+    }
+}
+
+/// Sends the current memory statistics to the host.
+pub fn report_memory_stats() -> Result<()> {
+    let stats = memory_stats();
+    let mut writer = Writer::new();
+    for value in [
+        stats.memory_pages,
+        stats.live_allocations,
+        stats.live_bytes,
+        stats.peak_bytes,
+        stats.pooled_bytes,
+    ] {
+        writer.write_u32(u32::try_from(value).unwrap_or(u32::MAX));
+    }
+    let encoded = writer.into_bytes();
+
+    let status =
+        unsafe { host::report_memory_stats(encoded.as_ptr() as usize, encoded.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(())
+}
+
+/// Makes the job's main entrypoint report memory statistics to the host once it has produced its
+/// output. Off by default.
+pub fn set_report_memory_stats_at_exit(enabled: bool) {
+    REPORT_AT_EXIT.store(enabled, Ordering::Relaxed);
+}
+
+/// Called by the entrypoint glue when a job is done.
+pub(crate) fn at_exit() {
+    if REPORT_AT_EXIT.load(Ordering::Relaxed) {
+        // The job's own result matters more than the statistics, so a failure here is dropped.
+        let _ = report_memory_stats();
+    }
+}
+
+pub(crate) fn record_alloc(len: usize) {
+    LIVE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    let live = LIVE_BYTES.fetch_add(len, Ordering::Relaxed) + len;
+    PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+}
+
+pub(crate) fn record_free(len: usize) {
+    LIVE_ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+    LIVE_BYTES.fetch_sub(len, Ordering::Relaxed);
+}
+
+#[cfg(target_family = "wasm")]
+fn memory_pages() -> usize {
+    crate::arch::memory_size(0)
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn memory_pages() -> usize {
+    0
+}
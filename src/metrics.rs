@@ -0,0 +1,364 @@
+//! Counters, gauges and histograms reported to the host, so a job's numbers show up in the
+//! platform's dashboards.
+//!
+//! ```ignore
+//! use serval::metrics::{Counter, Histogram};
+//!
+//! let processed = Counter::with_labels("records_processed", &[("source", "s3")]);
+//! let latency = Histogram::new("fetch_seconds", &[0.01, 0.1, 1.0]);
+//! processed.increment(batch.len() as u64);
+//! latency.record(started.elapsed().as_secs_f64());
+//! ```
+//!
+//! Updates are aggregated in the guest and only sent to the host when flushed: on demand with
+//! `flush`, every so often if `set_flush_interval` is used, and when the job's main entrypoint
+//! returns.
+//! Handles with the same name and labels share the same metric, so they can be created wherever
+//! they're needed. A metric can have at most `u16::MAX` labels; one registered with more is
+//! never reported, and a warning is logged when it's registered.
+//!
+//! A flush hands the host a u32 metric count followed by that many metrics, each a kind byte (see
+//! `kinds`), the length-prefixed name, a u16 label count and that many length-prefixed key and
+//! value pairs, and then the value. Integers are little-endian and floats are sent as the
+//! little-endian bits of an f64. Counters send the u64 amount they grew by since the last flush and
+//! gauges their current f64 value. Histograms send a u32 bucket count and that many pairs of an f64
+//! upper bound and the u64 number of values recorded in that bucket since the last flush, then the
+//! u64 number and f64 sum of all those values; the last bucket's bound is infinity.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::wire::Writer;
+use crate::{host, ExtensionErrorCode, Result};
+
+/// The kind bytes metrics are sent with.
+pub mod kinds {
+    pub const COUNTER: u8 = 0;
+    pub const GAUGE: u8 = 1;
+    pub const HISTOGRAM: u8 = 2;
+}
+
+/// The bucket bounds `Histogram::new` is commonly given, for latencies in seconds.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    metrics: Vec::new(),
+    index: BTreeMap::new(),
+});
+
+/// The flush interval in nanoseconds, 0 if periodic flushing is off.
+static FLUSH_INTERVAL_NANOS: AtomicU64 = AtomicU64::new(0);
+/// When metrics were last flushed, on the host's monotonic clock.
+static LAST_FLUSH: AtomicU64 = AtomicU64::new(0);
+
+type Key = (String, Vec<(String, String)>);
+
+struct Registry {
+    metrics: Vec<Metric>,
+    index: BTreeMap<Key, usize>,
+}
+
+struct Metric {
+    kind: u8,
+    key: Key,
+    value: Value,
+    /// Whether the metric has been updated since the last flush.
+    dirty: bool,
+    /// False for metrics with more labels than a flush can carry, which are never sent.
+    reportable: bool,
+}
+
+enum Value {
+    Counter(u64),
+    Gauge(f64),
+    Histogram(HistogramState),
+}
+
+struct HistogramState {
+    /// Upper bounds of every bucket but the last, in increasing order.
+    bounds: Vec<f64>,
+    /// One count per bound, plus one for everything above the last bound.
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Registry {
+    /// Returns the index of the metric with the given key, registering it with `value` if it's
+    /// new.
+    fn register(
+        &mut self,
+        kind: u8,
+        name: &str,
+        labels: &[(&str, &str)],
+        value: impl FnOnce() -> Value,
+    ) -> usize {
+        let mut labels: Vec<(String, String)> = labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        labels.sort();
+        let key = (name.to_string(), labels);
+        if let Some(&id) = self.index.get(&key) {
+            return id;
+        }
+        let reportable = key.1.len() <= u16::MAX as usize;
+        if !reportable {
+            crate::log::warn(&format!(
+                "metric {name} has more than {} labels and won't be reported",
+                u16::MAX
+            ));
+        }
+        let id = self.metrics.len();
+        self.metrics.push(Metric {
+            kind,
+            key: key.clone(),
+            value: value(),
+            dirty: false,
+            reportable,
+        });
+        self.index.insert(key, id);
+        id
+    }
+}
+
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+    let mut registry = REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut registry)
+}
+
+/// Applies an update to a metric, then flushes if periodic flushing is due.
+fn update(id: usize, f: impl FnOnce(&mut Value)) {
+    with_registry(|registry| {
+        let metric = &mut registry.metrics[id];
+        f(&mut metric.value);
+        metric.dirty = metric.reportable;
+    });
+    maybe_flush();
+}
+
+/// A count that only goes up, such as requests handled.
+#[derive(Clone, Copy, Debug)]
+pub struct Counter {
+    id: usize,
+}
+
+impl Counter {
+    pub fn new(name: &str) -> Self {
+        Self::with_labels(name, &[])
+    }
+
+    pub fn with_labels(name: &str, labels: &[(&str, &str)]) -> Self {
+        let id = with_registry(|registry| {
+            registry.register(kinds::COUNTER, name, labels, || Value::Counter(0))
+        });
+        Self { id }
+    }
+
+    pub fn increment(&self, by: u64) {
+        update(self.id, |value| {
+            if let Value::Counter(delta) = value {
+                *delta = delta.saturating_add(by);
+            }
+        });
+    }
+}
+
+/// A value that goes up and down, such as the size of a queue.
+#[derive(Clone, Copy, Debug)]
+pub struct Gauge {
+    id: usize,
+}
+
+impl Gauge {
+    pub fn new(name: &str) -> Self {
+        Self::with_labels(name, &[])
+    }
+
+    pub fn with_labels(name: &str, labels: &[(&str, &str)]) -> Self {
+        let id = with_registry(|registry| {
+            registry.register(kinds::GAUGE, name, labels, || Value::Gauge(0.0))
+        });
+        Self { id }
+    }
+
+    pub fn set(&self, to: f64) {
+        update(self.id, |value| {
+            if let Value::Gauge(current) = value {
+                *current = to;
+            }
+        });
+    }
+
+    /// Adds `by`, which may be negative, to the gauge.
+    pub fn add(&self, by: f64) {
+        update(self.id, |value| {
+            if let Value::Gauge(current) = value {
+                *current += by;
+            }
+        });
+    }
+}
+
+/// A distribution of values, such as request latencies, counted into buckets.
+#[derive(Clone, Copy, Debug)]
+pub struct Histogram {
+    id: usize,
+}
+
+impl Histogram {
+    /// Registers a histogram with the given bucket upper bounds; see `DEFAULT_BUCKETS`. A
+    /// histogram that's already registered under the same name and labels keeps its buckets.
+    pub fn new(name: &str, buckets: &[f64]) -> Self {
+        Self::with_labels(name, &[], buckets)
+    }
+
+    pub fn with_labels(name: &str, labels: &[(&str, &str)], buckets: &[f64]) -> Self {
+        let id = with_registry(|registry| {
+            registry.register(kinds::HISTOGRAM, name, labels, || {
+                let mut bounds: Vec<f64> = buckets
+                    .iter()
+                    .copied()
+                    .filter(|bound| bound.is_finite())
+                    .collect();
+                bounds.sort_by(f64::total_cmp);
+                bounds.dedup();
+                Value::Histogram(HistogramState {
+                    counts: vec![0; bounds.len() + 1],
+                    bounds,
+                    count: 0,
+                    sum: 0.0,
+                })
+            })
+        });
+        Self { id }
+    }
+
+    pub fn record(&self, sample: f64) {
+        update(self.id, |value| {
+            if let Value::Histogram(histogram) = value {
+                let bucket = histogram.bounds.partition_point(|bound| *bound < sample);
+                histogram.counts[bucket] += 1;
+                histogram.count += 1;
+                histogram.sum += sample;
+            }
+        });
+    }
+}
+
+/// Sends every metric updated since the last flush to the host. If the host can't take them,
+/// the updates are kept for the next flush.
+pub fn flush() -> Result<()> {
+    with_registry(|registry| {
+        let updated: Vec<&Metric> = registry.metrics.iter().filter(|m| m.dirty).collect();
+        if updated.is_empty() {
+            return Ok(());
+        }
+
+        let mut writer = Writer::new();
+        writer.write_u32(updated.len() as u32);
+        for metric in updated {
+            write_metric(&mut writer, metric);
+        }
+        let bytes = writer.into_bytes();
+        let status = unsafe { host::metrics_flush(bytes.as_ptr() as usize, bytes.len() as u32) };
+        if status < 0 {
+            return Err(ExtensionErrorCode::from(status).into());
+        }
+
+        for metric in registry.metrics.iter_mut().filter(|m| m.dirty) {
+            metric.dirty = false;
+            match &mut metric.value {
+                Value::Counter(delta) => *delta = 0,
+                Value::Gauge(_) => {}
+                Value::Histogram(histogram) => {
+                    histogram.counts.fill(0);
+                    histogram.count = 0;
+                    histogram.sum = 0.0;
+                }
+            }
+        }
+        LAST_FLUSH.store(unsafe { host::monotonic_now() }, Ordering::Relaxed);
+        Ok(())
+    })
+}
+
+fn write_metric(writer: &mut Writer, metric: &Metric) {
+    let (name, labels) = &metric.key;
+    writer.write_u8(metric.kind);
+    writer.write_str(name);
+    // Only reportable metrics are flushed, so the count fits.
+    writer.write_u16(labels.len() as u16);
+    for (key, value) in labels {
+        writer.write_str(key);
+        writer.write_str(value);
+    }
+    match &metric.value {
+        Value::Counter(delta) => writer.write_u64(*delta),
+        Value::Gauge(value) => writer.write_u64(value.to_bits()),
+        Value::Histogram(histogram) => {
+            writer.write_u32(histogram.counts.len() as u32);
+            let bounds = histogram.bounds.iter().copied().chain([f64::INFINITY]);
+            for (bound, count) in bounds.zip(&histogram.counts) {
+                writer.write_u64(bound.to_bits());
+                writer.write_u64(*count);
+            }
+            writer.write_u64(histogram.count);
+            writer.write_u64(histogram.sum.to_bits());
+        }
+    }
+}
+
+/// Has metrics flushed automatically, at most once per `interval`, as they're updated. `None`
+/// turns periodic flushing off, which is the default; metrics are still flushed when the job's
+/// main entrypoint returns.
+pub fn set_flush_interval(interval: Option<Duration>) {
+    let nanos = interval.map_or(0, |interval| {
+        u64::try_from(interval.as_nanos())
+            .unwrap_or(u64::MAX)
+            .max(1)
+    });
+    FLUSH_INTERVAL_NANOS.store(nanos, Ordering::Relaxed);
+}
+
+fn maybe_flush() {
+    let interval = FLUSH_INTERVAL_NANOS.load(Ordering::Relaxed);
+    if interval == 0 {
+        return;
+    }
+    let now = unsafe { host::monotonic_now() };
+    if now.saturating_sub(LAST_FLUSH.load(Ordering::Relaxed)) >= interval {
+        // Failed updates are kept for the next flush, so there's nothing to do about an error.
+        let _ = flush();
+    }
+}
+
+/// Called by the entrypoint glue when a job is done.
+pub(crate) fn at_exit() {
+    let _ = flush();
+}
+
+#[cfg(all(test, feature = "mock-host"))]
+mod tests {
+    use super::{with_registry, Counter};
+
+    #[test]
+    fn metrics_with_more_labels_than_a_flush_carries_are_never_sent() {
+        let keys: Vec<String> = (0..=u16::MAX as usize).map(|key| key.to_string()).collect();
+        let labels: Vec<(&str, &str)> = keys.iter().map(|key| (key.as_str(), "")).collect();
+        let too_many = Counter::with_labels("too_many_labels", &labels);
+        let fine = Counter::with_labels("few_labels", &labels[..1]);
+        too_many.increment(1);
+        fine.increment(1);
+        with_registry(|registry| {
+            assert!(!registry.metrics[too_many.id].dirty);
+            assert!(registry.metrics[fine.id].dirty);
+        });
+    }
+}
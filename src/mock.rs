@@ -0,0 +1,400 @@
+//! An in-process stand-in for the Serval host, so guest logic can be unit tested natively with
+//! plain `cargo test`. The `mock-host` feature swaps the wasm imports declared in `host` for the
+//! functions in `imports`, which serve every call from the state kept here.
+//!
+//! ```ignore
+//! serval::mock::respond("greeter", |name| Ok([b"hello ", name].concat()));
+//! assert_eq!(serval::invoke_extension("greeter", "ferris")?, b"hello ferris");
+//! ```
+//!
+//! Extensions are whatever has been registered with `respond` or `respond_with`; invoking anything
+//! else fails with `ExtensionErrorCode::NotFound`. Handlers see exactly the bytes the guest sent,
+//! so those behind `frame::invoke_framed` and the platform service clients receive an encoded
+//! `frame::Frame` and have to answer with one. Streams, requests written in pieces, async calls,
+//! batches, pipelines and channels are all built on the same handlers and complete immediately.
+//!
+//! Every invocation is recorded, and `calls` returns them for tests to check what the guest sent.
+//! `inject` and `inject_nth` make chosen calls fail, arrive late or come back cut short (see
+//! `Fault`), to exercise the guest's retry, timeout and corruption handling. `TestHost` wraps all
+//! of this up for a single test.
+//!
+//! Beyond extensions, the mock keeps an in-memory filesystem for `fs`, configuration variables,
+//! secrets and job input that tests can set up, and records the job's output, completion, progress,
+//! heartbeats and log messages for them to check. Clocks and sleeping are the real ones unless a
+//! `FakeClock` is installed, and randomness comes from a generator seeded at random unless
+//! `seed_rng` fixes the seed. Sockets can't be opened, and memory statistics, metrics, trace
+//! records and error reports are accepted and dropped.
+//!
+//! The mock's state is per thread, so tests running in parallel don't see each other's handlers.
+//! The flags `job` caches once the host has reported them (cancellation, completion, output) are
+//! process-wide, though; `reset` clears them along with the calling thread's state.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::caps::Capability;
+use crate::extensions::Description;
+use crate::frame::Frame;
+use crate::host_bytes::OwnedHostBytes;
+use crate::job::{JobMetadata, JobStatus};
+use crate::log::Level;
+use crate::{bytes_to_host, ExtensionErrorCode, Result, SdkError};
+
+mod clock;
+mod faults;
+pub(crate) mod imports;
+mod test_host;
+
+pub use clock::FakeClock;
+pub use faults::{clear_faults, inject, inject_nth, Fault};
+pub use test_host::TestHost;
+
+/// What a mocked extension answers a call with: a response body or the error code the host would
+/// report.
+pub type Reply = std::result::Result<Vec<u8>, ExtensionErrorCode>;
+
+type Handler = Rc<RefCell<dyn FnMut(&[u8]) -> Reply>>;
+
+/// Registers `handler` as the extension `name`, replacing any handler registered before.
+pub fn respond(name: &str, handler: impl FnMut(&[u8]) -> Reply + 'static) {
+    with(|state| {
+        let handler: Handler = Rc::new(RefCell::new(handler));
+        state.handlers.insert(name.to_string(), handler);
+    });
+}
+
+/// Registers an extension `name` that answers every call with `reply`.
+pub fn respond_with(name: &str, reply: Reply) {
+    respond(name, move |_| reply.clone());
+}
+
+/// Sets the version `extensions::list` reports for the extension `name`. Extensions without one
+/// are listed with an empty version.
+pub fn set_extension_version(name: &str, version: &str) {
+    with(|state| {
+        state.versions.insert(name.to_string(), version.to_string());
+    });
+}
+
+/// Sets what `extensions::describe` reports for the extension `description.name`, along with the
+/// version `extensions::list` reports for it. It only takes effect while the extension is
+/// registered; registered extensions without a description are described as declaring no
+/// operations or codecs.
+pub fn set_extension_description(description: Description) {
+    with(|state| {
+        state
+            .versions
+            .insert(description.name.clone(), description.version.clone());
+        state
+            .descriptions
+            .insert(description.name.clone(), description);
+    });
+}
+
+/// Unregisters the extension `name`, so calls to it fail with `ExtensionErrorCode::NotFound`.
+pub fn remove(name: &str) {
+    with(|state| {
+        state.handlers.remove(name);
+    });
+}
+
+/// Returns the calling thread's mock host to its initial state and clears the job flags the SDK
+/// caches. Buffers the host handed out that the guest never took are freed.
+pub fn reset() {
+    let state = STATE.with(|state| state.take());
+    for ptr in state.buffers.into_values() {
+        // Safety: every pointer in `buffers` came from `bytes_to_host` and hasn't been taken.
+        drop(unsafe { OwnedHostBytes::from_host(ptr) });
+    }
+    crate::job::reset();
+}
+
+/// An invocation the mock host has served.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Call {
+    pub extension: String,
+    /// The payload exactly as the guest sent it.
+    pub payload: Vec<u8>,
+    /// What the extension answered with.
+    pub reply: Reply,
+}
+
+impl Call {
+    /// Decodes the payload as an invocation frame, for calls made through `frame::invoke_framed`
+    /// and the platform service clients.
+    pub fn frame(&self) -> Result<Frame> {
+        Frame::decode(&self.payload)
+    }
+}
+
+/// Every invocation the mock host has served on this thread, oldest first. Each stage of a
+/// pipeline, call in a batch and message on a channel counts as one.
+pub fn calls() -> Vec<Call> {
+    with(|state| state.calls.clone())
+}
+
+/// Forgets the invocations recorded so far.
+pub fn clear_calls() {
+    with(|state| state.calls.clear());
+}
+
+/// Sets a configuration variable for `env::get` and `env::vars`.
+pub fn set_env(key: &str, value: &str) {
+    with(|state| {
+        state.env.insert(key.to_string(), value.to_string());
+    });
+}
+
+/// Sets a secret for `secrets::get`.
+pub fn set_secret(name: &str, value: impl Into<Vec<u8>>) {
+    with(|state| {
+        state.secrets.insert(name.to_string(), value.into());
+    });
+}
+
+/// Sets what `job::input` returns. The job starts out with no parameters and an empty body.
+pub fn set_job_input<K, V>(params: impl IntoIterator<Item = (K, V)>, body: impl Into<Vec<u8>>)
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    with(|state| {
+        state.job_params = params
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect();
+        state.job_body = body.into();
+    });
+}
+
+/// Sets what the host reports as the job's metadata. `job::metadata` only fetches it once per
+/// process, so this has to be called before the first test that uses it.
+pub fn set_job_metadata(metadata: JobMetadata) {
+    with(|state| state.job_metadata = Some(metadata));
+}
+
+/// Sets the trace ID the host assigned the job, which `current_trace_id` falls back to.
+pub fn set_job_trace_id(id: &str) {
+    with(|state| state.job_trace_id = Some(id.to_string()));
+}
+
+/// Makes the host report the job as cancelled, or not.
+pub fn set_cancelled(cancelled: bool) {
+    with(|state| state.cancelled = cancelled);
+}
+
+/// Makes the host speak only ABI version `version` instead of `abi::ABI_VERSION`, to test how the
+/// guest handles a host it's incompatible with.
+pub fn set_abi_version(version: u32) {
+    with(|state| state.abi_version = Some(version));
+}
+
+/// Restricts the job to `capabilities`, which `caps` reports and checks against. Until this is
+/// called, and again after `reset`, the job is granted everything.
+pub fn set_capabilities(capabilities: impl IntoIterator<Item = Capability>) {
+    with(|state| state.capabilities = Some(capabilities.into_iter().collect()));
+}
+
+/// The output the job has published, if it has.
+pub fn job_output() -> Option<Vec<u8>> {
+    with(|state| state.job_output.clone())
+}
+
+/// How the job has said it ended, if it has.
+pub fn job_completion() -> Option<(JobStatus, String)> {
+    with(|state| state.job_completion.clone())
+}
+
+/// Every progress report the job has sent, oldest first.
+pub fn progress() -> Vec<(f32, String)> {
+    with(|state| state.progress.clone())
+}
+
+/// How many heartbeats the job has sent.
+pub fn heartbeats() -> usize {
+    with(|state| state.heartbeats)
+}
+
+/// Every message the guest has logged through the host, oldest first.
+pub fn logs() -> Vec<(Level, String)> {
+    with(|state| state.logs.clone())
+}
+
+/// Makes `rand::fill` and everything built on it produce the same bytes on every run, starting
+/// from `seed`.
+pub fn seed_rng(seed: u64) {
+    // Spread the seed's bits out with a splitmix64 step, so that small seeds don't start the
+    // xorshift generator on a run of near-zero outputs. Xorshift gets stuck on zero, hence the
+    // `| 1`.
+    let mut x = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    with(|state| state.rng = (x ^ (x >> 31)) | 1);
+}
+
+/// Creates or replaces a file in the mock filesystem.
+pub fn set_file(path: &str, contents: impl Into<Vec<u8>>) {
+    with(|state| {
+        state.files.insert(path.to_string(), contents.into());
+    });
+}
+
+/// The contents of a file in the mock filesystem, if it exists.
+pub fn file(path: &str) -> Option<Vec<u8>> {
+    with(|state| state.files.get(path).cloned())
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::default();
+}
+
+#[derive(Default)]
+struct State {
+    handlers: HashMap<String, Handler>,
+    versions: HashMap<String, String>,
+    descriptions: HashMap<String, Description>,
+    /// Handles for everything the guest addresses by id, shared by every kind so that an id can
+    /// never be mistaken for another kind's.
+    next_id: u32,
+    /// Buffers handed to the guest. Their addresses don't fit in the i32 the imports return on a
+    /// 64-bit host, so the guest is given an id instead, which `resolve` turns back into the
+    /// address.
+    buffers: HashMap<u32, usize>,
+    /// Calls made with `start_invoke` that the guest hasn't collected yet.
+    started: HashMap<u32, Reply>,
+    /// Every invocation so far, oldest first.
+    calls: Vec<Call>,
+    faults: Vec<faults::Injection>,
+    streams: HashMap<u32, Stream>,
+    requests: HashMap<u32, (String, Vec<u8>)>,
+    channels: HashMap<u32, Channel>,
+    files: BTreeMap<String, Vec<u8>>,
+    open_files: HashMap<u32, OpenFile>,
+    env: BTreeMap<String, String>,
+    secrets: HashMap<String, Vec<u8>>,
+    job_metadata: Option<JobMetadata>,
+    job_trace_id: Option<String>,
+    job_params: BTreeMap<String, String>,
+    job_body: Vec<u8>,
+    job_output: Option<Vec<u8>>,
+    job_completion: Option<(JobStatus, String)>,
+    cancelled: bool,
+    abi_version: Option<u32>,
+    /// What `set_capabilities` restricted the job to, or `None` to grant everything.
+    capabilities: Option<Vec<Capability>>,
+    progress: Vec<(f32, String)>,
+    heartbeats: usize,
+    logs: Vec<(Level, String)>,
+    /// The state of the xorshift generator behind `random_fill`, or 0 before it's been seeded.
+    rng: u64,
+    clock: Option<FakeTime>,
+}
+
+impl State {
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn capabilities(&self) -> Vec<Capability> {
+        self.capabilities
+            .clone()
+            .unwrap_or_else(crate::caps::everything)
+    }
+}
+
+/// The time shown by a `FakeClock`, in nanoseconds.
+struct FakeTime {
+    wall_nanos: u64,
+    monotonic_nanos: u64,
+}
+
+impl FakeTime {
+    fn advance(&mut self, nanos: u64) {
+        self.wall_nanos = self.wall_nanos.saturating_add(nanos);
+        self.monotonic_nanos = self.monotonic_nanos.saturating_add(nanos);
+    }
+}
+
+/// A response being fetched with `stream_next`.
+struct Stream {
+    data: Vec<u8>,
+    position: usize,
+    /// Bytes the guest has granted and that haven't been delivered yet.
+    credit: usize,
+}
+
+struct Channel {
+    extension: String,
+    /// Replies to the guest's messages that it hasn't received yet.
+    inbox: VecDeque<Vec<u8>>,
+}
+
+struct OpenFile {
+    path: String,
+    position: u64,
+    readable: bool,
+    writable: bool,
+    append: bool,
+}
+
+fn with<T>(f: impl FnOnce(&mut State) -> T) -> T {
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+/// Calls the handler registered for `extension`, subject to any fault injected into the call. The
+/// state isn't borrowed while it runs, so handlers are free to use the rest of this module.
+fn invoke(extension: &str, payload: &[u8]) -> Reply {
+    invoke_within(extension, payload, None)
+}
+
+/// Like `invoke`, for a call the guest gave a timeout.
+fn invoke_within(extension: &str, payload: &[u8], timeout: Option<Duration>) -> Reply {
+    let fault = faults::next(extension);
+    let reply = faults::apply(fault, timeout, || {
+        match with(|state| state.handlers.get(extension).cloned()) {
+            Some(handler) => (*handler.borrow_mut())(payload),
+            None => Err(ExtensionErrorCode::NotFound),
+        }
+    });
+    let call = Call {
+        extension: extension.to_string(),
+        payload: payload.to_vec(),
+        reply: reply.clone(),
+    };
+    with(|state| state.calls.push(call));
+    reply
+}
+
+/// Copies `bytes` into a length-prefixed buffer for the guest and returns the id it's known by.
+pub(crate) fn to_guest(bytes: &[u8]) -> isize {
+    let ptr = bytes_to_host(bytes);
+    with(|state| {
+        let id = state.next_id();
+        state.buffers.insert(id, ptr);
+        id as isize
+    })
+}
+
+/// Turns a reply into what an import returning a buffer gives the guest.
+fn reply_to_guest(reply: Reply) -> isize {
+    match reply {
+        Ok(bytes) => to_guest(&bytes),
+        Err(code) => code.as_raw() as isize,
+    }
+}
+
+/// Turns the id of a buffer handed to the guest back into its address, giving up ownership of it.
+/// Called by `take_host_bytes` for every buffer the guest receives.
+pub(crate) fn resolve(id: usize) -> Result<usize> {
+    let ptr = u32::try_from(id)
+        .ok()
+        .and_then(|id| with(|state| state.buffers.remove(&id)));
+    ptr.ok_or(SdkError::CorruptFrame {
+        ptr: id,
+        len: None,
+        memory_size: 0,
+    })
+}
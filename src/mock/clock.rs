@@ -0,0 +1,97 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{with, FakeTime};
+
+/// Replaces the mock host's clocks with ones that only move when told to, so that timeouts,
+/// backoff and anything else time-dependent can be tested deterministically.
+///
+/// ```ignore
+/// let clock = FakeClock::new();
+/// let lease = cache.lease("report")?;
+/// clock.advance(Duration::from_secs(61));
+/// assert!(lease.is_expired());
+/// ```
+///
+/// While it's installed, `time::sleep` returns at once and moves both clocks forward by the time
+/// slept, and `time::Instant` and `time::now` read the fake time. Dropping it puts the real clocks
+/// back. Like the rest of the mock host, it only affects the calling thread.
+#[derive(Debug)]
+pub struct FakeClock {
+    _private: (),
+}
+
+impl FakeClock {
+    /// The wall-clock time a `FakeClock` starts at unless told otherwise: 2024-01-01T00:00:00Z.
+    pub const DEFAULT_START: Duration = Duration::from_secs(1_704_067_200);
+
+    /// Installs a fake clock at `DEFAULT_START`.
+    pub fn new() -> Self {
+        Self::starting_at(UNIX_EPOCH + Self::DEFAULT_START)
+    }
+
+    /// Installs a fake clock whose wall-clock time starts at `start`. The monotonic clock starts
+    /// at zero.
+    pub fn starting_at(start: SystemTime) -> Self {
+        let wall_nanos = nanos(start.duration_since(UNIX_EPOCH).unwrap_or_default());
+        with(|state| {
+            state.clock = Some(FakeTime {
+                wall_nanos,
+                monotonic_nanos: 0,
+            })
+        });
+        Self { _private: () }
+    }
+
+    /// Moves both clocks forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        with(|state| {
+            if let Some(clock) = &mut state.clock {
+                clock.advance(nanos(duration));
+            }
+        });
+    }
+
+    /// Sets the wall-clock time, which may go backwards like a real one can. The monotonic clock
+    /// doesn't move.
+    pub fn set(&self, now: SystemTime) {
+        let wall_nanos = nanos(now.duration_since(UNIX_EPOCH).unwrap_or_default());
+        with(|state| {
+            if let Some(clock) = &mut state.clock {
+                clock.wall_nanos = wall_nanos;
+            }
+        });
+    }
+
+    /// The current fake wall-clock time.
+    pub fn now(&self) -> SystemTime {
+        let wall_nanos = with(|state| state.clock.as_ref().map_or(0, |clock| clock.wall_nanos));
+        UNIX_EPOCH + Duration::from_nanos(wall_nanos)
+    }
+
+    /// How far the monotonic clock has moved since the fake clock was installed.
+    pub fn elapsed(&self) -> Duration {
+        let monotonic_nanos = with(|state| {
+            state
+                .clock
+                .as_ref()
+                .map_or(0, |clock| clock.monotonic_nanos)
+        });
+        Duration::from_nanos(monotonic_nanos)
+    }
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FakeClock {
+    fn drop(&mut self) {
+        with(|state| state.clock = None);
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use super::{with, Reply};
+use crate::ExtensionErrorCode;
+
+/// A failure the mock host can inject into calls to an extension, to check how the guest copes
+/// with hosts and extensions misbehaving.
+///
+/// ```ignore
+/// // The first attempt times out and the second succeeds.
+/// mock::inject_nth("kv", 1, Fault::Error(ExtensionErrorCode::TimedOut));
+/// assert_eq!(fetch_with_retry("profile/alice")?, b"alice");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The call fails with the code without reaching the extension.
+    Error(ExtensionErrorCode),
+    /// The extension runs, but only the first `len` bytes of its response reach the guest, e.g.
+    /// to cut a response frame short.
+    Truncate(usize),
+    /// The response arrives after the delay. The wait is taken off a `FakeClock` if one is
+    /// installed. A call made with a timeout shorter than the delay fails with
+    /// `ExtensionErrorCode::TimedOut` once the timeout has passed, without reaching the extension.
+    Delay(Duration),
+    /// The extension runs, but the host fails to allocate the buffer for its response, so the call
+    /// fails with `ExtensionErrorCode::AllocationFailed`.
+    AllocationFailure,
+}
+
+/// A fault waiting for the calls it applies to.
+pub(super) struct Injection {
+    extension: String,
+    /// The 1-based call, counting from when the fault was injected, that it applies to, or `None`
+    /// for every call.
+    nth: Option<usize>,
+    seen: usize,
+    fault: Fault,
+}
+
+/// Injects `fault` into every call to the extension `extension` from now on, until
+/// `clear_faults` or `reset`.
+pub fn inject(extension: &str, fault: Fault) {
+    push(extension, None, fault);
+}
+
+/// Injects `fault` into only the `nth` call to the extension `extension` from now on, counting
+/// from 1. Faults injected into the same call apply in the order they were injected, the first
+/// one winning.
+pub fn inject_nth(extension: &str, nth: usize, fault: Fault) {
+    push(extension, Some(nth.max(1)), fault);
+}
+
+/// Removes every fault injected so far.
+pub fn clear_faults() {
+    with(|state| state.faults.clear());
+}
+
+fn push(extension: &str, nth: Option<usize>, fault: Fault) {
+    with(|state| {
+        state.faults.push(Injection {
+            extension: extension.to_string(),
+            nth,
+            seen: 0,
+            fault,
+        })
+    });
+}
+
+/// Counts a call to `extension` against the injected faults and returns the one to apply to it.
+pub(super) fn next(extension: &str) -> Option<Fault> {
+    with(|state| {
+        let mut chosen = None;
+        for injection in state
+            .faults
+            .iter_mut()
+            .filter(|injection| injection.extension == extension)
+        {
+            injection.seen += 1;
+            let applies = injection.nth.is_none_or(|nth| nth == injection.seen);
+            if applies && chosen.is_none() {
+                chosen = Some(injection.fault.clone());
+            }
+        }
+        state
+            .faults
+            .retain(|injection| injection.nth.is_none_or(|nth| injection.seen < nth));
+        chosen
+    })
+}
+
+/// Runs `call` under `fault`, given the call's timeout if it has one.
+pub(super) fn apply(
+    fault: Option<Fault>,
+    timeout: Option<Duration>,
+    call: impl FnOnce() -> Reply,
+) -> Reply {
+    match fault {
+        None => call(),
+        Some(Fault::Error(code)) => Err(code),
+        Some(Fault::Truncate(len)) => call().map(|mut bytes| {
+            bytes.truncate(len);
+            bytes
+        }),
+        Some(Fault::Delay(delay)) => match timeout {
+            Some(timeout) if timeout < delay => {
+                wait(timeout);
+                Err(ExtensionErrorCode::TimedOut)
+            }
+            _ => {
+                wait(delay);
+                call()
+            }
+        },
+        Some(Fault::AllocationFailure) => call().and(Err(ExtensionErrorCode::AllocationFailed)),
+    }
+}
+
+fn wait(duration: Duration) {
+    let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    // Safety: the mock's `sleep` doesn't touch guest memory.
+    unsafe { super::imports::sleep(nanos) };
+}
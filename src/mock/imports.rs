@@ -0,0 +1,792 @@
+//! The mock host's implementations of the imports declared in `host`, with the same names and
+//! signatures so that the rest of the crate can't tell them apart. See `mock` for what each kind
+//! of call does.
+
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{invoke, invoke_within, reply_to_guest, to_guest, with, Channel, OpenFile, Stream};
+use crate::caps;
+use crate::extensions::Description;
+use crate::fs::{flags, whence};
+#[cfg(feature = "inline-returns")]
+use crate::inline;
+use crate::job::{JobMetadata, JobStatus};
+use crate::log::Level;
+use crate::wire::{Reader, Writer};
+use crate::ExtensionErrorCode;
+
+/// Borrows `len` bytes of guest memory at `ptr`.
+unsafe fn bytes<'a>(ptr: usize, len: u32) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(ptr as *const u8, len as usize)
+}
+
+unsafe fn bytes_mut<'a>(ptr: usize, len: u32) -> &'a mut [u8] {
+    if len == 0 {
+        return &mut [];
+    }
+    std::slice::from_raw_parts_mut(ptr as *mut u8, len as usize)
+}
+
+unsafe fn string(ptr: usize, len: u32) -> String {
+    String::from_utf8_lossy(bytes(ptr, len)).into_owned()
+}
+
+/// What the imports return for a handle they don't know. A real host would trap.
+const UNKNOWN_HANDLE: i32 = -6;
+
+fn status(result: Result<(), ExtensionErrorCode>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(code) => code.as_raw(),
+    }
+}
+
+pub(crate) unsafe fn invoke_raw(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> isize {
+    reply_to_guest(invoke(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+    ))
+}
+
+#[cfg(feature = "inline-returns")]
+pub(crate) unsafe fn invoke_raw_inline(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i64 {
+    let reply = invoke(&string(name_ptr, name_len), bytes(data_ptr, data_len));
+    match reply.as_deref().ok().and_then(inline::pack) {
+        Some(value) => value,
+        None => reply_to_guest(reply) as i64,
+    }
+}
+
+pub(crate) unsafe fn invoke_raw_with_timeout(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+    timeout_ms: u32,
+) -> isize {
+    let timeout = (timeout_ms != u32::MAX).then(|| Duration::from_millis(timeout_ms.into()));
+    reply_to_guest(invoke_within(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+        timeout,
+    ))
+}
+
+pub(crate) unsafe fn invoke_raw_oneway(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i32 {
+    status(invoke(&string(name_ptr, name_len), bytes(data_ptr, data_len)).map(drop))
+}
+
+pub(crate) unsafe fn invoke_framed(
+    name_ptr: usize,
+    name_len: u32,
+    frame_ptr: usize,
+    frame_len: u32,
+) -> isize {
+    invoke_raw(name_ptr, name_len, frame_ptr, frame_len)
+}
+
+pub(crate) unsafe fn invoke_batch(batch_ptr: usize, batch_len: u32) -> isize {
+    let mut reader = Reader::new(bytes(batch_ptr, batch_len));
+    let calls = reader.read_u32().and_then(|count| {
+        (0..count)
+            .map(|_| Ok((reader.read_str()?.to_string(), reader.read_prefixed()?)))
+            .collect::<crate::Result<Vec<_>>>()
+    });
+    let Ok(calls) = calls else {
+        return ExtensionErrorCode::InvalidPayload.as_raw() as isize;
+    };
+
+    let mut writer = Writer::new();
+    writer.write_u32(calls.len() as u32);
+    for (name, payload) in calls {
+        let (status, body) = match invoke(&name, payload) {
+            Ok(body) => (0, body),
+            Err(code) => (code.as_raw(), Vec::new()),
+        };
+        writer.write_i32(status);
+        writer.write_prefixed(&body);
+    }
+    to_guest(&writer.into_bytes())
+}
+
+pub(crate) unsafe fn invoke_pipeline(pipeline_ptr: usize, pipeline_len: u32) -> isize {
+    let mut reader = Reader::new(bytes(pipeline_ptr, pipeline_len));
+    let stages = reader.read_u32().and_then(|count| {
+        (0..count)
+            .map(|_| Ok(reader.read_str()?.to_string()))
+            .collect::<crate::Result<Vec<_>>>()
+    });
+    let Ok(stages) = stages else {
+        return ExtensionErrorCode::InvalidPayload.as_raw() as isize;
+    };
+
+    let output = stages
+        .iter()
+        .try_fold(reader.remaining().to_vec(), |data, stage| {
+            invoke(stage, &data)
+        });
+    reply_to_guest(output)
+}
+
+pub(crate) unsafe fn start_invoke(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i32 {
+    let reply = invoke(&string(name_ptr, name_len), bytes(data_ptr, data_len));
+    with(|state| {
+        let id = state.next_id();
+        state.started.insert(id, reply);
+        id as i32
+    })
+}
+
+pub(crate) unsafe fn poll_invoke(handle: u32) -> isize {
+    match with(|state| state.started.remove(&handle)) {
+        Some(reply) => reply_to_guest(reply),
+        None => UNKNOWN_HANDLE as isize,
+    }
+}
+
+pub(crate) unsafe fn wait_invoke(handle: u32) -> isize {
+    poll_invoke(handle)
+}
+
+pub(crate) unsafe fn wait_any(_handles_ptr: usize, count: u32) -> i32 {
+    // Every call finishes as it starts, so the first handle is always ready.
+    if count == 0 {
+        return ExtensionErrorCode::InvalidPayload.as_raw();
+    }
+    0
+}
+
+pub(crate) unsafe fn cancel_invoke(handle: u32) -> i32 {
+    match with(|state| state.started.remove(&handle)) {
+        Some(_) => 0,
+        None => UNKNOWN_HANDLE,
+    }
+}
+
+pub(crate) unsafe fn list_extensions() -> isize {
+    let extensions = with(|state| {
+        let mut names: Vec<&String> = state.handlers.keys().collect();
+        names.sort();
+        let mut writer = Writer::new();
+        writer.write_u32(names.len() as u32);
+        for name in names {
+            writer.write_str(name);
+            writer.write_str(state.versions.get(name).map_or("", String::as_str));
+        }
+        writer.into_bytes()
+    });
+    to_guest(&extensions)
+}
+
+pub(crate) unsafe fn describe_extension(name_ptr: usize, name_len: u32) -> isize {
+    let name = string(name_ptr, name_len);
+    let description = with(|state| {
+        let registered = state.handlers.contains_key(&name);
+        registered.then(|| {
+            state
+                .descriptions
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| Description {
+                    name: name.clone(),
+                    version: state.versions.get(&name).cloned().unwrap_or_default(),
+                    ..Description::default()
+                })
+        })
+    });
+    match description {
+        Some(description) => to_guest(&description.encode()),
+        None => 0,
+    }
+}
+
+fn open_stream(reply: super::Reply) -> i32 {
+    let data = match reply {
+        Ok(data) => data,
+        Err(code) => return code.as_raw(),
+    };
+    with(|state| {
+        let id = state.next_id();
+        let stream = Stream {
+            data,
+            position: 0,
+            credit: 0,
+        };
+        state.streams.insert(id, stream);
+        id as i32
+    })
+}
+
+pub(crate) unsafe fn invoke_streaming(
+    name_ptr: usize,
+    name_len: u32,
+    data_ptr: usize,
+    data_len: u32,
+) -> i32 {
+    open_stream(invoke(
+        &string(name_ptr, name_len),
+        bytes(data_ptr, data_len),
+    ))
+}
+
+pub(crate) unsafe fn stream_next(stream: u32) -> isize {
+    let chunk = with(|state| {
+        let Some(open) = state.streams.get_mut(&stream) else {
+            return Err(UNKNOWN_HANDLE);
+        };
+        if open.position == open.data.len() {
+            state.streams.remove(&stream);
+            return Ok(None);
+        }
+        if open.credit == 0 {
+            // A real host would wait for credit forever.
+            state.streams.remove(&stream);
+            return Err(UNKNOWN_HANDLE);
+        }
+        let len = open.credit.min(open.data.len() - open.position);
+        let chunk = open.data[open.position..open.position + len].to_vec();
+        open.position += len;
+        open.credit -= len;
+        Ok(Some(chunk))
+    });
+    match chunk {
+        Ok(Some(chunk)) => to_guest(&chunk),
+        Ok(None) => 0,
+        Err(code) => code as isize,
+    }
+}
+
+pub(crate) unsafe fn stream_grant(stream: u32, credit: u32) -> i32 {
+    with(|state| match state.streams.get_mut(&stream) {
+        Some(open) => {
+            open.credit += credit as usize;
+            0
+        }
+        None => UNKNOWN_HANDLE,
+    })
+}
+
+pub(crate) unsafe fn stream_close(stream: u32) -> i32 {
+    match with(|state| state.streams.remove(&stream)) {
+        Some(_) => 0,
+        None => UNKNOWN_HANDLE,
+    }
+}
+
+pub(crate) unsafe fn open_request(name_ptr: usize, name_len: u32) -> i32 {
+    let name = string(name_ptr, name_len);
+    with(|state| {
+        let id = state.next_id();
+        state.requests.insert(id, (name, Vec::new()));
+        id as i32
+    })
+}
+
+pub(crate) unsafe fn request_write(request: u32, data_ptr: usize, data_len: u32) -> i32 {
+    with(|state| match state.requests.get_mut(&request) {
+        Some((_, payload)) => {
+            payload.extend_from_slice(bytes(data_ptr, data_len));
+            0
+        }
+        None => UNKNOWN_HANDLE,
+    })
+}
+
+pub(crate) unsafe fn request_finish(request: u32) -> isize {
+    match with(|state| state.requests.remove(&request)) {
+        Some((name, payload)) => reply_to_guest(invoke(&name, &payload)),
+        None => UNKNOWN_HANDLE as isize,
+    }
+}
+
+pub(crate) unsafe fn request_finish_streaming(request: u32) -> i32 {
+    match with(|state| state.requests.remove(&request)) {
+        Some((name, payload)) => open_stream(invoke(&name, &payload)),
+        None => UNKNOWN_HANDLE,
+    }
+}
+
+pub(crate) unsafe fn negotiate_segment_size(preferred: u32) -> i32 {
+    preferred.min(i32::MAX as u32) as i32
+}
+
+pub(crate) unsafe fn request_abort(request: u32) -> i32 {
+    match with(|state| state.requests.remove(&request)) {
+        Some(_) => 0,
+        None => UNKNOWN_HANDLE,
+    }
+}
+
+pub(crate) unsafe fn open_channel(name_ptr: usize, name_len: u32) -> i32 {
+    let extension = string(name_ptr, name_len);
+    with(|state| {
+        if !state.handlers.contains_key(&extension) {
+            return ExtensionErrorCode::NotFound.as_raw();
+        }
+        let id = state.next_id();
+        let channel = Channel {
+            extension,
+            inbox: Default::default(),
+        };
+        state.channels.insert(id, channel);
+        id as i32
+    })
+}
+
+/// Each message the guest sends is a call to the channel's extension, and a non-empty response
+/// is the message the extension sends back.
+pub(crate) unsafe fn channel_send(channel: u32, data_ptr: usize, data_len: u32) -> i32 {
+    let Some(extension) = with(|state| {
+        let channel = state.channels.get(&channel)?;
+        Some(channel.extension.clone())
+    }) else {
+        return UNKNOWN_HANDLE;
+    };
+    match invoke(&extension, bytes(data_ptr, data_len)) {
+        Ok(reply) => {
+            with(|state| {
+                if let Some(channel) = state.channels.get_mut(&channel) {
+                    if !reply.is_empty() {
+                        channel.inbox.push_back(reply);
+                    }
+                }
+            });
+            0
+        }
+        Err(code) => code.as_raw(),
+    }
+}
+
+/// Once the guest has received every reply, the extension's side reads as closed; a real host
+/// would block until it sent something.
+pub(crate) unsafe fn channel_recv(channel: u32) -> isize {
+    let message = with(|state| {
+        let channel = state
+            .channels
+            .get_mut(&channel)
+            .ok_or(UNKNOWN_HANDLE as isize)?;
+        Ok(channel.inbox.pop_front())
+    });
+    match message {
+        Ok(Some(message)) => to_guest(&message),
+        Ok(None) => 0,
+        Err(code) => code,
+    }
+}
+
+pub(crate) unsafe fn channel_grant(channel: u32, _credit: u32) -> i32 {
+    channel_exists(channel)
+}
+
+pub(crate) unsafe fn channel_close_send(channel: u32) -> i32 {
+    channel_exists(channel)
+}
+
+pub(crate) unsafe fn channel_close(channel: u32) -> i32 {
+    match with(|state| state.channels.remove(&channel)) {
+        Some(_) => 0,
+        None => UNKNOWN_HANDLE,
+    }
+}
+
+fn channel_exists(channel: u32) -> i32 {
+    match with(|state| state.channels.contains_key(&channel)) {
+        true => 0,
+        false => UNKNOWN_HANDLE,
+    }
+}
+
+pub(crate) unsafe fn abi_negotiate(min: u32, max: u32) -> i32 {
+    let version = with(|state| state.abi_version).unwrap_or(crate::abi::ABI_VERSION);
+    if (min..=max).contains(&version) {
+        version as i32
+    } else {
+        ExtensionErrorCode::NoMatchingVersion.as_raw()
+    }
+}
+
+pub(crate) unsafe fn caps_granted() -> isize {
+    let granted = with(|state| caps::encode(&state.capabilities()));
+    to_guest(&granted)
+}
+
+pub(crate) unsafe fn caps_can_invoke(name_ptr: usize, name_len: u32) -> i32 {
+    let name = string(name_ptr, name_len);
+    with(|state| caps::permits(&state.capabilities(), &name)) as i32
+}
+
+pub(crate) unsafe fn get_last_error() -> isize {
+    0
+}
+
+pub(crate) unsafe fn report_memory_stats(_ptr: usize, _len: u32) -> i32 {
+    0
+}
+
+pub(crate) unsafe fn job_metadata() -> isize {
+    let metadata = with(|state| state.job_metadata.clone()).unwrap_or_else(|| JobMetadata {
+        job_id: "mock-job".to_string(),
+        run_id: "mock-run".to_string(),
+        node_id: "mock-node".to_string(),
+        tenant: "mock-tenant".to_string(),
+        submitted_at: UNIX_EPOCH,
+        labels: BTreeMap::new(),
+    });
+    to_guest(&metadata.encode())
+}
+
+pub(crate) unsafe fn job_input() -> isize {
+    let input = with(|state| {
+        let mut writer = Writer::new();
+        writer.write_u32(state.job_params.len() as u32);
+        for (name, value) in &state.job_params {
+            writer.write_str(name);
+            writer.write_str(value);
+        }
+        writer.write_bytes(&state.job_body);
+        writer.into_bytes()
+    });
+    to_guest(&input)
+}
+
+pub(crate) unsafe fn job_trace_id() -> isize {
+    match with(|state| state.job_trace_id.clone()) {
+        Some(id) => to_guest(id.as_bytes()),
+        None => 0,
+    }
+}
+
+pub(crate) unsafe fn job_set_output(ptr: usize, len: u32) -> i32 {
+    let output = bytes(ptr, len).to_vec();
+    with(|state| state.job_output = Some(output));
+    0
+}
+
+pub(crate) unsafe fn job_complete(status: u32, message_ptr: usize, message_len: u32) -> i32 {
+    let status = match status {
+        0 => JobStatus::Succeeded,
+        1 => JobStatus::Failed,
+        _ => return ExtensionErrorCode::InvalidPayload.as_raw(),
+    };
+    let message = string(message_ptr, message_len);
+    with(|state| state.job_completion = Some((status, message)));
+    0
+}
+
+pub(crate) unsafe fn job_progress(percent: f32, message_ptr: usize, message_len: u32) -> i32 {
+    let message = string(message_ptr, message_len);
+    with(|state| state.progress.push((percent, message)));
+    0
+}
+
+pub(crate) unsafe fn job_cancelled() -> i32 {
+    with(|state| state.cancelled as i32)
+}
+
+pub(crate) unsafe fn job_heartbeat() -> i32 {
+    with(|state| state.heartbeats += 1);
+    0
+}
+
+pub(crate) unsafe fn metrics_flush(_ptr: usize, _len: u32) -> i32 {
+    0
+}
+
+pub(crate) unsafe fn secret_get(name_ptr: usize, name_len: u32) -> isize {
+    let name = string(name_ptr, name_len);
+    match with(|state| state.secrets.get(&name).cloned()) {
+        Some(value) => to_guest(&value),
+        None => 0,
+    }
+}
+
+pub(crate) unsafe fn env_get(key_ptr: usize, key_len: u32) -> isize {
+    let key = string(key_ptr, key_len);
+    match with(|state| state.env.get(&key).cloned()) {
+        Some(value) => to_guest(value.as_bytes()),
+        None => 0,
+    }
+}
+
+pub(crate) unsafe fn env_vars() -> isize {
+    let vars = with(|state| {
+        let mut writer = Writer::new();
+        writer.write_u32(state.env.len() as u32);
+        for (key, value) in &state.env {
+            writer.write_str(key);
+            writer.write_str(value);
+        }
+        writer.into_bytes()
+    });
+    to_guest(&vars)
+}
+
+pub(crate) unsafe fn wall_clock_now() -> u64 {
+    if let Some(wall_nanos) = with(|state| state.clock.as_ref().map(|clock| clock.wall_nanos)) {
+        return wall_nanos;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    u64::try_from(now.as_nanos()).unwrap_or(u64::MAX)
+}
+
+pub(crate) unsafe fn monotonic_now() -> u64 {
+    if let Some(nanos) = with(|state| state.clock.as_ref().map(|clock| clock.monotonic_nanos)) {
+        return nanos;
+    }
+    static START: OnceLock<Instant> = OnceLock::new();
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+}
+
+pub(crate) unsafe fn socket_connect(_kind: u32, _addr_ptr: usize, _addr_len: u32) -> i32 {
+    ExtensionErrorCode::NotFound.as_raw()
+}
+
+pub(crate) unsafe fn socket_read(_socket: u32, _buf_ptr: usize, _buf_len: u32) -> i32 {
+    UNKNOWN_HANDLE
+}
+
+pub(crate) unsafe fn socket_write(_socket: u32, _ptr: usize, _len: u32) -> i32 {
+    UNKNOWN_HANDLE
+}
+
+pub(crate) unsafe fn socket_close(_socket: u32) -> i32 {
+    UNKNOWN_HANDLE
+}
+
+pub(crate) unsafe fn fs_open(path_ptr: usize, path_len: u32, open_flags: u32) -> i32 {
+    let path = string(path_ptr, path_len);
+    let has = |flag| open_flags & flag != 0;
+    with(|state| {
+        match state.files.get_mut(&path) {
+            Some(contents) if has(flags::TRUNCATE) => contents.clear(),
+            Some(_) => {}
+            None if has(flags::CREATE) => {
+                state.files.insert(path.clone(), Vec::new());
+            }
+            None => return ExtensionErrorCode::NotFound.as_raw(),
+        }
+        let id = state.next_id();
+        let file = OpenFile {
+            path,
+            position: 0,
+            readable: has(flags::READ),
+            writable: has(flags::WRITE),
+            append: has(flags::APPEND),
+        };
+        state.open_files.insert(id, file);
+        id as i32
+    })
+}
+
+pub(crate) unsafe fn fs_read(file: u32, buf_ptr: usize, buf_len: u32) -> i32 {
+    let buf = bytes_mut(buf_ptr, buf_len);
+    with(|state| {
+        let Some(open) = state.open_files.get_mut(&file) else {
+            return UNKNOWN_HANDLE;
+        };
+        if !open.readable {
+            return ExtensionErrorCode::InvalidPayload.as_raw();
+        }
+        let contents = state.files.get(&open.path).map_or(&[][..], Vec::as_slice);
+        let start = (open.position as usize).min(contents.len());
+        let len = buf.len().min(contents.len() - start).min(i32::MAX as usize);
+        buf[..len].copy_from_slice(&contents[start..start + len]);
+        open.position += len as u64;
+        len as i32
+    })
+}
+
+pub(crate) unsafe fn fs_write(file: u32, ptr: usize, len: u32) -> i32 {
+    let data = bytes(ptr, len);
+    with(|state| {
+        let Some(open) = state.open_files.get_mut(&file) else {
+            return UNKNOWN_HANDLE;
+        };
+        if !open.writable {
+            return ExtensionErrorCode::InvalidPayload.as_raw();
+        }
+        let contents = state.files.entry(open.path.clone()).or_default();
+        if open.append {
+            open.position = contents.len() as u64;
+        }
+        let start = open.position as usize;
+        let len = data.len().min(i32::MAX as usize);
+        if contents.len() < start + len {
+            contents.resize(start + len, 0);
+        }
+        contents[start..start + len].copy_from_slice(&data[..len]);
+        open.position += len as u64;
+        len as i32
+    })
+}
+
+pub(crate) unsafe fn fs_seek(file: u32, from: u32, offset: i64) -> i64 {
+    with(|state| {
+        let Some(open) = state.open_files.get_mut(&file) else {
+            return UNKNOWN_HANDLE.into();
+        };
+        let len = state.files.get(&open.path).map_or(0, Vec::len);
+        let base = match from {
+            whence::START => 0,
+            whence::CURRENT => open.position as i64,
+            whence::END => len as i64,
+            _ => return ExtensionErrorCode::InvalidPayload.as_raw().into(),
+        };
+        match base.checked_add(offset) {
+            Some(position) if position >= 0 => {
+                open.position = position as u64;
+                position
+            }
+            _ => ExtensionErrorCode::InvalidPayload.as_raw().into(),
+        }
+    })
+}
+
+pub(crate) unsafe fn fs_close(file: u32) -> i32 {
+    match with(|state| state.open_files.remove(&file)) {
+        Some(_) => 0,
+        None => UNKNOWN_HANDLE,
+    }
+}
+
+/// Directories only exist as the prefixes of the files in them.
+pub(crate) unsafe fn fs_list(path_ptr: usize, path_len: u32) -> isize {
+    let path = string(path_ptr, path_len);
+    let dir = path.trim_end_matches('/');
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+
+    let entries = with(|state| {
+        let mut entries = BTreeMap::new();
+        for (file, contents) in &state.files {
+            let Some(rest) = file.strip_prefix(&prefix) else {
+                continue;
+            };
+            match rest.split_once('/') {
+                Some((name, _)) => entries.insert(name.to_string(), (true, 0)),
+                None => entries.insert(rest.to_string(), (false, contents.len() as u64)),
+            };
+        }
+        entries
+    });
+    if entries.is_empty() && !dir.is_empty() {
+        return ExtensionErrorCode::NotFound.as_raw() as isize;
+    }
+
+    let mut writer = Writer::new();
+    writer.write_u32(entries.len() as u32);
+    for (name, (is_dir, len)) in entries {
+        writer.write_str(&name);
+        writer.write_u8(is_dir as u8);
+        writer.write_u64(len);
+    }
+    to_guest(&writer.into_bytes())
+}
+
+pub(crate) unsafe fn fs_remove(path_ptr: usize, path_len: u32) -> i32 {
+    let path = string(path_ptr, path_len);
+    match with(|state| state.files.remove(&path)) {
+        Some(_) => 0,
+        None => ExtensionErrorCode::NotFound.as_raw(),
+    }
+}
+
+pub(crate) unsafe fn sleep(nanos: u64) {
+    let faked = with(|state| match &mut state.clock {
+        Some(clock) => {
+            clock.advance(nanos);
+            true
+        }
+        None => false,
+    });
+    if !faked {
+        std::thread::sleep(Duration::from_nanos(nanos));
+    }
+}
+
+pub(crate) unsafe fn yield_now() {
+    std::thread::yield_now();
+}
+
+pub(crate) unsafe fn random_fill(ptr: usize, len: u32) -> i32 {
+    let buf = bytes_mut(ptr, len);
+    with(|state| {
+        if state.rng == 0 {
+            // Xorshift gets stuck on zero.
+            state.rng = std::collections::hash_map::RandomState::new().hash_one(0u8) | 1;
+        }
+        for chunk in buf.chunks_mut(8) {
+            let mut x = state.rng;
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            state.rng = x;
+            chunk.copy_from_slice(&x.to_le_bytes()[..chunk.len()]);
+        }
+    });
+    0
+}
+
+pub(crate) unsafe fn log_raw(level: u32, ptr: usize, len: u32) {
+    let level = match level {
+        0 => Level::Trace,
+        1 => Level::Debug,
+        2 => Level::Info,
+        3 => Level::Warn,
+        _ => Level::Error,
+    };
+    let message = string(ptr, len);
+    with(|state| state.logs.push((level, message)));
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) unsafe fn trace_record(_ptr: usize, _len: u32) -> i32 {
+    0
+}
+
+/// The mock host never enables exchange memory, as it passes buffers by handle anyway.
+#[cfg(feature = "exchange-memory")]
+pub(crate) unsafe fn exchange_read(_offset: usize, _dst_ptr: usize, _len: u32) -> i32 {
+    ExtensionErrorCode::HostTrap.as_raw()
+}
+
+#[cfg(feature = "exchange-memory")]
+pub(crate) unsafe fn exchange_free(_offset: usize) -> i32 {
+    ExtensionErrorCode::HostTrap.as_raw()
+}
+
+pub(crate) unsafe fn report_error(_ptr: usize, _len: u32) -> i32 {
+    0
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use super::{Call, Fault, Reply};
+
+/// A mock host set up for one test: it starts from a clean slate, registers extensions as
+/// closures, and checks what the guest sent them.
+///
+/// ```ignore
+/// let host = TestHost::new()
+///     .register("kv", |key| Ok(format!("value of {}", String::from_utf8_lossy(key)).into()))
+///     .register_reply("audit", Ok(Vec::new()));
+///
+/// sync_profile("alice")?;
+///
+/// host.assert_called_with("kv", b"profile/alice");
+/// assert_eq!(host.calls_to("audit").len(), 1);
+/// ```
+///
+/// It's a view of the calling thread's mock host, so the free functions in `mock` (for job input,
+/// files and so on) apply to it too. Dropping it resets the mock host again, so nothing a test
+/// registers or records leaks into the next one on the same thread.
+#[derive(Debug)]
+pub struct TestHost {
+    _private: (),
+}
+
+impl TestHost {
+    /// Resets the mock host and returns a handle to it.
+    pub fn new() -> Self {
+        super::reset();
+        Self { _private: () }
+    }
+
+    /// Registers `handler` as the extension `name`; see `mock::respond`.
+    pub fn register(self, name: &str, handler: impl FnMut(&[u8]) -> Reply + 'static) -> Self {
+        super::respond(name, handler);
+        self
+    }
+
+    /// Registers an extension `name` that answers every call with `reply`.
+    pub fn register_reply(self, name: &str, reply: Reply) -> Self {
+        super::respond_with(name, reply);
+        self
+    }
+
+    /// Injects `fault` into every call to the extension `name`; see `mock::inject`.
+    pub fn inject(self, name: &str, fault: Fault) -> Self {
+        super::inject(name, fault);
+        self
+    }
+
+    /// Injects `fault` into the `nth` call to the extension `name`; see `mock::inject_nth`.
+    pub fn inject_nth(self, name: &str, nth: usize, fault: Fault) -> Self {
+        super::inject_nth(name, nth, fault);
+        self
+    }
+
+    /// Every invocation served so far, oldest first.
+    pub fn calls(&self) -> Vec<Call> {
+        super::calls()
+    }
+
+    /// The invocations of the extension `name` so far, oldest first.
+    pub fn calls_to(&self, name: &str) -> Vec<Call> {
+        let mut calls = self.calls();
+        calls.retain(|call| call.extension == name);
+        calls
+    }
+
+    /// Forgets the invocations recorded so far, to check only what a later step does.
+    pub fn clear_calls(&self) {
+        super::clear_calls();
+    }
+
+    /// Panics unless the extension `name` has been invoked.
+    #[track_caller]
+    pub fn assert_called(&self, name: &str) {
+        if self.calls_to(name).is_empty() {
+            panic!("expected a call to {name}, {}", self.describe_calls());
+        }
+    }
+
+    /// Panics unless the extension `name` has been invoked with exactly `payload`.
+    #[track_caller]
+    pub fn assert_called_with(&self, name: &str, payload: impl AsRef<[u8]>) {
+        let payload = payload.as_ref();
+        if !self
+            .calls_to(name)
+            .iter()
+            .any(|call| call.payload == payload)
+        {
+            panic!(
+                "expected a call to {name} with {:?}, {}",
+                String::from_utf8_lossy(payload),
+                self.describe_calls()
+            );
+        }
+    }
+
+    /// Panics if the extension `name` has been invoked.
+    #[track_caller]
+    pub fn assert_not_called(&self, name: &str) {
+        let calls = self.calls_to(name);
+        if !calls.is_empty() {
+            panic!("expected no calls to {name}, got {}", calls.len());
+        }
+    }
+
+    /// Lists the calls made so far, for assertion messages.
+    fn describe_calls(&self) -> String {
+        let calls = self.calls();
+        if calls.is_empty() {
+            return "but nothing was invoked".to_string();
+        }
+        let mut description = "but got:".to_string();
+        for call in calls {
+            let payload = String::from_utf8_lossy(&call.payload);
+            let _ = write!(description, "\n  {} with {payload:?}", call.extension);
+        }
+        description
+    }
+}
+
+impl Default for TestHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TestHost {
+    fn drop(&mut self) {
+        super::reset();
+    }
+}
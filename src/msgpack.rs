@@ -0,0 +1,42 @@
+//! Helpers for extensions that speak MessagePack, which is a lot more compact than JSON for large
+//! numeric payloads.
+
+use std::io::Cursor;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::{invoke_with_codec, MsgPack};
+use crate::{CodecError, Result, SdkError};
+
+/// Encodes a value as a MessagePack payload. Structs are encoded as maps keyed by field name so
+/// extensions written in other languages can decode them without knowing the field order.
+pub fn to_payload_msgpack<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    write_payload_msgpack(value, &mut payload)?;
+    Ok(payload)
+}
+
+/// Like `to_payload_msgpack`, but appends the payload to `buf`.
+pub fn write_payload_msgpack<T: Serialize + ?Sized>(value: &T, buf: &mut Vec<u8>) -> Result<()> {
+    rmp_serde::encode::write_named(buf, value)
+        .map_err(|err| SdkError::Encode(CodecError::new(err.to_string())))
+}
+
+/// Decodes a MessagePack payload. If decoding fails, the error carries the byte offset the
+/// decoder had reached.
+pub fn from_payload_msgpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let mut deserializer = rmp_serde::Deserializer::new(Cursor::new(bytes));
+    T::deserialize(&mut deserializer).map_err(|err| {
+        let offset = deserializer.get_ref().position() as usize;
+        SdkError::Decode(CodecError::new(err.to_string()).with_offset(offset))
+    })
+}
+
+/// Invokes an extension with a MessagePack-encoded request and decodes its MessagePack response.
+pub fn invoke_msgpack<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    invoke_with_codec(extension_name, &MsgPack, request)
+}
@@ -0,0 +1,153 @@
+//! TCP and UDP sockets proxied through the host, for protocols the HTTP extension can't speak.
+//! `TcpStream` implements `io::Read` and `io::Write`, so existing protocol crates can be layered
+//! on top of it.
+//!
+//! ```ignore
+//! let mut stream = serval::net::TcpStream::connect("redis.internal:6379")?;
+//! stream.write_all(b"PING\r\n")?;
+//! ```
+//!
+//! Every call blocks until the host has completed it. Addresses are `host:port` strings, resolved
+//! by the host.
+
+use std::io;
+
+use crate::{host, ExtensionErrorCode, Result};
+
+/// The socket kinds `socket_connect` takes.
+mod kinds {
+    pub const TCP: u32 = 0;
+    pub const UDP: u32 = 1;
+}
+
+/// A socket opened by the host: the shared plumbing behind `TcpStream` and `UdpSocket`.
+#[derive(Debug)]
+struct Socket {
+    id: u32,
+    addr: String,
+    closed: bool,
+}
+
+impl Socket {
+    fn connect(kind: u32, addr: &str) -> Result<Self> {
+        let id = unsafe { host::socket_connect(kind, addr.as_ptr() as usize, addr.len() as u32) };
+        Ok(Self {
+            id: check(id)?,
+            addr: addr.to_string(),
+            closed: false,
+        })
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let len =
+            unsafe { host::socket_read(self.id, buf.as_mut_ptr() as usize, buf.len() as u32) };
+        Ok(check(len)? as usize)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let len = unsafe { host::socket_write(self.id, buf.as_ptr() as usize, buf.len() as u32) };
+        Ok(check(len)? as usize)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.closed = true;
+        let status = unsafe { host::socket_close(self.id) };
+        check(status).map(drop)
+    }
+}
+
+/// Turns a socket call's return value into the non-negative value it carries, or the error it
+/// reports.
+fn check(status: i32) -> Result<u32> {
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(status as u32)
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if !self.closed {
+            unsafe { host::socket_close(self.id) };
+        }
+    }
+}
+
+/// A TCP connection proxied through the host.
+#[derive(Debug)]
+pub struct TcpStream {
+    socket: Socket,
+}
+
+impl TcpStream {
+    /// Connects to `addr`, a `host:port` string.
+    pub fn connect(addr: &str) -> Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(kinds::TCP, addr)?,
+        })
+    }
+
+    /// The address the stream was connected to.
+    pub fn peer_addr(&self) -> &str {
+        &self.socket.addr
+    }
+
+    /// Closes the connection, reporting any error the host ran into doing so. Dropping the stream
+    /// closes it too, but ignores errors.
+    pub fn close(mut self) -> Result<()> {
+        self.socket.close()
+    }
+}
+
+impl io::Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.socket.read(buf).map_err(io::Error::other)
+    }
+}
+
+impl io::Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.socket.write(buf).map_err(io::Error::other)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A UDP socket proxied through the host, connected to a single peer.
+#[derive(Debug)]
+pub struct UdpSocket {
+    socket: Socket,
+}
+
+impl UdpSocket {
+    /// Opens a socket that sends datagrams to and receives them from `addr`, a `host:port`
+    /// string.
+    pub fn connect(addr: &str) -> Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(kinds::UDP, addr)?,
+        })
+    }
+
+    /// The address the socket was connected to.
+    pub fn peer_addr(&self) -> &str {
+        &self.socket.addr
+    }
+
+    /// Sends `buf` as a single datagram, returning how many bytes were sent.
+    pub fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.socket.write(buf)
+    }
+
+    /// Waits for a datagram and copies it into `buf`, returning its length. Whatever doesn't fit
+    /// in `buf` is discarded.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.socket.read(buf)
+    }
+
+    /// Closes the socket, reporting any error the host ran into doing so.
+    pub fn close(mut self) -> Result<()> {
+        self.socket.close()
+    }
+}
@@ -0,0 +1,32 @@
+use std::panic::PanicHookInfo;
+
+use crate::{report_error, ExtensionErrorCode, GuestError};
+
+/// Installs a panic hook that reports the panic message and location to the host as a
+/// `GuestError` before the module traps. Any previously installed hook still runs afterwards.
+///
+/// Call this once at the start of your entrypoint.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // There's nothing useful we can do if reporting fails; we're about to trap anyway.
+        let _ = report_error(&panic_to_error(info));
+        previous(info);
+    }));
+}
+
+fn panic_to_error(info: &PanicHookInfo<'_>) -> GuestError {
+    let payload = info.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Box<dyn Any>"
+    };
+    let message = match info.location() {
+        Some(location) => format!("panicked at {location}: {message}"),
+        None => format!("panicked: {message}"),
+    };
+    GuestError::new(ExtensionErrorCode::Panicked.as_raw(), message).capture_backtrace()
+}
@@ -0,0 +1,114 @@
+//! The permission manifest: which extensions and capabilities a guest intends to use, declared
+//! with `serval::manifest!` and embedded in the module as the `SECTION` custom section. Hosts can
+//! read it without instantiating the module to check the job's grants before running it, and keep
+//! it for auditing.
+//!
+//! ```ignore
+//! serval::manifest! {
+//!     extensions = ["geo.lookup", "kv"],
+//!     capabilities = ["network", "storage"],
+//! }
+//! ```
+//!
+//! Capabilities are named as in `caps::Capability`. The section holds, with integers
+//! little-endian and strings length-prefixed:
+//!
+//! ```text
+//! u8   manifest version (VERSION)
+//! u32  number of extensions, then each extension's name
+//! u32  number of capabilities, then each capability's name
+//! ```
+
+use crate::caps::Capability;
+use crate::wire::Reader;
+use crate::{Result, SdkError};
+
+/// The name of the custom section `manifest!` embeds.
+pub const SECTION: &str = "serval.manifest";
+
+/// The version of the section layout described above.
+pub const VERSION: u8 = 1;
+
+/// A guest's permission manifest, as read back from its `SECTION`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PermissionManifest {
+    pub extensions: Vec<String>,
+    pub capabilities: Vec<Capability>,
+}
+
+impl PermissionManifest {
+    /// Decodes the contents of a module's `SECTION`.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_u8()? != VERSION {
+            return Err(SdkError::InvalidPayload);
+        }
+        let mut extensions = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            extensions.push(reader.read_str()?.to_string());
+        }
+        let mut capabilities = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            capabilities.push(Capability::parse(reader.read_str()?));
+        }
+        Ok(Self {
+            extensions,
+            capabilities,
+        })
+    }
+}
+
+/// The size of the section `encode` produces, which `manifest!` needs for the type of the static
+/// it's placed in.
+#[doc(hidden)]
+pub const fn encoded_len(extensions: &[&str], capabilities: &[&str]) -> usize {
+    1 + list_len(extensions) + list_len(capabilities)
+}
+
+const fn list_len(names: &[&str]) -> usize {
+    let mut len = 4;
+    let mut i = 0;
+    while i < names.len() {
+        len += 4 + names[i].len();
+        i += 1;
+    }
+    len
+}
+
+/// Encodes the section at compile time, for `manifest!`. `N` must be `encoded_len` of the same
+/// lists.
+#[doc(hidden)]
+pub const fn encode<const N: usize>(extensions: &[&str], capabilities: &[&str]) -> [u8; N] {
+    let mut section = [0; N];
+    section[0] = VERSION;
+    let offset = write_list(&mut section, 1, extensions);
+    write_list(&mut section, offset, capabilities);
+    section
+}
+
+const fn write_list<const N: usize>(section: &mut [u8; N], offset: usize, names: &[&str]) -> usize {
+    let mut offset = write_u32(section, offset, names.len() as u32);
+    let mut i = 0;
+    while i < names.len() {
+        let name = names[i].as_bytes();
+        offset = write_u32(section, offset, name.len() as u32);
+        let mut j = 0;
+        while j < name.len() {
+            section[offset + j] = name[j];
+            j += 1;
+        }
+        offset += name.len();
+        i += 1;
+    }
+    offset
+}
+
+const fn write_u32<const N: usize>(section: &mut [u8; N], offset: usize, value: u32) -> usize {
+    let bytes = value.to_le_bytes();
+    let mut i = 0;
+    while i < 4 {
+        section[offset + i] = bytes[i];
+        i += 1;
+    }
+    offset + 4
+}
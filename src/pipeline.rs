@@ -0,0 +1,62 @@
+//! Chains of extension calls that the host runs on our behalf.
+//!
+//! A pipeline is sent as a u32 stage count, that many length-prefixed extension names, and then the
+//! payload for the first stage as the remainder. The host feeds each stage's output straight into
+//! the next one and only copies the last stage's output back to us, so intermediate results never
+//! enter guest memory.
+
+use crate::wire::Writer;
+use crate::{host, read_response, Result};
+
+/// A sequence of extensions to call one after the other, each receiving the previous one's output.
+///
+/// ```ignore
+/// let thumbnail = Pipeline::new()
+///     .then("image-decode")
+///     .then("image-resize")
+///     .then("image-encode")
+///     .invoke(&upload)?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    stages: Vec<String>,
+}
+
+impl Pipeline {
+    /// An empty pipeline. Invoking it returns the payload unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an extension to the end of the pipeline.
+    pub fn then(mut self, extension_name: impl Into<String>) -> Self {
+        self.stages.push(extension_name.into());
+        self
+    }
+
+    /// The extensions in the pipeline, in the order they're called.
+    pub fn stages(&self) -> &[String] {
+        &self.stages
+    }
+
+    /// Runs the pipeline on the host with `data` as the input to the first stage and returns the
+    /// output of the last one. Errors name the whole pipeline; `last_error` reports which stage
+    /// failed.
+    pub fn invoke(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if self.stages.is_empty() {
+            return Ok(data.to_vec());
+        }
+
+        let mut writer = Writer::new();
+        writer.write_u32(self.stages.len() as u32);
+        for stage in &self.stages {
+            writer.write_str(stage);
+        }
+        writer.write_bytes(data);
+        let encoded = writer.into_bytes();
+
+        let out_ptr =
+            unsafe { host::invoke_pipeline(encoded.as_ptr() as usize, encoded.len() as u32) };
+        read_response(out_ptr, &self.stages.join(" | "), data.len())
+    }
+}
@@ -0,0 +1,153 @@
+//! A pool of the buffers our `alloc` export hands out, so long-running jobs reuse the same blocks
+//! for every response instead of fragmenting linear memory with fresh allocations.
+//!
+//! Requests up to `MAX_POOLED_SIZE` bytes are rounded up to a power-of-two size class (at least
+//! `MIN_BLOCK_SIZE`), and freed blocks are kept per class for the next allocation of that class, up
+//! to a cap on the total pooled bytes. Larger requests bypass the pool. Since a block's class only
+//! depends on the length it was requested with, `dealloc(ptr, len)` always knows the real size of
+//! the block it's freeing.
+
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// The smallest block handed out; smaller requests are rounded up to it.
+pub const MIN_BLOCK_SIZE: usize = 64;
+/// The largest request served from the pool.
+pub const MAX_POOLED_SIZE: usize = 1 << 20;
+/// The total size of the blocks the pool keeps around by default: 4 MiB.
+pub const DEFAULT_CAPACITY: usize = 4 << 20;
+
+thread_local! {
+    static POOL: RefCell<Pool> = const {
+        RefCell::new(Pool {
+            free: BTreeMap::new(),
+            pooled_bytes: 0,
+            capacity: DEFAULT_CAPACITY,
+        })
+    };
+}
+
+struct Pool {
+    /// Free blocks by size class.
+    free: BTreeMap<usize, Vec<usize>>,
+    pooled_bytes: usize,
+    capacity: usize,
+}
+
+impl Pool {
+    fn take(&mut self, size: usize) -> Option<*mut u8> {
+        let ptr = self.free.get_mut(&size)?.pop()?;
+        self.pooled_bytes -= size;
+        Some(ptr as *mut u8)
+    }
+
+    /// Keeps `ptr` for reuse if there's room, otherwise hands it back.
+    fn put(&mut self, ptr: *mut u8, size: usize) -> Option<*mut u8> {
+        if size > MAX_POOLED_SIZE || self.pooled_bytes + size > self.capacity {
+            return Some(ptr);
+        }
+        self.free.entry(size).or_default().push(ptr as usize);
+        self.pooled_bytes += size;
+        None
+    }
+
+    fn trim(&mut self, capacity: usize) {
+        while self.pooled_bytes > capacity {
+            // Drop the largest blocks first; they're the least likely to be reused.
+            let Some(mut entry) = self.free.last_entry() else {
+                break;
+            };
+            let size = *entry.key();
+            if let Some(ptr) = entry.get_mut().pop() {
+                self.pooled_bytes -= size;
+                unsafe { std::alloc::dealloc(ptr as *mut u8, layout(size)) };
+            }
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.trim(0);
+    }
+}
+
+/// The size of the block actually allocated for a request of `len` bytes.
+pub(crate) fn block_size(len: usize) -> usize {
+    if len > MAX_POOLED_SIZE {
+        len
+    } else {
+        len.max(MIN_BLOCK_SIZE).next_power_of_two()
+    }
+}
+
+/// Same layout as a `Vec<u8>` with capacity `size`, so a block can be turned into one.
+fn layout(size: usize) -> Layout {
+    Layout::array::<u8>(size).expect("block sizes are validated when they're allocated")
+}
+
+/// Allocates a block for `len` bytes, reusing a pooled one if possible. Returns null if `len` is
+/// zero or the allocation fails.
+pub(crate) fn allocate(len: usize) -> *mut u8 {
+    if len == 0 || Layout::array::<u8>(len).is_err() {
+        return std::ptr::null_mut();
+    }
+    let size = block_size(len);
+    POOL.with_borrow_mut(|pool| pool.take(size))
+        .unwrap_or_else(|| unsafe { std::alloc::alloc(layout(size)) })
+}
+
+/// Frees a block allocated by `allocate(len)`, keeping it in the pool if there's room.
+///
+/// # Safety
+/// `ptr` must have come from `allocate(len)` with the same `len`, and not have been freed already.
+pub(crate) unsafe fn release(ptr: *mut u8, len: usize) {
+    let size = block_size(len);
+    if let Some(ptr) = POOL.with_borrow_mut(|pool| pool.put(ptr, size)) {
+        std::alloc::dealloc(ptr, layout(size));
+    }
+}
+
+/// Fills the pool with `count` blocks big enough for `len`-byte buffers ahead of time, as far as
+/// the pool's capacity allows, so the first responses of a job don't pay for fresh allocations.
+pub fn prewarm(len: usize, count: usize) {
+    if len == 0 || len > MAX_POOLED_SIZE {
+        return;
+    }
+    let size = block_size(len);
+    POOL.with_borrow_mut(|pool| {
+        for _ in 0..count {
+            let ptr = unsafe { std::alloc::alloc(layout(size)) };
+            if ptr.is_null() {
+                break;
+            }
+            if let Some(ptr) = pool.put(ptr, size) {
+                unsafe { std::alloc::dealloc(ptr, layout(size)) };
+                break;
+            }
+        }
+    });
+}
+
+/// Frees every block in the pool.
+pub fn clear() {
+    POOL.with_borrow_mut(|pool| pool.trim(0));
+}
+
+/// Sets the total size of the blocks the pool may keep, freeing blocks if it's already holding
+/// more than that. Zero disables pooling. Defaults to `DEFAULT_CAPACITY`.
+pub fn set_capacity(bytes: usize) {
+    POOL.with_borrow_mut(|pool| {
+        pool.capacity = bytes;
+        pool.trim(bytes);
+    });
+}
+
+/// The total size of the blocks currently held by the pool.
+pub fn pooled_bytes() -> usize {
+    POOL.with_borrow(|pool| pool.pooled_bytes)
+}
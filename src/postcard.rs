@@ -0,0 +1,107 @@
+//! Helpers for the postcard format, a compact serde encoding that doesn't need std. This is the
+//! format to reach for in guests compiled for minimal size.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::{invoke_with_codec, Postcard};
+use crate::{CodecError, Result, SdkError};
+
+/// Encodes a value as a postcard payload.
+pub fn to_payload_postcard<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    write_payload_postcard(value, &mut payload)?;
+    Ok(payload)
+}
+
+/// Like `to_payload_postcard`, but appends the payload to `buf`.
+pub fn write_payload_postcard<T: Serialize + ?Sized>(value: &T, buf: &mut Vec<u8>) -> Result<()> {
+    *buf = ::postcard::to_extend(value, std::mem::take(buf))
+        .map_err(|err| SdkError::Encode(CodecError::new(err.to_string())))?;
+    Ok(())
+}
+
+/// Decodes a postcard payload. Trailing bytes after the value are rejected, since they almost
+/// always mean the two sides disagree about the schema.
+pub fn from_payload_postcard<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let (value, rest) = ::postcard::take_from_bytes(bytes)
+        .map_err(|err| SdkError::Decode(CodecError::new(err.to_string())))?;
+    if !rest.is_empty() {
+        let offset = bytes.len() - rest.len();
+        return Err(SdkError::Decode(
+            CodecError::new("trailing bytes after postcard value").with_offset(offset),
+        ));
+    }
+    Ok(value)
+}
+
+/// Invokes an extension with a postcard-encoded request and decodes its postcard response.
+pub fn invoke_postcard<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    invoke_with_codec(extension_name, &Postcard, request)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{from_payload_postcard, to_payload_postcard, write_payload_postcard};
+    use crate::codec::{Codec, Postcard};
+    use crate::frame::Frame;
+    use crate::framing::{decode_frame, encode_frame};
+    use crate::SdkError;
+
+    type Record = (u32, i64, String, Vec<u8>, Option<bool>);
+
+    proptest! {
+        #[test]
+        fn payloads_round_trip_through_a_length_prefixed_frame(record in any::<Record>()) {
+            let framed = encode_frame(&to_payload_postcard(&record)?);
+            let (payload, rest) = decode_frame(&framed)?;
+            prop_assert!(rest.is_empty());
+            prop_assert_eq!(from_payload_postcard::<Record>(payload)?, record);
+        }
+
+        #[test]
+        fn payloads_round_trip_through_an_invocation_frame(record in any::<Record>()) {
+            let request =
+                Frame::new(Postcard.encode(&record)?).with_content_type(Postcard.content_type());
+            let decoded = Frame::decode(&request.encode()?)?;
+            prop_assert_eq!(decoded.content_type(), Some(crate::content_type::POSTCARD));
+            prop_assert_eq!(from_payload_postcard::<Record>(&decoded.body)?, record);
+        }
+    }
+
+    #[test]
+    fn appended_payloads_decode_one_frame_at_a_time() {
+        let mut first = Vec::new();
+        write_payload_postcard(&(1u8, "one"), &mut first).unwrap();
+        let mut framed = encode_frame(&first);
+        framed.extend_from_slice(&encode_frame(&to_payload_postcard(&(2u8, "two")).unwrap()));
+
+        let (payload, rest) = decode_frame(&framed).unwrap();
+        assert_eq!(
+            from_payload_postcard::<(u8, String)>(payload).unwrap(),
+            (1, "one".into())
+        );
+        let (payload, rest) = decode_frame(rest).unwrap();
+        assert_eq!(
+            from_payload_postcard::<(u8, String)>(payload).unwrap(),
+            (2, "two".into())
+        );
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected_with_their_offset() {
+        let mut payload = to_payload_postcard(&7u32).unwrap();
+        let end = payload.len();
+        payload.push(0);
+        match from_payload_postcard::<u32>(&payload) {
+            Err(SdkError::Decode(err)) => assert_eq!(err.offset, Some(end)),
+            other => panic!("expected a decode error, got {other:?}"),
+        }
+    }
+}
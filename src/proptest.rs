@@ -0,0 +1,119 @@
+//! Proptest strategies for the layouts exchanged with the host, for property tests of code built
+//! on them.
+//!
+//! ```ignore
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn frames_round_trip(frame in serval::proptest::frame()) {
+//!         prop_assert_eq!(Frame::decode(&frame.encode()?)?, frame);
+//!     }
+//! }
+//! ```
+
+use ::proptest::collection::vec;
+use ::proptest::prelude::*;
+
+use crate::envelope::Envelope;
+use crate::frame::{Frame, HeaderField};
+use crate::ExtensionErrorCode;
+
+/// Payloads of up to 4 KiB of arbitrary bytes.
+pub fn payload() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..=4096)
+}
+
+/// Header fields with any tag, known or not, and short values.
+pub fn header_field() -> impl Strategy<Value = HeaderField> {
+    (any::<u8>(), vec(any::<u8>(), 0..64)).prop_map(|(tag, value)| HeaderField { tag, value })
+}
+
+/// Frames with any flags, up to 8 header fields and a `payload` body.
+pub fn frame() -> impl Strategy<Value = Frame> {
+    (any::<u8>(), vec(header_field(), 0..8), payload()).prop_map(|(flags, fields, body)| Frame {
+        flags,
+        fields,
+        body,
+    })
+}
+
+/// Envelopes with any schema version, a short content type and a `payload` body.
+pub fn envelope() -> impl Strategy<Value = Envelope> {
+    (any::<u32>(), "[a-z]{1,12}/[a-z0-9.+-]{1,20}", payload()).prop_map(
+        |(schema_version, content_type, body)| Envelope::new(schema_version, content_type, body),
+    )
+}
+
+/// Every error code in the table, plus unknown negative codes.
+pub fn error_code() -> impl Strategy<Value = ExtensionErrorCode> {
+    (i32::MIN..0).prop_map(ExtensionErrorCode::from)
+}
+
+/// `bytes` cut into consecutive chunks at arbitrary points, some of them empty, the way a stream
+/// may deliver it. Concatenating the chunks gives back `bytes`.
+pub fn chunks(bytes: Vec<u8>) -> impl Strategy<Value = Vec<Vec<u8>>> {
+    let len = bytes.len();
+    vec(0..=len, 0..16).prop_map(move |mut cuts| {
+        cuts.sort_unstable();
+        let mut chunks = Vec::with_capacity(cuts.len() + 1);
+        let mut start = 0;
+        for cut in cuts {
+            chunks.push(bytes[start..cut].to_vec());
+            start = cut;
+        }
+        chunks.push(bytes[start..].to_vec());
+        chunks
+    })
+}
+
+/// A `payload` along with a way of cutting it into `chunks`.
+pub fn chunked_payload() -> impl Strategy<Value = (Vec<u8>, Vec<Vec<u8>>)> {
+    payload().prop_flat_map(|payload| (Just(payload.clone()), chunks(payload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::proptest::prelude::*;
+
+    use super::{chunked_payload, envelope, error_code, frame, header_field, payload};
+    use crate::envelope::Envelope;
+    use crate::frame::Frame;
+    use crate::framing::{decode_frame, encode_frame};
+    use crate::ExtensionErrorCode;
+
+    proptest! {
+        #[test]
+        fn payloads_round_trip_through_framing(payload in payload()) {
+            let framed = encode_frame(&payload);
+            prop_assert_eq!(decode_frame(&framed)?, (&payload[..], &[][..]));
+        }
+
+        #[test]
+        fn header_fields_round_trip_in_a_frame(field in header_field()) {
+            let mut frame = Frame::new(Vec::new());
+            frame.push_field(field.tag, field.value.clone());
+            prop_assert_eq!(Frame::decode(&frame.encode()?)?.fields, vec![field]);
+        }
+
+        #[test]
+        fn frames_round_trip(frame in frame()) {
+            prop_assert_eq!(Frame::decode(&frame.encode()?)?, frame);
+        }
+
+        #[test]
+        fn envelopes_round_trip(envelope in envelope()) {
+            prop_assert_eq!(Envelope::decode(&envelope.encode())?, envelope);
+        }
+
+        #[test]
+        fn error_codes_round_trip_through_their_raw_value(code in error_code()) {
+            prop_assert_eq!(ExtensionErrorCode::from(code.as_raw()), code);
+        }
+
+        #[test]
+        fn chunks_concatenate_to_the_payload((payload, chunks) in chunked_payload()) {
+            prop_assert_eq!(chunks.concat(), payload);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Helpers for extensions with existing protobuf contracts. Request and response types are the
+//! structs generated by prost-build from the shared .proto files.
+
+use prost::Message;
+
+use crate::{invoke_extension, CodecError, Result, SdkError};
+
+/// Encodes a protobuf message as a payload.
+pub fn to_payload_proto<T: Message>(message: &T) -> Vec<u8> {
+    message.encode_to_vec()
+}
+
+/// Decodes a protobuf payload.
+pub fn from_payload_proto<T: Message + Default>(bytes: &[u8]) -> Result<T> {
+    T::decode(bytes).map_err(|err| SdkError::Decode(CodecError::new(err.to_string())))
+}
+
+/// Invokes an extension with a protobuf-encoded request and decodes its protobuf response.
+pub fn invoke_proto<Req: Message, Resp: Message + Default>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    let response = invoke_extension(extension_name, to_payload_proto(request))?;
+    from_payload_proto(&response)
+}
@@ -0,0 +1,155 @@
+//! A client for the mesh's message bus, for jobs that coordinate by publishing to and subscribing
+//! to topics.
+//!
+//! ```ignore
+//! use serval::pubsub::{self, Subscription};
+//!
+//! pubsub::publish("builds.finished", &report)?;
+//!
+//! let subscription = Subscription::subscribe("builds.requested")?;
+//! for message in subscription.poll(16, Duration::from_secs(5))? {
+//!     handle(message.payload);
+//! }
+//! ```
+//!
+//! Messages can also be pushed to the guest as they arrive with `Subscription::subscribe_with`,
+//! which registers a `Callback` for them. Like every callback, it only runs while the guest is
+//! waiting in a host call, such as `time::sleep` or an extension invocation.
+//!
+//! Requests are frames sent to the `EXTENSION` extension, laid out like `kv`'s:
+//!
+//! | operation | request body | response body |
+//! |---|---|---|
+//! | `publish` | topic, payload | u64 message id |
+//! | `subscribe` | topic | subscription id |
+//! | `poll` | subscription id, u32 max messages, u32 wait in milliseconds | u32 count, that many messages |
+//! | `unsubscribe` | subscription id | empty |
+//!
+//! A `subscribe` request carrying a `tags::CALLBACK` field has messages delivered to that
+//! callback, under the name `message`, instead of queued for `poll`; each call's frame body is a
+//! single message. A message is its length-prefixed topic, its u64 id and its length-prefixed
+//! payload.
+
+use std::time::Duration;
+
+use crate::frame::{tags, Frame};
+use crate::wire::{Reader, Writer};
+use crate::{service, Callback, GuestError, Result};
+
+/// The extension the message bus is registered under.
+pub const EXTENSION: &str = "pubsub";
+
+/// A message received on a subscription.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub topic: String,
+    /// Identifies the message on the bus, for deduplicating redeliveries.
+    pub id: u64,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    fn read(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(Self {
+            topic: reader.read_str()?.to_string(),
+            id: reader.read_u64()?,
+            payload: reader.read_prefixed()?.to_vec(),
+        })
+    }
+}
+
+/// Publishes `payload` on `topic` and returns the id the bus assigned to the message.
+pub fn publish(topic: &str, payload: &[u8]) -> Result<u64> {
+    let mut body = Writer::new();
+    body.write_str(topic);
+    body.write_bytes(payload);
+    let response = service::call(EXTENSION, "publish", body.into_bytes())?;
+    Reader::new(&response).read_u64()
+}
+
+/// A subscription to a topic. Dropping it unsubscribes.
+#[derive(Debug)]
+pub struct Subscription {
+    id: String,
+    topic: String,
+    /// The callback messages are pushed to, for subscriptions made with `subscribe_with`.
+    callback: Option<Callback>,
+    closed: bool,
+}
+
+impl Subscription {
+    /// Subscribes to `topic`, queueing its messages on the bus until they're fetched with `poll`.
+    pub fn subscribe(topic: &str) -> Result<Self> {
+        Self::open(topic, None)
+    }
+
+    /// Subscribes to `topic`, calling `f` with each of its messages; see the module docs for when
+    /// it runs.
+    pub fn subscribe_with(topic: &str, mut f: impl FnMut(Message) + 'static) -> Result<Self> {
+        let callback = Callback::register("message", move |frame: Frame| {
+            let message = Message::read(&mut Reader::new(&frame.body)).map_err(GuestError::from)?;
+            f(message);
+            Ok::<_, GuestError>(())
+        });
+        Self::open(topic, Some(callback))
+    }
+
+    fn open(topic: &str, callback: Option<Callback>) -> Result<Self> {
+        let mut body = Writer::new();
+        body.write_str(topic);
+        let mut request = service::request("subscribe", body.into_bytes());
+        if let Some(callback) = &callback {
+            request.push_field(tags::CALLBACK, callback.header_value());
+        }
+        let response = service::send(EXTENSION, request)?;
+        Ok(Self {
+            id: Reader::new(&response).read_str()?.to_string(),
+            topic: topic.to_string(),
+            callback,
+            closed: false,
+        })
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Fetches up to `max` queued messages, waiting up to `wait` for the first one to arrive.
+    /// Returns an empty list if none did. Subscriptions made with `subscribe_with` never have
+    /// anything queued.
+    pub fn poll(&self, max: u32, wait: Duration) -> Result<Vec<Message>> {
+        let mut body = Writer::new();
+        body.write_str(&self.id);
+        body.write_u32(max);
+        body.write_u32(u32::try_from(wait.as_millis()).unwrap_or(u32::MAX));
+        let response = service::call(EXTENSION, "poll", body.into_bytes())?;
+
+        let mut reader = Reader::new(&response);
+        let count = reader.read_u32()?;
+        (0..count).map(|_| Message::read(&mut reader)).collect()
+    }
+
+    /// Unsubscribes, reporting any error the bus ran into. Dropping the subscription does the
+    /// same but ignores errors.
+    pub fn unsubscribe(mut self) -> Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.closed = true;
+        let mut body = Writer::new();
+        body.write_str(&self.id);
+        service::call(EXTENSION, "unsubscribe", body.into_bytes())?;
+        // No more messages are coming, so the callback can go.
+        self.callback = None;
+        Ok(())
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = self.close();
+        }
+    }
+}
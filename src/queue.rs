@@ -0,0 +1,173 @@
+//! A client for the platform's work queues, for worker-style jobs that consume tasks.
+//!
+//! ```ignore
+//! use serval::queue::Queue;
+//!
+//! let queue = Queue::new("thumbnails");
+//! while let Some(lease) = queue.dequeue(Duration::from_secs(60), Duration::from_secs(5))? {
+//!     match render(lease.payload()) {
+//!         Ok(()) => lease.ack()?,
+//!         Err(_) => lease.nack(Duration::from_secs(30))?,
+//!     }
+//! }
+//! ```
+//!
+//! Dequeuing a task leases it: the task stays on the queue, hidden from other consumers, until the
+//! lease's visibility timeout runs out. Acking the lease removes the task for good; nacking it or
+//! letting the lease expire makes the task visible again, so a worker that dies mid-task doesn't
+//! lose it. Work that runs longer than expected can keep its lease with `Lease::extend`. Dropping a
+//! lease does nothing, leaving the task to reappear once the lease expires.
+//!
+//! Requests are frames sent to the `EXTENSION` extension, laid out like `kv`'s, with durations as
+//! u32 milliseconds:
+//!
+//! | operation | request body | response body |
+//! |---|---|---|
+//! | `enqueue` | queue, delay, payload | task id |
+//! | `dequeue` | queue, visibility timeout, wait | u8 found, then if found: task id, lease receipt, u32 attempts, length-prefixed payload |
+//! | `ack` | queue, lease receipt | empty |
+//! | `nack` | queue, lease receipt, delay | empty |
+//! | `extend` | queue, lease receipt, visibility timeout | empty |
+//!
+//! Acting on a lease that has already expired fails with `frame::status::CONFLICT`, since the task
+//! may have been handed to another consumer in the meantime.
+
+use std::time::Duration;
+
+use crate::service;
+use crate::wire::{Reader, Writer};
+use crate::Result;
+
+/// The extension the platform's work queues are registered under.
+pub const EXTENSION: &str = "queue";
+
+fn millis(duration: Duration) -> u32 {
+    u32::try_from(duration.as_millis()).unwrap_or(u32::MAX)
+}
+
+/// A handle to a named queue.
+#[derive(Clone, Debug)]
+pub struct Queue {
+    name: String,
+    extension: String,
+}
+
+impl Queue {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            extension: EXTENSION.to_string(),
+        }
+    }
+
+    /// Talks to a queue service registered under a different extension name.
+    pub fn with_extension(mut self, extension_name: impl Into<String>) -> Self {
+        self.extension = extension_name.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds a task to the queue and returns its id.
+    pub fn enqueue(&self, payload: &[u8]) -> Result<String> {
+        self.enqueue_delayed(payload, Duration::ZERO)
+    }
+
+    /// Adds a task that only becomes visible to consumers once `delay` has passed.
+    pub fn enqueue_delayed(&self, payload: &[u8], delay: Duration) -> Result<String> {
+        let mut body = self.body();
+        body.write_u32(millis(delay));
+        body.write_bytes(payload);
+        let response = service::call(&self.extension, "enqueue", body.into_bytes())?;
+        Ok(Reader::new(&response).read_str()?.to_string())
+    }
+
+    /// Leases the next visible task for `visibility_timeout`, waiting up to `wait` for one to
+    /// become available. Returns `None` if none did.
+    pub fn dequeue(&self, visibility_timeout: Duration, wait: Duration) -> Result<Option<Lease>> {
+        let mut body = self.body();
+        body.write_u32(millis(visibility_timeout));
+        body.write_u32(millis(wait));
+        let response = service::call(&self.extension, "dequeue", body.into_bytes())?;
+
+        let mut reader = Reader::new(&response);
+        if reader.read_u8()? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Lease {
+            queue: self.clone(),
+            task_id: reader.read_str()?.to_string(),
+            receipt: reader.read_str()?.to_string(),
+            attempts: reader.read_u32()?,
+            payload: reader.read_prefixed()?.to_vec(),
+        }))
+    }
+
+    /// Starts a request body with the queue's name.
+    fn body(&self) -> Writer {
+        let mut body = Writer::new();
+        body.write_str(&self.name);
+        body
+    }
+}
+
+/// A task leased from a queue; see the module docs.
+#[derive(Debug)]
+pub struct Lease {
+    queue: Queue,
+    task_id: String,
+    receipt: String,
+    attempts: u32,
+    payload: Vec<u8>,
+}
+
+impl Lease {
+    pub fn task_id(&self) -> &str {
+        &self.task_id
+    }
+
+    /// How many times the task has been leased, including this time.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
+    }
+
+    /// Marks the task as done, removing it from the queue.
+    pub fn ack(self) -> Result<()> {
+        let body = self.body();
+        service::call(&self.queue.extension, "ack", body.into_bytes())?;
+        Ok(())
+    }
+
+    /// Gives the task back, to become visible again after `delay`.
+    pub fn nack(self, delay: Duration) -> Result<()> {
+        let mut body = self.body();
+        body.write_u32(millis(delay));
+        service::call(&self.queue.extension, "nack", body.into_bytes())?;
+        Ok(())
+    }
+
+    /// Keeps the task hidden for another `visibility_timeout`, counted from now.
+    pub fn extend(&self, visibility_timeout: Duration) -> Result<()> {
+        let mut body = self.body();
+        body.write_u32(millis(visibility_timeout));
+        service::call(&self.queue.extension, "extend", body.into_bytes())?;
+        Ok(())
+    }
+
+    /// Starts a request body with the queue's name and the lease's receipt.
+    fn body(&self) -> Writer {
+        let mut body = self.queue.body();
+        body.write_str(&self.receipt);
+        body
+    }
+}
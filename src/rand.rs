@@ -0,0 +1,65 @@
+//! Random bytes from the host's entropy source, for UUIDs, nonces and keys.
+//!
+//! With the `rand_core` feature, `HostRng` plugs the same source into the `rand` ecosystem. With
+//! the `getrandom` feature, the SDK registers it as getrandom's custom backend, so crates that call
+//! `getrandom` directly work too; build with `--cfg getrandom_backend="custom"` to use it.
+
+use crate::{host, ExtensionErrorCode, Result};
+
+/// Fills `dest` with cryptographically secure random bytes from the host.
+pub fn fill(dest: &mut [u8]) -> Result<()> {
+    let status = unsafe { host::random_fill(dest.as_mut_ptr() as usize, dest.len() as u32) };
+    if status < 0 {
+        return Err(ExtensionErrorCode::from(status).into());
+    }
+    Ok(())
+}
+
+/// A `rand_core` generator backed by the host's entropy source. It has no state of its own, so
+/// it's free to create and every instance draws from the same source. Panics if the host can't
+/// supply entropy, as `rand`'s own `OsRng` does.
+#[cfg(feature = "rand_core")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HostRng;
+
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for HostRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(err) = fill(dest) {
+            panic!("host entropy unavailable: {err}");
+        }
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::CryptoRng for HostRng {}
+
+/// getrandom's custom backend hook; see the getrandom docs.
+#[cfg(feature = "getrandom")]
+#[no_mangle]
+unsafe extern "Rust" fn __getrandom_v03_custom(
+    dest: *mut u8,
+    len: usize,
+) -> std::result::Result<(), getrandom::Error> {
+    // The buffer may be uninitialized, so zero it before making a slice of it.
+    std::ptr::write_bytes(dest, 0, len);
+    let dest = std::slice::from_raw_parts_mut(dest, len);
+    // getrandom reserves codes from Error::CUSTOM_START for custom backends, so pass ours along
+    // as the offset from it.
+    fill(dest).map_err(|err| {
+        let code = err.code().map_or(0, |code| code.as_raw().unsigned_abs());
+        getrandom::Error::new_custom(u16::try_from(code).unwrap_or(u16::MAX))
+    })
+}
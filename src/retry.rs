@@ -0,0 +1,154 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+use crate::{invoke_extension, Result, SdkError};
+
+/// Describes how failed invocations should be retried: how many times, how long to wait between
+/// attempts, and which errors are worth retrying at all.
+///
+/// Backoff grows exponentially from `initial_backoff` by `multiplier` per attempt, is capped at
+/// `max_backoff`, and is then randomly shortened by up to `jitter` (a fraction between 0 and 1) so
+/// that many guests failing at once don't all retry in lockstep.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+    jitter: f64,
+    retry_if: fn(&SdkError) -> bool,
+    sleep: fn(Duration),
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2,
+            jitter: 0.5,
+            retry_if: SdkError::is_retryable,
+            sleep: crate::time::sleep,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The total number of attempts, including the first one. Values below 1 are treated as 1.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// The fraction (clamped to 0..=1) of each backoff that may be randomly subtracted from it.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Overrides which errors are considered transient. Defaults to `SdkError::is_retryable`.
+    pub fn retry_if(mut self, retry_if: fn(&SdkError) -> bool) -> Self {
+        self.retry_if = retry_if;
+        self
+    }
+
+    /// Sets the function used to wait between attempts. Defaults to `time::sleep`.
+    pub fn sleep_with(mut self, sleep: fn(Duration)) -> Self {
+        self.sleep = sleep;
+        self
+    }
+
+    /// Returns how long to wait after the given (zero-based) failed attempt, before jitter.
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1).saturating_pow(attempt);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Runs `f` until it succeeds, fails with an error that isn't retryable, runs out of attempts,
+    /// or the job is cancelled. The last error is returned if every attempt fails.
+    pub fn run<T>(&self, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt + 1 < self.max_attempts && (self.retry_if)(&err) => {
+                    // A cancelled job shouldn't keep retrying; report the failure it gave up on.
+                    if crate::job::is_cancelled() {
+                        return Err(err);
+                    }
+                    (self.sleep)(self.jittered(self.backoff_for(attempt)));
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn jittered(&self, backoff: Duration) -> Duration {
+        if self.jitter == 0.0 {
+            return backoff;
+        }
+        backoff.mul_f64(1.0 - self.jitter * next_random_fraction())
+    }
+}
+
+/// Invokes an extension, retrying transient failures according to `policy`.
+pub fn invoke_extension_with_retry(
+    extension_name: &str,
+    data: &[u8],
+    policy: &RetryPolicy,
+) -> Result<Vec<u8>> {
+    policy.run(|| invoke_extension(extension_name, data))
+}
+
+thread_local! {
+    /// Zero until the first use, when it's seeded from the host so that guests retrying the same
+    /// failure don't all pick the same delays.
+    static JITTER_STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns a pseudo-random number in 0..1. Jitter only needs to decorrelate retries, not be
+/// unpredictable, so a xorshift generator is plenty.
+fn next_random_fraction() -> f64 {
+    JITTER_STATE.with(|state| {
+        let mut x = state.get();
+        if x == 0 {
+            let mut seed = [0; 8];
+            x = match crate::rand::fill(&mut seed) {
+                Ok(()) => u64::from_le_bytes(seed),
+                Err(_) => 0,
+            };
+            // Xorshift gets stuck on zero.
+            if x == 0 {
+                x = 0x9e37_79b9_7f4a_7c15;
+            }
+        }
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
@@ -0,0 +1,66 @@
+//! Secrets such as API keys and passwords, fetched from the host's secret store. Unlike
+//! `crate::env`, values come back wrapped in a `Secret`, which zeroes its memory when dropped and
+//! never prints its contents.
+
+use std::fmt;
+
+use crate::{host, take_host_bytes, zero, ExtensionErrorCode, Result, SdkError};
+
+/// Fetches the named secret. Fails with `SdkError::NotFound` if there's no secret by that name.
+pub fn get(name: &str) -> Result<Secret> {
+    let out_ptr = unsafe { host::secret_get(name.as_ptr() as usize, name.len() as u32) };
+    if out_ptr < 0 {
+        return Err(ExtensionErrorCode::from(out_ptr as i32).into());
+    }
+    if out_ptr == 0 {
+        return Err(SdkError::NotFound(format!("secret {name}")));
+    }
+    Ok(Secret::new(take_host_bytes(out_ptr as usize)?.into_vec()))
+}
+
+/// The value of a secret. The bytes are zeroed when the `Secret` is dropped, and `Debug` only
+/// shows that there's a secret, not what it is. There's deliberately no `Clone` or `Display`; call
+/// `expose` where the value is needed and keep what's done with it short.
+pub struct Secret {
+    bytes: Vec<u8>,
+}
+
+impl Secret {
+    fn new(mut bytes: Vec<u8>) -> Self {
+        // Whatever is in the spare capacity may be left over from copying the value around.
+        let spare = bytes.spare_capacity_mut();
+        unsafe { zero(spare.as_mut_ptr().cast(), spare.len()) };
+        Self { bytes }
+    }
+
+    /// The secret's value.
+    pub fn expose(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The secret's value as UTF-8, or `None` if it isn't valid UTF-8.
+    pub fn expose_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.bytes).ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        // Safety: the whole allocation is ours, initialized or not.
+        unsafe { zero(self.bytes.as_mut_ptr(), self.bytes.capacity()) };
+    }
+}
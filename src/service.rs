@@ -0,0 +1,42 @@
+//! The calling convention shared by the clients for platform services (`kv` and friends): each
+//! call is a frame naming the operation, with a body laid out by the client module, and the
+//! response reports success or failure in its `tags::STATUS` field.
+
+use crate::frame::{send_frame, status, tags, Frame};
+use crate::{Result, SdkError};
+
+/// Invokes `operation` on the service behind `extension_name` and returns the response body,
+/// turning a failure status into the matching `SdkError`.
+pub(crate) fn call(extension_name: &str, operation: &str, body: Vec<u8>) -> Result<Vec<u8>> {
+    send(extension_name, request(operation, body))
+}
+
+/// Like `call`, for a request frame that needs header fields of its own.
+pub(crate) fn send(extension_name: &str, request: Frame) -> Result<Vec<u8>> {
+    response_body(send_frame(extension_name, request)?)
+}
+
+/// Builds the request frame for `operation`.
+pub(crate) fn request(operation: &str, body: Vec<u8>) -> Frame {
+    let mut frame = Frame::new(body);
+    frame.set_operation(operation);
+    frame
+}
+
+/// Returns the body of a service's response, or the error its status reports.
+pub(crate) fn response_body(response: Frame) -> Result<Vec<u8>> {
+    let status = match response.field(tags::STATUS) {
+        None => status::OK,
+        Some(value) => u32::from_le_bytes(value.try_into().map_err(|_| SdkError::InvalidPayload)?),
+    };
+    if status == status::OK {
+        return Ok(response.body);
+    }
+
+    let message = String::from_utf8_lossy(&response.body).into_owned();
+    Err(match status {
+        status::NOT_FOUND => SdkError::NotFound(message),
+        status::CONFLICT => SdkError::Conflict(message),
+        status => SdkError::Service { status, message },
+    })
+}
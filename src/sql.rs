@@ -0,0 +1,396 @@
+//! A client for the platform's database extension: parameterized queries in, rows of typed
+//! values out.
+//!
+//! ```ignore
+//! use serval::sql::{self, Value};
+//!
+//! let rows = sql::query(
+//!     "SELECT id, name FROM users WHERE team = ? AND active = ?",
+//!     &["infra".into(), true.into()],
+//! )?;
+//! for row in &rows {
+//!     let name = row.get_by_name("name").and_then(Value::as_str);
+//! }
+//! ```
+//!
+//! With the `json` feature, rows can also be deserialized straight into serde types, matching
+//! columns to fields by name; see `Row::deserialize`.
+//!
+//! Requests are frames sent to the `EXTENSION` extension, laid out like `kv`'s. Both operations
+//! take the database name, the SQL text, a u16 parameter count and that many values:
+//!
+//! | operation | response body |
+//! |---|---|
+//! | `query` | u16 column count, that many column names, u32 row count, then each row's values in column order |
+//! | `execute` | u64 number of rows affected |
+//!
+//! A value is a type byte (see `value_types`) followed by nothing for null, a u8 for booleans, an
+//! i64 for integers, the bits of an f64 for floats, or a length-prefixed string or byte string.
+
+use std::ops::Index;
+use std::sync::Arc;
+
+use crate::service;
+use crate::wire::{Reader, Writer};
+use crate::{Result, SdkError};
+
+/// The extension the platform's database is registered under.
+pub const EXTENSION: &str = "sql";
+
+/// The type bytes values are sent with.
+pub mod value_types {
+    pub const NULL: u8 = 0;
+    pub const BOOL: u8 = 1;
+    pub const INT: u8 = 2;
+    pub const FLOAT: u8 = 3;
+    pub const TEXT: u8 = 4;
+    pub const BYTES: u8 = 5;
+}
+
+/// A query parameter or a column's value in a row.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value as a float, converting integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            Value::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Value::Null => writer.write_u8(value_types::NULL),
+            Value::Bool(value) => {
+                writer.write_u8(value_types::BOOL);
+                writer.write_u8(*value as u8);
+            }
+            Value::Int(value) => {
+                writer.write_u8(value_types::INT);
+                writer.write_u64(*value as u64);
+            }
+            Value::Float(value) => {
+                writer.write_u8(value_types::FLOAT);
+                writer.write_u64(value.to_bits());
+            }
+            Value::Text(value) => {
+                writer.write_u8(value_types::TEXT);
+                writer.write_str(value);
+            }
+            Value::Bytes(value) => {
+                writer.write_u8(value_types::BYTES);
+                writer.write_prefixed(value);
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(match reader.read_u8()? {
+            value_types::NULL => Value::Null,
+            value_types::BOOL => Value::Bool(reader.read_u8()? != 0),
+            value_types::INT => Value::Int(reader.read_u64()? as i64),
+            value_types::FLOAT => Value::Float(f64::from_bits(reader.read_u64()?)),
+            value_types::TEXT => Value::Text(reader.read_str()?.to_string()),
+            value_types::BYTES => Value::Bytes(reader.read_prefixed()?.to_vec()),
+            _ => return Err(SdkError::InvalidPayload),
+        })
+    }
+
+    #[cfg(feature = "json")]
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(value) => (*value).into(),
+            Value::Int(value) => (*value).into(),
+            Value::Float(value) => (*value).into(),
+            Value::Text(value) => value.as_str().into(),
+            Value::Bytes(value) => value.as_slice().into(),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Bytes(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// A handle to a database. The free functions in this module use the default one.
+#[derive(Clone, Debug)]
+pub struct Database {
+    name: String,
+    extension: String,
+}
+
+impl Default for Database {
+    /// The job's default database, as configured by the platform.
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl Database {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            extension: EXTENSION.to_string(),
+        }
+    }
+
+    /// Talks to a database extension registered under a different name.
+    pub fn with_extension(mut self, extension_name: impl Into<String>) -> Self {
+        self.extension = extension_name.into();
+        self
+    }
+
+    /// Runs a query, binding `params` to its placeholders in order, and returns the rows it
+    /// produced. At most `u16::MAX` parameters can be bound; more fail with `SdkError::Encode`.
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Rows> {
+        let response = service::call(&self.extension, "query", self.body(sql, params)?)?;
+
+        let mut reader = Reader::new(&response);
+        let column_count = reader.read_u16()?;
+        let columns: Arc<[String]> = (0..column_count)
+            .map(|_| Ok(reader.read_str()?.to_string()))
+            .collect::<Result<_>>()?;
+        let row_count = reader.read_u32()?;
+        let mut rows = Vec::new();
+        for _ in 0..row_count {
+            let values = (0..column_count)
+                .map(|_| Value::read(&mut reader))
+                .collect::<Result<_>>()?;
+            rows.push(Row {
+                columns: columns.clone(),
+                values,
+            });
+        }
+        Ok(Rows { columns, rows })
+    }
+
+    /// Runs a statement that doesn't return rows, such as an `INSERT` or `UPDATE`, and returns the
+    /// number of rows it affected.
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<u64> {
+        let response = service::call(&self.extension, "execute", self.body(sql, params)?)?;
+        Reader::new(&response).read_u64()
+    }
+
+    /// Fails with `SdkError::Encode` if there are more parameters than the u16 count can hold.
+    fn body(&self, sql: &str, params: &[Value]) -> Result<Vec<u8>> {
+        let mut body = Writer::new();
+        body.write_str(&self.name);
+        body.write_str(sql);
+        body.write_u16_count(params.len(), "query parameters")?;
+        for param in params {
+            param.write(&mut body);
+        }
+        Ok(body.into_bytes())
+    }
+}
+
+/// Runs a query against the default database; see `Database::query`.
+pub fn query(sql: &str, params: &[Value]) -> Result<Rows> {
+    Database::default().query(sql, params)
+}
+
+/// See `Database::execute`.
+pub fn execute(sql: &str, params: &[Value]) -> Result<u64> {
+    Database::default().execute(sql, params)
+}
+
+/// The result of a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rows {
+    columns: Arc<[String]>,
+    rows: Vec<Row>,
+}
+
+impl Rows {
+    /// The names of the result's columns, in order.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Row> {
+        self.rows.iter()
+    }
+
+    /// Deserializes every row; see `Row::deserialize`.
+    #[cfg(feature = "json")]
+    pub fn deserialize<T: serde::de::DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.rows.iter().map(Row::deserialize).collect()
+    }
+}
+
+impl IntoIterator for Rows {
+    type Item = Row;
+    type IntoIter = std::vec::IntoIter<Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Rows {
+    type Item = &'a Row;
+    type IntoIter = std::slice::Iter<'a, Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.iter()
+    }
+}
+
+/// One row of a query's result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// The value of the column at `index`.
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)
+    }
+
+    /// The value of the first column named `name`.
+    pub fn get_by_name(&self, name: &str) -> Option<&Value> {
+        let index = self.columns.iter().position(|column| column == name)?;
+        self.values.get(index)
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Deserializes the row into a serde type, treating it as a map from column names to values.
+    /// Byte strings become sequences of numbers.
+    #[cfg(feature = "json")]
+    pub fn deserialize<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        let map = self
+            .columns
+            .iter()
+            .zip(&self.values)
+            .map(|(column, value)| (column.clone(), value.to_json()))
+            .collect();
+        serde_json::from_value(serde_json::Value::Object(map))
+            .map_err(|err| SdkError::Decode(crate::CodecError::new(err.to_string())))
+    }
+}
+
+impl Index<usize> for Row {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        &self.values[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Database, Value};
+    use crate::SdkError;
+
+    #[test]
+    fn bodies_with_more_parameters_than_the_count_holds_fail() {
+        let database = Database::default();
+        let mut params = vec![Value::Null; u16::MAX as usize];
+        assert!(database.body("INSERT", &params).is_ok());
+        params.push(Value::Null);
+        assert!(matches!(
+            database.body("INSERT", &params),
+            Err(SdkError::Encode(_))
+        ));
+    }
+}
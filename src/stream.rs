@@ -0,0 +1,350 @@
+//! Streaming requests to and responses from extensions.
+//!
+//! `invoke_streaming` starts a call whose response the host keeps on its side; the guest then
+//! pulls it over one chunk at a time, so only the chunk being processed has to fit in memory.
+//! `InvocationWriter` does the same for requests, pushing the payload to the host as it's written
+//! and only invoking the extension once it's finished.
+//!
+//! `invoke_chunked` combines the two to pass payloads larger than the guest's memory limit through
+//! it in fixed-size segments.
+//!
+//! Incoming data is flow controlled: the host may only deliver as many bytes as the guest has
+//! granted it credit for, and the SDK tops the credit back up to the stream's window size each time
+//! it asks for more. The window bounds how much the host buffers ahead and how large a single
+//! chunk written into our memory can be; tune it with `set_window_size`.
+//!
+//! Both directions check for cancellation as they go: once the job is cancelled, the next chunk
+//! read or write fails with `SdkError::Cancelled` and the host side is released.
+
+use std::io;
+
+use crate::{
+    check_ptr, check_status, get_bytes_from_host, host, job, read_response, ExtensionErrorCode,
+    InvocationError, Result, SdkError,
+};
+
+/// Invokes the named extension and returns its response as a stream of chunks instead of a single
+/// buffer.
+pub fn invoke_streaming(extension_name: &str, data: &[u8]) -> Result<ResponseStream> {
+    let id = unsafe {
+        host::invoke_streaming(
+            extension_name.as_ptr() as usize,
+            extension_name.len() as u32,
+            data.as_ptr() as usize,
+            data.len() as u32,
+        )
+    };
+
+    check_status(id, extension_name, data.len())?;
+    Ok(ResponseStream::new(id as u32, extension_name, data.len()))
+}
+
+/// The segment size `invoke_chunked` asks the host for: 1 MiB.
+pub const DEFAULT_SEGMENT_SIZE: u32 = 1 << 20;
+
+/// Invokes the named extension with a payload read from `input`, writing its response to
+/// `output`, without ever holding more than a segment of either in memory. This lets payloads far
+/// larger than the guest's memory limit pass through it. The segment size is negotiated with the
+/// host, starting from `DEFAULT_SEGMENT_SIZE`. Returns the number of response bytes written.
+pub fn invoke_chunked(
+    extension_name: &str,
+    mut input: impl io::Read,
+    mut output: impl io::Write,
+) -> Result<u64> {
+    let segment_size = negotiate_segment_size(DEFAULT_SEGMENT_SIZE)
+        .map_err(|err| InvocationError::new(extension_name, 0, 0, err))?;
+
+    let mut request = InvocationWriter::new(extension_name)?;
+    let mut segment = vec![0; segment_size as usize];
+    loop {
+        let len = match input.read(&mut segment) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(SdkError::from(err)),
+        };
+        io::Write::write_all(&mut request, &segment[..len]).map_err(SdkError::from)?;
+    }
+    drop(segment);
+
+    let mut response = request.finish_streaming()?;
+    response.set_window_size(segment_size);
+    let mut written = 0;
+    for chunk in &mut response {
+        let chunk = chunk?;
+        output.write_all(&chunk).map_err(SdkError::from)?;
+        written += chunk.len() as u64;
+    }
+    output.flush().map_err(SdkError::from)?;
+    Ok(written)
+}
+
+/// Asks the host to use segments of `preferred` bytes for chunked transfers, returning the size it
+/// settled on, which is never larger.
+pub(crate) fn negotiate_segment_size(preferred: u32) -> Result<u32> {
+    let size = unsafe { host::negotiate_segment_size(preferred) };
+    if size < 0 {
+        return Err(ExtensionErrorCode::from(size).into());
+    }
+    Ok((size as u32).clamp(1, preferred))
+}
+
+/// The window size streams and channels start out with: 1 MiB.
+pub const DEFAULT_WINDOW_SIZE: u32 = 1 << 20;
+
+/// Tracks how much credit the host has to send us data with.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FlowControl {
+    window: u32,
+    /// Credit granted to the host that it hasn't used up yet.
+    outstanding: u32,
+}
+
+impl FlowControl {
+    pub(crate) fn new(window: u32) -> Self {
+        Self {
+            window,
+            outstanding: 0,
+        }
+    }
+
+    /// Sets the window, clamped to at least 1 byte so the next read can't wait forever.
+    pub(crate) fn set_window(&mut self, window: u32) {
+        // Credit that's already been granted can't be taken back; a smaller window takes effect
+        // once the host has used it up.
+        self.window = window.max(1);
+    }
+
+    /// How much credit to grant to bring the host back up to a full window, if any.
+    pub(crate) fn top_up(&mut self) -> Option<u32> {
+        let credit = self.window.saturating_sub(self.outstanding);
+        self.outstanding += credit;
+        (credit > 0).then_some(credit)
+    }
+
+    /// Records that the host has used `len` bytes of credit.
+    pub(crate) fn received(&mut self, len: usize) {
+        let len = u32::try_from(len).unwrap_or(u32::MAX);
+        self.outstanding = self.outstanding.saturating_sub(len);
+    }
+}
+
+/// An extension's response, fetched from the host as it's consumed. It can be read chunk by chunk
+/// through `Iterator` or as a byte stream through `io::Read`; mixing the two is fine, any part of
+/// a chunk `read` hasn't consumed yet comes out of the iterator first. Dropping the stream
+/// before the end releases whatever the host still holds.
+#[derive(Debug)]
+pub struct ResponseStream {
+    id: u32,
+    extension: String,
+    payload_len: usize,
+    finished: bool,
+    /// The chunk `read` is working through, and how much of it has been handed out.
+    buffered: Vec<u8>,
+    position: usize,
+    flow: FlowControl,
+}
+
+impl ResponseStream {
+    fn new(id: u32, extension_name: &str, payload_len: usize) -> Self {
+        Self {
+            id,
+            extension: extension_name.to_string(),
+            payload_len,
+            finished: false,
+            buffered: Vec::new(),
+            position: 0,
+            flow: FlowControl::new(DEFAULT_WINDOW_SIZE),
+        }
+    }
+
+    /// The extension the response is coming from.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Sets how many bytes the host may deliver ahead of what's been asked for, which also caps
+    /// the size of a chunk. Defaults to `DEFAULT_WINDOW_SIZE`. 0 is taken as 1, since the host
+    /// couldn't send anything at all otherwise.
+    pub fn set_window_size(&mut self, bytes: u32) {
+        self.flow.set_window(bytes);
+    }
+
+    /// Reads the rest of the response into a single buffer.
+    pub fn collect_bytes(mut self) -> Result<Vec<u8>> {
+        let mut bytes = self.buffered.split_off(self.position);
+        for chunk in &mut self {
+            bytes.extend_from_slice(&chunk?);
+        }
+        Ok(bytes)
+    }
+
+    fn next_chunk(&mut self) -> Option<Result<Vec<u8>>> {
+        if self.finished {
+            return None;
+        }
+        if let Err(err) = job::check_cancelled() {
+            self.finished = true;
+            unsafe { host::stream_close(self.id) };
+            return Some(Err(err));
+        }
+
+        let (extension, payload_len) = (&self.extension, self.payload_len);
+        if let Some(credit) = self.flow.top_up() {
+            let status = unsafe { host::stream_grant(self.id, credit) };
+            if let Err(err) = check_status(status, extension, payload_len) {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        }
+
+        let out_ptr = unsafe { host::stream_next(self.id) };
+        if out_ptr == 0 {
+            self.finished = true;
+            return None;
+        }
+        let chunk = check_ptr(out_ptr, &self.extension, self.payload_len)
+            .and_then(get_bytes_from_host)
+            .inspect(|chunk| self.flow.received(chunk.len()))
+            .map_err(|err| {
+                InvocationError::new(&self.extension, self.payload_len, out_ptr as i32, err).into()
+            });
+        // The host gives up on the stream once it has reported an error.
+        self.finished = chunk.is_err();
+        Some(chunk)
+    }
+}
+
+impl Iterator for ResponseStream {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position < self.buffered.len() {
+            let rest = self.buffered.split_off(self.position);
+            self.buffered.clear();
+            self.position = 0;
+            return Some(Ok(rest));
+        }
+        self.next_chunk()
+    }
+}
+
+impl io::Read for ResponseStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffered.len() {
+            match self.next_chunk() {
+                Some(chunk) => {
+                    self.buffered = chunk.map_err(io::Error::other)?;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.buffered.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffered[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        if !self.finished {
+            unsafe { host::stream_close(self.id) };
+        }
+    }
+}
+
+/// A request whose payload is sent to the host as it's written. The extension is invoked when
+/// `finish` is called; dropping the writer first abandons the request. Each `write` is a host
+/// call, so wrap it in an `io::BufWriter` when writing many small pieces.
+#[derive(Debug)]
+pub struct InvocationWriter {
+    id: u32,
+    extension: String,
+    written: usize,
+    finished: bool,
+}
+
+impl InvocationWriter {
+    /// Opens a request to the named extension.
+    pub fn new(extension_name: &str) -> Result<Self> {
+        let id = unsafe {
+            host::open_request(
+                extension_name.as_ptr() as usize,
+                extension_name.len() as u32,
+            )
+        };
+
+        check_status(id, extension_name, 0)?;
+        Ok(Self {
+            id: id as u32,
+            extension: extension_name.to_string(),
+            written: 0,
+            finished: false,
+        })
+    }
+
+    /// The extension the request is for.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Invokes the extension with everything written so far and returns its response.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        self.finished = true;
+        let out_ptr = unsafe { host::request_finish(self.id) };
+        read_response(out_ptr, &self.extension, self.written)
+    }
+
+    /// Like `finish`, but returns the response as a stream instead of a single buffer.
+    pub fn finish_streaming(mut self) -> Result<ResponseStream> {
+        self.finished = true;
+        let id = unsafe { host::request_finish_streaming(self.id) };
+        check_status(id, &self.extension, self.written)?;
+        Ok(ResponseStream::new(
+            id as u32,
+            &self.extension,
+            self.written,
+        ))
+    }
+}
+
+impl io::Write for InvocationWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        job::check_cancelled().map_err(io::Error::other)?;
+        let status =
+            unsafe { host::request_write(self.id, buf.as_ptr() as usize, buf.len() as u32) };
+        check_status(status, &self.extension, self.written).map_err(io::Error::other)?;
+        self.written += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for InvocationWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            unsafe { host::request_abort(self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlowControl;
+
+    #[test]
+    fn a_zero_window_still_grants_credit() {
+        let mut flow = FlowControl::new(16);
+        flow.set_window(0);
+        assert_eq!(flow.top_up(), Some(1));
+        assert_eq!(flow.top_up(), None);
+        flow.received(1);
+        assert_eq!(flow.top_up(), Some(1));
+    }
+}
@@ -0,0 +1,123 @@
+//! Clocks provided by the host. `std::time::SystemTime::now` panics on wasm32-unknown-unknown, so
+//! use these instead.
+//!
+//! `now` reads the wall clock, for timestamps. `Instant` reads a separate monotonic counter, for
+//! measuring durations without clock adjustments getting in the way.
+//!
+//! `sleep` and `yield_now` hand control back to the host, which has better things to do with a
+//! waiting guest than let it spin.
+
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::host;
+
+/// Returns the current wall-clock time as the time elapsed since the UNIX epoch, with nanosecond
+/// precision. The host's clock can be adjusted, so consecutive readings may go backwards.
+pub fn now() -> Duration {
+    Duration::from_nanos(unsafe { host::wall_clock_now() })
+}
+
+/// Returns the current wall-clock time as a `SystemTime`, for APIs that expect one.
+pub fn system_time() -> SystemTime {
+    UNIX_EPOCH + now()
+}
+
+/// Suspends the guest for at least `duration`, letting the host schedule other work in the
+/// meantime. Durations longer than `u64::MAX` nanoseconds are clamped.
+pub fn sleep(duration: Duration) {
+    let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    unsafe { host::sleep(nanos) };
+}
+
+/// Tells the host the guest has nothing to do right now but would like to keep going, so it can
+/// run other work first. Use this in polling loops in place of spinning.
+pub fn yield_now() {
+    unsafe { host::yield_now() };
+}
+
+/// A reading of the host's monotonic clock, like `std::time::Instant`. Readings never go
+/// backwards, but they're only meaningful relative to each other within the same job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    nanos: u64,
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Self {
+            nanos: unsafe { host::monotonic_now() },
+        }
+    }
+
+    /// The time elapsed since this instant was taken.
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+
+    /// The time elapsed from `earlier` to `self`, or zero if `earlier` is later.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// The time elapsed from `earlier` to `self`, or `None` if `earlier` is later.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.nanos
+            .checked_sub(earlier.nanos)
+            .map(Duration::from_nanos)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        Some(Self {
+            nanos: self.nanos.checked_add(nanos)?,
+        })
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        Some(Self {
+            nanos: self.nanos.checked_sub(nanos)?,
+        })
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    /// Panics on overflow, like `std::time::Instant`.
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    /// Panics on overflow, like `std::time::Instant`.
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
@@ -0,0 +1,62 @@
+//! The trace ID that ties a request together as it hops from the guest to an extension and on to
+//! the jobs that extension starts.
+//!
+//! Every framed invocation carries the current trace ID in its `tags::TRACE_ID` header field, so
+//! extensions and the platform services can log it and hand it on. It starts out as the one the
+//! host assigned the job, if it did; `with_trace_id` replaces it for the duration of a closure,
+//! for a guest that serves several requests and wants each one traced on its own.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use crate::{get_bytes_from_host, host};
+
+thread_local! {
+    /// The IDs of the `with_trace_id` calls running on this thread, innermost last.
+    static SCOPES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the trace ID framed invocations are currently tagged with: the innermost
+/// `with_trace_id` one, or else the job's. `None` if the host didn't assign the job one and no
+/// `with_trace_id` call is running.
+pub fn current_trace_id() -> Option<String> {
+    if let Some(id) = SCOPES.with(|scopes| scopes.borrow().last().cloned()) {
+        return Some(id);
+    }
+    job_trace_id()
+}
+
+/// Runs `f` with `id` as the current trace ID, restoring the previous one when it returns (or
+/// unwinds).
+pub fn with_trace_id<T>(id: &str, f: impl FnOnce() -> T) -> T {
+    struct Scope;
+    impl Drop for Scope {
+        fn drop(&mut self) {
+            SCOPES.with(|scopes| scopes.borrow_mut().pop());
+        }
+    }
+
+    SCOPES.with(|scopes| scopes.borrow_mut().push(id.to_string()));
+    let _scope = Scope;
+    f()
+}
+
+/// The trace ID the host assigned the job. It doesn't change during a run, so it's only fetched
+/// once.
+fn job_trace_id() -> Option<String> {
+    static JOB_TRACE_ID: OnceLock<Option<String>> = OnceLock::new();
+    if cfg!(feature = "mock-host") {
+        // The mock host's can change from one test to the next.
+        return fetch_job_trace_id();
+    }
+    JOB_TRACE_ID.get_or_init(fetch_job_trace_id).clone()
+}
+
+fn fetch_job_trace_id() -> Option<String> {
+    let out_ptr = unsafe { host::job_trace_id() };
+    if out_ptr <= 0 {
+        return None;
+    }
+    let id = get_bytes_from_host(out_ptr as usize).ok()?;
+    String::from_utf8(id).ok()
+}
@@ -0,0 +1,146 @@
+//! A `tracing-subscriber` layer that forwards spans and events to the host, so the node can stitch
+//! the guest's work into its own traces.
+//!
+//! ```ignore
+//! serval::tracing::init();
+//! let _span = tracing::info_span!("resize", width = 640).entered();
+//! tracing::info!(bytes = input.len(), "decoded image");
+//! ```
+//!
+//! Each record is handed to the host's `trace_record` import as its own buffer, starting with a
+//! kind byte:
+//!
+//! - `NEW_SPAN`: u64 span id, u64 parent id, then the metadata and fields.
+//! - `RECORD`: u64 span id, then fields recorded on the span after it was created.
+//! - `EVENT`: u64 parent id, then the metadata and fields.
+//! - `CLOSE_SPAN`: u64 span id.
+//!
+//! IDs are little-endian and a parent id of 0 means the span or event is a root. Metadata is a
+//! level byte (a `log::Level`) followed by the target and name as length-prefixed strings. Fields
+//! are a u16 count followed by that many pairs of length-prefixed strings, the field name and its
+//! value formatted with `Debug` (or as is, for string fields).
+
+use std::fmt;
+
+use ::tracing::field::{Field, Visit};
+use ::tracing::span::{self, Attributes};
+use ::tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+
+use crate::host;
+use crate::log::Level;
+use crate::wire::Writer;
+
+/// The kind bytes records start with.
+pub mod kinds {
+    pub const NEW_SPAN: u8 = 0;
+    pub const RECORD: u8 = 1;
+    pub const EVENT: u8 = 2;
+    pub const CLOSE_SPAN: u8 = 3;
+}
+
+/// Installs a registry with a `ServalLayer` as the global default subscriber. Returns an error if
+/// one has already been set.
+pub fn init() -> Result<(), ::tracing::subscriber::SetGlobalDefaultError> {
+    ::tracing::subscriber::set_global_default(tracing_subscriber::registry().with(ServalLayer))
+}
+
+/// Forwards span lifecycles and events to the host; see the module docs for the format.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ServalLayer;
+
+impl<S: Subscriber> Layer<S> for ServalLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.clone()),
+            None if attrs.is_contextual() => ctx.current_span().id().cloned(),
+            None => None,
+        };
+
+        let mut writer = Writer::new();
+        writer.write_u8(kinds::NEW_SPAN);
+        writer.write_u64(id.into_u64());
+        writer.write_u64(parent.map_or(0, |parent| parent.into_u64()));
+        write_metadata(&mut writer, attrs.metadata());
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        fields.write(&mut writer);
+        send(writer);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if fields.0.is_empty() {
+            return;
+        }
+
+        let mut writer = Writer::new();
+        writer.write_u8(kinds::RECORD);
+        writer.write_u64(id.into_u64());
+        fields.write(&mut writer);
+        send(writer);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let parent = match event.parent() {
+            Some(parent) => Some(parent.clone()),
+            None if event.is_contextual() => ctx.current_span().id().cloned(),
+            None => None,
+        };
+
+        let mut writer = Writer::new();
+        writer.write_u8(kinds::EVENT);
+        writer.write_u64(parent.map_or(0, |parent| parent.into_u64()));
+        write_metadata(&mut writer, event.metadata());
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        fields.write(&mut writer);
+        send(writer);
+    }
+
+    fn on_close(&self, id: span::Id, _ctx: Context<'_, S>) {
+        let mut writer = Writer::new();
+        writer.write_u8(kinds::CLOSE_SPAN);
+        writer.write_u64(id.into_u64());
+        send(writer);
+    }
+}
+
+fn write_metadata(writer: &mut Writer, metadata: &Metadata<'_>) {
+    writer.write_u8(Level::from(*metadata.level()) as u8);
+    writer.write_str(metadata.target());
+    writer.write_str(metadata.name());
+}
+
+fn send(writer: Writer) {
+    let bytes = writer.into_bytes();
+    // Like logging, tracing must never fail the guest; a record the host can't take is dropped.
+    let _ = unsafe { host::trace_record(bytes.as_ptr() as usize, bytes.len() as u32) };
+}
+
+/// Collects a span's or event's fields as name/value strings.
+#[derive(Default)]
+struct Fields(Vec<(&'static str, String)>);
+
+impl Fields {
+    fn write(&self, writer: &mut Writer) {
+        let count = u16::try_from(self.0.len()).unwrap_or(u16::MAX);
+        writer.write_u16(count);
+        for (name, value) in self.0.iter().take(count as usize) {
+            writer.write_str(name);
+            writer.write_str(value);
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push((field.name(), format!("{value:?}")));
+    }
+}
@@ -0,0 +1,13 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{json, Result};
+
+/// Invokes an extension with a serializable request and deserializes its response. Both
+/// directions are encoded as JSON.
+pub fn invoke_extension_typed<Req: Serialize, Resp: DeserializeOwned>(
+    extension_name: &str,
+    request: &Req,
+) -> Result<Resp> {
+    json::invoke_json(extension_name, request)
+}
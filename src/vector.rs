@@ -0,0 +1,359 @@
+//! A client for the platform's vector store: upserting embeddings and running nearest-neighbour
+//! queries over them.
+//!
+//! ```ignore
+//! use serval::vector::{Collection, Filter, Query, Record};
+//!
+//! let docs = Collection::new("docs");
+//! docs.upsert(&[Record::new("readme", embedding).metadata("lang", "en")])?;
+//! let matches = docs.query(&Query::new(question, 5).filter(Filter::eq("lang", "en")))?;
+//! ```
+//!
+//! Requests are frames sent to the `EXTENSION` extension, laid out like `kv`'s. Vectors are packed
+//! as a u32 dimension followed by that many little-endian f32s, rather than spelled out as text,
+//! and metadata is a u16 count followed by that many length-prefixed key and value pairs:
+//!
+//! | operation | request body | response body |
+//! |---|---|---|
+//! | `upsert` | collection, u32 count, that many (id, vector, metadata) records | empty |
+//! | `query` | collection, u32 k, vector, u8 include vectors, u8 has filter, filter | u32 count, that many matches |
+//! | `delete` | collection, u32 count, that many ids | u64 number deleted |
+//!
+//! A match is its id, its f32 score, its metadata, a u8 saying whether a vector follows and, if
+//! so, the vector. A filter is an operator byte (see `filter_ops`) followed by a key and value for
+//! `EQ` and `NOT_EQ`, a key, u16 count and that many values for `IN`, or a u16 count and that many
+//! filters for `AND` and `OR`.
+
+use std::collections::BTreeMap;
+
+use crate::service;
+use crate::wire::{Reader, Writer};
+use crate::Result;
+
+/// The extension the platform's vector store is registered under.
+pub const EXTENSION: &str = "vector";
+
+/// The operator bytes filters are sent with.
+pub mod filter_ops {
+    pub const EQ: u8 = 0;
+    pub const NOT_EQ: u8 = 1;
+    pub const IN: u8 = 2;
+    pub const AND: u8 = 3;
+    pub const OR: u8 = 4;
+}
+
+/// A vector to store, along with the id it's stored under and metadata to filter queries on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Record {
+    pub fn new(id: impl Into<String>, vector: Vec<f32>) -> Self {
+        Self {
+            id: id.into(),
+            vector,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A condition on records' metadata that a query's matches have to satisfy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Eq(String, String),
+    NotEq(String, String),
+    /// The key's value is one of the given values, of which there can be at most `u16::MAX`.
+    In(String, Vec<String>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+impl Filter {
+    pub fn eq(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Filter::Eq(key.into(), value.into())
+    }
+
+    pub fn not_eq(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Filter::NotEq(key.into(), value.into())
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+
+    /// Fails with `SdkError::Encode` if an `In`, `And` or `Or` has more entries than the u16 count
+    /// can hold.
+    fn write(&self, writer: &mut Writer) -> Result<()> {
+        match self {
+            Filter::Eq(key, value) | Filter::NotEq(key, value) => {
+                let op = match self {
+                    Filter::Eq(..) => filter_ops::EQ,
+                    _ => filter_ops::NOT_EQ,
+                };
+                writer.write_u8(op);
+                writer.write_str(key);
+                writer.write_str(value);
+            }
+            Filter::In(key, values) => {
+                writer.write_u8(filter_ops::IN);
+                writer.write_str(key);
+                writer.write_u16_count(values.len(), "filter values")?;
+                for value in values {
+                    writer.write_str(value);
+                }
+            }
+            Filter::And(filters) | Filter::Or(filters) => {
+                let op = match self {
+                    Filter::And(_) => filter_ops::AND,
+                    _ => filter_ops::OR,
+                };
+                writer.write_u8(op);
+                writer.write_u16_count(filters.len(), "filters")?;
+                for filter in filters {
+                    filter.write(writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A nearest-neighbour query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    vector: Vec<f32>,
+    k: u32,
+    filter: Option<Filter>,
+    include_vectors: bool,
+}
+
+impl Query {
+    /// Asks for the `k` stored vectors nearest to `vector`.
+    pub fn new(vector: Vec<f32>, k: u32) -> Self {
+        Self {
+            vector,
+            k,
+            filter: None,
+            include_vectors: false,
+        }
+    }
+
+    /// Only considers records whose metadata satisfies `filter`.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Has the matches' vectors returned along with their ids and metadata.
+    pub fn include_vectors(mut self, include_vectors: bool) -> Self {
+        self.include_vectors = include_vectors;
+        self
+    }
+}
+
+/// One of a query's results.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub id: String,
+    /// How close the match is to the query; what the number means depends on the collection's
+    /// distance metric, but higher is always closer.
+    pub score: f32,
+    pub metadata: BTreeMap<String, String>,
+    /// The stored vector, if the query asked for it.
+    pub vector: Option<Vec<f32>>,
+}
+
+/// A handle to a named collection of vectors.
+#[derive(Clone, Debug)]
+pub struct Collection {
+    name: String,
+    extension: String,
+}
+
+impl Collection {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            extension: EXTENSION.to_string(),
+        }
+    }
+
+    /// Talks to a vector store registered under a different extension name.
+    pub fn with_extension(mut self, extension_name: impl Into<String>) -> Self {
+        self.extension = extension_name.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stores `records`, replacing any already stored under the same ids. Fails with
+    /// `SdkError::Encode` if a record has more than `u16::MAX` metadata entries.
+    pub fn upsert(&self, records: &[Record]) -> Result<()> {
+        let mut body = self.body();
+        body.write_u32(records.len() as u32);
+        for record in records {
+            body.write_str(&record.id);
+            write_vector(&mut body, &record.vector);
+            write_metadata(&mut body, &record.metadata)?;
+        }
+        service::call(&self.extension, "upsert", body.into_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the records nearest to the query's vector, closest first.
+    pub fn query(&self, query: &Query) -> Result<Vec<Match>> {
+        let mut body = self.body();
+        body.write_u32(query.k);
+        write_vector(&mut body, &query.vector);
+        body.write_u8(query.include_vectors as u8);
+        body.write_u8(query.filter.is_some() as u8);
+        if let Some(filter) = &query.filter {
+            filter.write(&mut body)?;
+        }
+        let response = service::call(&self.extension, "query", body.into_bytes())?;
+
+        let mut reader = Reader::new(&response);
+        let count = reader.read_u32()?;
+        (0..count)
+            .map(|_| {
+                let id = reader.read_str()?.to_string();
+                let score = f32::from_bits(reader.read_u32()?);
+                let metadata = read_metadata(&mut reader)?;
+                let vector = match reader.read_u8()? {
+                    0 => None,
+                    _ => Some(read_vector(&mut reader)?),
+                };
+                Ok(Match {
+                    id,
+                    score,
+                    metadata,
+                    vector,
+                })
+            })
+            .collect()
+    }
+
+    /// Removes the records stored under `ids` and returns how many there were.
+    pub fn delete(&self, ids: &[&str]) -> Result<u64> {
+        let mut body = self.body();
+        body.write_u32(ids.len() as u32);
+        for id in ids {
+            body.write_str(id);
+        }
+        let response = service::call(&self.extension, "delete", body.into_bytes())?;
+        Reader::new(&response).read_u64()
+    }
+
+    /// Starts a request body with the collection's name.
+    fn body(&self) -> Writer {
+        let mut body = Writer::new();
+        body.write_str(&self.name);
+        body
+    }
+}
+
+fn write_vector(writer: &mut Writer, vector: &[f32]) {
+    writer.write_u32(vector.len() as u32);
+    for value in vector {
+        writer.write_bytes(&value.to_le_bytes());
+    }
+}
+
+fn read_vector(reader: &mut Reader<'_>) -> Result<Vec<f32>> {
+    let len = reader.read_u32()? as usize;
+    let bytes = reader.read_bytes(len.saturating_mul(4))?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+fn write_metadata(writer: &mut Writer, metadata: &BTreeMap<String, String>) -> Result<()> {
+    writer.write_u16_count(metadata.len(), "metadata entries")?;
+    for (key, value) in metadata {
+        writer.write_str(key);
+        writer.write_str(value);
+    }
+    Ok(())
+}
+
+fn read_metadata(reader: &mut Reader<'_>) -> Result<BTreeMap<String, String>> {
+    let count = reader.read_u16()?;
+    (0..count)
+        .map(|_| {
+            Ok((
+                reader.read_str()?.to_string(),
+                reader.read_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{write_metadata, Filter};
+    use crate::wire::Writer;
+    use crate::SdkError;
+
+    fn ids(count: usize) -> Vec<String> {
+        (0..count).map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn filters_with_more_entries_than_the_count_holds_fail() {
+        let max = u16::MAX as usize;
+        assert!(Filter::In("id".into(), ids(max))
+            .write(&mut Writer::new())
+            .is_ok());
+        let too_many = Filter::In("id".into(), ids(max + 1));
+        assert!(matches!(
+            too_many.write(&mut Writer::new()),
+            Err(SdkError::Encode(_))
+        ));
+        // The count is checked at every level of nesting.
+        let nested = Filter::And(vec![Filter::Eq("a".into(), "b".into()), too_many]);
+        assert!(nested.write(&mut Writer::new()).is_err());
+        let wide = Filter::Or(vec![Filter::Eq("a".into(), "b".into()); max + 1]);
+        assert!(wide.write(&mut Writer::new()).is_err());
+    }
+
+    #[test]
+    fn metadata_with_more_entries_than_the_count_holds_fails() {
+        let metadata: BTreeMap<_, _> = ids(u16::MAX as usize + 1)
+            .into_iter()
+            .map(|id| (id, String::new()))
+            .collect();
+        assert!(matches!(
+            write_metadata(&mut Writer::new(), &metadata),
+            Err(SdkError::Encode(_))
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+//! The parts of WASI preview 1 that common crates lean on, implemented with serval imports, for
+//! guests built for wasm32-wasip1. Serval hosts don't provide `wasi_snapshot_preview1`, so without
+//! this everything in std that goes through wasi-libc (clocks, stdio, environment variables,
+//! sleeping) would leave an import the host can't satisfy.
+//!
+//! wasi-libc calls WASI through symbols named `__imported_wasi_snapshot_preview1_<function>`,
+//! which only become imports if nothing else defines them. This module defines them, so the
+//! linker resolves them here instead:
+//!
+//! - `clock_time_get` and `clock_res_get` read the host's wall and monotonic clocks. The CPU-time
+//!   clocks read the monotonic one.
+//! - `random_get` draws from the host's entropy.
+//! - Each line written to stdout is logged at `Info`, and each line written to stderr at `Error`.
+//!   Stdin is always at end of file.
+//! - `environ_get` and `environ_sizes_get` list the job's configuration variables from `env`.
+//!   There are no command-line arguments and no preopened directories.
+//! - `poll_oneoff` sleeps on the host for clock subscriptions.
+//! - `sched_yield` yields to the host.
+//! - `proc_exit` logs a nonzero status and traps, since a guest can't end its job any other way.
+//!
+//! Anything else is left as an import. Code that declares WASI imports itself rather than going
+//! through libc, such as std's `HashMap` seeding and getrandom, is linked to the real imports
+//! too; use the `getrandom` feature for the latter.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::log::{self, Level};
+use crate::{env, rand, time};
+
+type Errno = i32;
+
+const SUCCESS: Errno = 0;
+const BADF: Errno = 8;
+const INVAL: Errno = 28;
+const IO: Errno = 29;
+const SPIPE: Errno = 70;
+
+const STDIN: i32 = 0;
+const STDOUT: i32 = 1;
+const STDERR: i32 = 2;
+
+const CLOCK_REALTIME: u32 = 0;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const RIGHT_FD_READ: u64 = 1 << 1;
+const RIGHT_FD_WRITE: u64 = 1 << 6;
+
+/// Output written to stdout and stderr since the last newline.
+static PENDING_STDOUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+static PENDING_STDERR: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+const EVENTTYPE_CLOCK: u8 = 0;
+const SUBCLOCKFLAGS_ABSTIME: u16 = 1;
+
+/// A `ciovec`: a buffer being written.
+#[repr(C)]
+struct Ciovec {
+    buf: *const u8,
+    len: usize,
+}
+
+/// An `iovec`: a buffer to read into.
+#[repr(C)]
+struct Iovec {
+    buf: *mut u8,
+    len: usize,
+}
+
+/// A `fdstat`.
+#[repr(C)]
+struct Fdstat {
+    filetype: u8,
+    flags: u16,
+    rights_base: u64,
+    rights_inheriting: u64,
+}
+
+/// A `subscription`, with its union read as the clock variant; the others are only told apart by
+/// `tag`.
+#[repr(C)]
+struct Subscription {
+    userdata: u64,
+    tag: u8,
+    clock: SubscriptionClock,
+}
+
+#[repr(C)]
+struct SubscriptionClock {
+    id: u32,
+    timeout: u64,
+    precision: u64,
+    flags: u16,
+}
+
+/// An `event`.
+#[repr(C)]
+struct Event {
+    userdata: u64,
+    error: u16,
+    kind: u8,
+    nbytes: u64,
+    flags: u16,
+}
+
+fn clock_now(id: u32) -> u64 {
+    match id {
+        CLOCK_REALTIME => u64::try_from(time::now().as_nanos()).unwrap_or(u64::MAX),
+        _ => unsafe { crate::host::monotonic_now() },
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_clock_time_get(
+    id: u32,
+    _precision: u64,
+    time: *mut u64,
+) -> Errno {
+    if id > 3 {
+        return INVAL;
+    }
+    *time = clock_now(id);
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_clock_res_get(
+    id: u32,
+    resolution: *mut u64,
+) -> Errno {
+    if id > 3 {
+        return INVAL;
+    }
+    *resolution = 1;
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_random_get(
+    buf: *mut u8,
+    len: usize,
+) -> Errno {
+    let dest = std::slice::from_raw_parts_mut(buf, len);
+    match rand::fill(dest) {
+        Ok(()) => SUCCESS,
+        Err(_) => IO,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_write(
+    fd: i32,
+    iovs: *const Ciovec,
+    iovs_len: usize,
+    written: *mut usize,
+) -> Errno {
+    let pending = match fd {
+        STDOUT => &PENDING_STDOUT,
+        STDERR => &PENDING_STDERR,
+        _ => return BADF,
+    };
+    let mut pending = pending.lock().unwrap_or_else(|err| err.into_inner());
+    let mut len = 0;
+    for iov in std::slice::from_raw_parts(iovs, iovs_len) {
+        pending.extend_from_slice(std::slice::from_raw_parts(iov.buf, iov.len));
+        len += iov.len;
+    }
+    *written = len;
+    // Each line becomes its own log message. stderr isn't buffered, so a single eprintln! can
+    // arrive over several writes.
+    while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+        let line: Vec<u8> = pending.drain(..=end).collect();
+        log::log(level(fd), &String::from_utf8_lossy(&line[..end]));
+    }
+    SUCCESS
+}
+
+fn level(fd: i32) -> Level {
+    if fd == STDERR {
+        Level::Error
+    } else {
+        Level::Info
+    }
+}
+
+/// Logs what's left of an unfinished line on stdout and stderr.
+fn flush_pending() {
+    for (fd, pending) in [(STDOUT, &PENDING_STDOUT), (STDERR, &PENDING_STDERR)] {
+        let mut pending = pending.lock().unwrap_or_else(|err| err.into_inner());
+        if !pending.is_empty() {
+            log::log(level(fd), &String::from_utf8_lossy(&pending));
+            pending.clear();
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_read(
+    fd: i32,
+    _iovs: *const Iovec,
+    _iovs_len: usize,
+    read: *mut usize,
+) -> Errno {
+    if fd != STDIN {
+        return BADF;
+    }
+    *read = 0;
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_fdstat_get(
+    fd: i32,
+    stat: *mut Fdstat,
+) -> Errno {
+    let rights = match fd {
+        STDIN => RIGHT_FD_READ,
+        STDOUT | STDERR => RIGHT_FD_WRITE,
+        _ => return BADF,
+    };
+    stat.write(Fdstat {
+        filetype: FILETYPE_CHARACTER_DEVICE,
+        flags: 0,
+        rights_base: rights,
+        rights_inheriting: 0,
+    });
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_seek(
+    fd: i32,
+    _offset: i64,
+    _whence: u8,
+    _position: *mut u64,
+) -> Errno {
+    match fd {
+        STDIN | STDOUT | STDERR => SPIPE,
+        _ => BADF,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_close(fd: i32) -> Errno {
+    match fd {
+        STDIN | STDOUT | STDERR => SUCCESS,
+        _ => BADF,
+    }
+}
+
+/// Reports that there are no preopened directories, which ends libc's scan for them.
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_prestat_get(
+    _fd: i32,
+    _prestat: *mut u8,
+) -> Errno {
+    BADF
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_prestat_dir_name(
+    _fd: i32,
+    _path: *mut u8,
+    _len: usize,
+) -> Errno {
+    BADF
+}
+
+/// Each variable as `KEY=VALUE` followed by a nul, the layout `environ_get` hands out.
+fn environ() -> Vec<Vec<u8>> {
+    env::vars()
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}\0").into_bytes())
+        .collect()
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_environ_sizes_get(
+    count: *mut usize,
+    buf_len: *mut usize,
+) -> Errno {
+    let environ = environ();
+    *count = environ.len();
+    *buf_len = environ.iter().map(Vec::len).sum();
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_environ_get(
+    environ_ptrs: *mut *mut u8,
+    buf: *mut u8,
+) -> Errno {
+    // libc sized the buffers from `environ_sizes_get`, and the variables don't change during a job.
+    let mut next = buf;
+    for (i, var) in environ().iter().enumerate() {
+        std::ptr::copy_nonoverlapping(var.as_ptr(), next, var.len());
+        *environ_ptrs.add(i) = next;
+        next = next.add(var.len());
+    }
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_args_sizes_get(
+    count: *mut usize,
+    buf_len: *mut usize,
+) -> Errno {
+    *count = 0;
+    *buf_len = 0;
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_args_get(
+    _args: *mut *mut u8,
+    _buf: *mut u8,
+) -> Errno {
+    SUCCESS
+}
+
+/// Waits out the earliest clock subscription. Subscriptions to file descriptors complete straight
+/// away with `BADF`, as none of ours can be polled.
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_poll_oneoff(
+    subscriptions: *const Subscription,
+    events: *mut Event,
+    count: usize,
+    event_count: *mut usize,
+) -> Errno {
+    if count == 0 {
+        return INVAL;
+    }
+    let subscriptions = std::slice::from_raw_parts(subscriptions, count);
+    let waits: Vec<Option<u64>> = subscriptions
+        .iter()
+        .map(|subscription| {
+            let clock = &subscription.clock;
+            (subscription.tag == EVENTTYPE_CLOCK).then(|| {
+                if clock.flags & SUBCLOCKFLAGS_ABSTIME != 0 {
+                    clock.timeout.saturating_sub(clock_now(clock.id))
+                } else {
+                    clock.timeout
+                }
+            })
+        })
+        .collect();
+    let polls_fds = waits.iter().any(Option::is_none);
+    let wait = waits.iter().flatten().min().copied();
+    if let (Some(wait), false) = (wait, polls_fds) {
+        time::sleep(Duration::from_nanos(wait));
+    }
+
+    let mut written = 0;
+    for (subscription, subscription_wait) in subscriptions.iter().zip(&waits) {
+        let error = match subscription_wait {
+            None => BADF,
+            Some(_) if polls_fds => continue,
+            Some(subscription_wait) if Some(*subscription_wait) == wait => SUCCESS,
+            Some(_) => continue,
+        };
+        events.add(written).write(Event {
+            userdata: subscription.userdata,
+            error: error as u16,
+            kind: subscription.tag,
+            nbytes: 0,
+            flags: 0,
+        });
+        written += 1;
+    }
+    *event_count = written;
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_sched_yield() -> Errno {
+    time::yield_now();
+    SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn __imported_wasi_snapshot_preview1_proc_exit(status: u32) -> ! {
+    flush_pending();
+    if status != 0 {
+        log::error(&format!("guest exited with status {status}"));
+    }
+    std::arch::wasm32::unreachable()
+}
@@ -0,0 +1,128 @@
+//! Helpers for reading and writing the little-endian binary layouts we exchange with the host.
+
+use crate::framing::{decode_frame, write_frame};
+use crate::{CodecError, Result, SdkError};
+
+/// A cursor over a byte slice received from the host. Every read is bounds-checked and returns
+/// `SdkError::InvalidPayload` if the data runs out early.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(SdkError::InvalidPayload);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_i32(&mut self) -> Result<i32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(i32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        let bytes = self.read_bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Returns everything that hasn't been read yet.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Reads a u32 length followed by that many bytes; see `crate::framing`.
+    pub(crate) fn read_prefixed(&mut self) -> Result<&'a [u8]> {
+        let (data, rest) = decode_frame(self.bytes)?;
+        self.bytes = rest;
+        Ok(data)
+    }
+
+    /// Reads a u32 length followed by that many bytes of UTF-8.
+    pub(crate) fn read_str(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.read_prefixed()?).map_err(|_| SdkError::InvalidPayload)
+    }
+}
+
+/// Builds up a byte buffer in the same layout `Reader` consumes.
+#[derive(Default)]
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes `count` as a u16, failing with `SdkError::Encode` instead of truncating it if there
+    /// are more `what` than that can hold.
+    pub(crate) fn write_u16_count(&mut self, count: usize, what: &str) -> Result<()> {
+        let count = u16::try_from(count).map_err(|_| {
+            SdkError::Encode(CodecError::new(format!(
+                "more than {} {what} in one request",
+                u16::MAX
+            )))
+        })?;
+        self.write_u16(count);
+        Ok(())
+    }
+
+    pub(crate) fn write_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Writes a u32 length followed by the bytes themselves; see `crate::framing`.
+    pub(crate) fn write_prefixed(&mut self, bytes: &[u8]) {
+        write_frame(&mut self.bytes, bytes);
+    }
+
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_prefixed(value.as_bytes());
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}